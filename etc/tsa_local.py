@@ -0,0 +1,117 @@
+#!/usr/bin/env python3
+# Stand-in RFC 3161 TSA for testing `hush timestamp` (plain http, answers with `openssl ts -reply`).
+#
+#   python3 etc/tsa_local.py <work_dir> [port]
+#
+# The first run makes a test CA and a TSA certificate in <work_dir> (ca.pem is the one to give
+# to --tsa_cert). Then it answers on http://127.0.0.1:<port>/ (default 3161).
+# Only for tests: the keys are not protected and the clock is the one of this machine.
+
+import http.server
+import os
+import subprocess
+import sys
+import tempfile
+
+CONFIG = """
+[ req ]
+distinguished_name = dn
+prompt = no
+[ dn ]
+CN = {cn}
+[ ca_ext ]
+basicConstraints = critical, CA:true
+keyUsage = critical, keyCertSign, cRLSign
+subjectKeyIdentifier = hash
+[ tsa_ext ]
+basicConstraints = critical, CA:false
+keyUsage = critical, digitalSignature, nonRepudiation
+extendedKeyUsage = critical, timeStamping
+subjectKeyIdentifier = hash
+authorityKeyIdentifier = keyid
+[ tsa ]
+default_tsa = tsa_config
+[ tsa_config ]
+dir = {dir}
+serial = $dir/serial
+signer_cert = $dir/tsa.pem
+certs = $dir/ca.pem
+signer_key = $dir/tsa.key
+signer_digest = sha256
+default_policy = 1.2.3.4.1
+digests = sha256, sha384, sha512
+accuracy = secs:1
+ess_cert_id_alg = sha256
+ess_cert_id_chain = no
+"""
+
+
+def openssl(*args):
+    subprocess.run(["openssl", *args], check=True, capture_output=True)
+
+
+def prepara(dir_trabalho):
+    config = os.path.join(dir_trabalho, "tsa.cnf")
+    with open(config, "w") as arquivo:
+        arquivo.write(CONFIG.format(cn="hush test TSA", dir=dir_trabalho))
+    if os.path.exists(os.path.join(dir_trabalho, "tsa.pem")):
+        return config
+
+    def caminho(nome):
+        return os.path.join(dir_trabalho, nome)
+
+    ca_config = caminho("ca.cnf")
+    with open(ca_config, "w") as arquivo:
+        arquivo.write(CONFIG.format(cn="hush test CA", dir=dir_trabalho))
+    openssl("req", "-x509", "-newkey", "rsa:2048", "-nodes", "-days", "3650",
+            "-config", ca_config, "-extensions", "ca_ext",
+            "-keyout", caminho("ca.key"), "-out", caminho("ca.pem"))
+    openssl("req", "-new", "-newkey", "rsa:2048", "-nodes", "-config", config,
+            "-keyout", caminho("tsa.key"), "-out", caminho("tsa.csr"))
+    openssl("x509", "-req", "-days", "3650", "-in", caminho("tsa.csr"),
+            "-CA", caminho("ca.pem"), "-CAkey", caminho("ca.key"), "-CAcreateserial",
+            "-extfile", config, "-extensions", "tsa_ext", "-out", caminho("tsa.pem"))
+    with open(caminho("serial"), "w") as arquivo:
+        arquivo.write("01\n")
+    return config
+
+
+class TSA(http.server.BaseHTTPRequestHandler):
+    config = None
+
+    def do_POST(self):
+        pedido = self.rfile.read(int(self.headers.get("Content-Length", 0)))
+        with tempfile.TemporaryDirectory() as tmp:
+            entrada = os.path.join(tmp, "req.tsq")
+            saida = os.path.join(tmp, "resp.tsr")
+            with open(entrada, "wb") as arquivo:
+                arquivo.write(pedido)
+            resultado = subprocess.run(
+                ["openssl", "ts", "-reply", "-config", self.config,
+                 "-queryfile", entrada, "-out", saida],
+                capture_output=True)
+            if resultado.returncode != 0:
+                self.send_error(400, resultado.stderr.decode(errors="replace")[:200])
+                return
+            with open(saida, "rb") as arquivo:
+                resposta = arquivo.read()
+        self.send_response(200)
+        self.send_header("Content-Type", "application/timestamp-reply")
+        self.send_header("Content-Length", str(len(resposta)))
+        self.end_headers()
+        self.wfile.write(resposta)
+
+
+def main():
+    if len(sys.argv) < 2:
+        sys.exit("Use: tsa_local.py <work_dir> [port]")
+    dir_trabalho = os.path.abspath(sys.argv[1])
+    porta = int(sys.argv[2]) if len(sys.argv) > 2 else 3161
+    os.makedirs(dir_trabalho, exist_ok=True)
+    TSA.config = prepara(dir_trabalho)
+    print("TSA on http://127.0.0.1:{}/ CA: {}".format(porta, os.path.join(dir_trabalho, "ca.pem")))
+    http.server.HTTPServer(("127.0.0.1", porta), TSA).serve_forever()
+
+
+if __name__ == "__main__":
+    main()
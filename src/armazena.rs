@@ -0,0 +1,709 @@
+// Compressão e cifragem das partes do split (--compress, --encrypt).
+//
+// Ordem na escrita: zstd, depois AES-256-GCM; no rebuild, o contrário.
+// A cifragem é feita em registros de REGISTRO_CIFRA bytes de texto, cada um com a sua tag de
+// autenticação. O nonce de cada registro é o índice da parte (8 bytes) e o número do registro
+// (4 bytes), com o bit mais alto marcando o último registro: parte trocada de lugar, registro
+// fora de ordem ou parte cortada não decifram. O último registro é sempre menor que
+// REGISTRO_CIFRA (pode ter só a tag), então registro cheio nunca é o último.
+// A chave é derivada com um sal aleatório por split, gravado no manifest: Argon2id para frase
+// secreta, HKDF-SHA256 para arquivo de chave. Assim a mesma frase ou arquivo de chave nunca
+// repete chave e nonce entre dois splits.
+// O manifest guarda o sha256 do texto (o hash do bloco) e o sha256 do que foi gravado: dá para
+// conferir as partes guardadas sem a chave, e o arquivo reconstruído com ela.
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+const REGISTRO_CIFRA: usize = 1024 * 1024;
+const TAMANHO_TAG: usize = 16;
+const ULTIMO_REGISTRO: u32 = 1 << 31;
+const NIVEL_ZSTD_PADRAO: i32 = 3;
+const CIFRA: &str = "aes-256-gcm";
+const KDF_FRASE: &str = "argon2id";
+const KDF_ARQUIVO: &str = "hkdf-sha256";
+// Argon2id: memória em KiB, iterações e paralelismo da frase secreta
+const ARGON2_MEMORIA: u32 = 64 * 1024;
+const ARGON2_ITERACOES: u32 = 3;
+const ARGON2_PARALELISMO: u32 = 1;
+// Manifest com custo absurdo não trava o rebuild
+const ARGON2_MEMORIA_MAX: u32 = 4 * 1024 * 1024;
+const TAMANHO_MINIMO_CHAVE: usize = 32;
+
+// Como as partes foram gravadas. Vai no manifest do split como "storage".
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Armazenamento {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<Cifragem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cifragem {
+    pub cipher: String,
+    pub record_size: usize,
+    pub kdf: String,
+    pub salt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub argon2_m_cost: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub argon2_t_cost: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub argon2_p_cost: Option<u32>,
+    // sha256 de um rótulo e da chave: frase ou arquivo de chave errado é avisado antes das partes
+    pub key_check: String,
+}
+
+// De onde vem o segredo: --passphrase_file, --passphrase_env ou --key_file
+#[derive(Debug, Clone)]
+pub enum Segredo {
+    Frase(Vec<u8>),
+    ArquivoChave(Vec<u8>),
+}
+
+impl Segredo {
+    pub fn de_frase_arquivo(caminho: &str) -> Result<Segredo, Box<dyn Error>> {
+        let texto = fs::read(caminho).map_err(|e| format!("{}: {}", caminho, e))?;
+        // Só a primeira linha, sem o fim de linha do editor
+        let frase = texto.split(|b| *b == b'\n').next().unwrap_or(&[]);
+        let frase = frase.strip_suffix(b"\r").unwrap_or(frase);
+        Segredo::frase(frase.to_vec())
+    }
+
+    pub fn de_frase_env(variavel: &str) -> Result<Segredo, Box<dyn Error>> {
+        let frase =
+            env::var(variavel).map_err(|_| format!("environment variable {} not set", variavel))?;
+        Segredo::frase(frase.into_bytes())
+    }
+
+    fn frase(frase: Vec<u8>) -> Result<Segredo, Box<dyn Error>> {
+        if frase.is_empty() {
+            return Err("empty passphrase".into());
+        }
+        Ok(Segredo::Frase(frase))
+    }
+
+    pub fn de_arquivo_chave(caminho: &str) -> Result<Segredo, Box<dyn Error>> {
+        let chave = fs::read(caminho).map_err(|e| format!("{}: {}", caminho, e))?;
+        if chave.len() < TAMANHO_MINIMO_CHAVE {
+            return Err(format!(
+                "{}: key file must have at least {} bytes (ex: head -c 32 /dev/urandom)",
+                caminho, TAMANHO_MINIMO_CHAVE
+            )
+            .into());
+        }
+        Ok(Segredo::ArquivoChave(chave))
+    }
+}
+
+// Compressão e cifra prontas para gravar ou ler partes
+pub struct Embalagem {
+    nivel_zstd: Option<i32>,
+    cifra: Option<Arc<Aes256Gcm>>,
+}
+
+// "zstd" ou "zstd:<nível>"
+pub fn parse_compressao(valor: &str) -> Result<i32, Box<dyn Error>> {
+    match valor.split_once(':') {
+        None if valor == "zstd" => Ok(NIVEL_ZSTD_PADRAO),
+        Some(("zstd", nivel)) => {
+            let nivel: i32 = nivel
+                .parse()
+                .map_err(|_| format!("invalid zstd level: {}", nivel))?;
+            if !zstd::compression_level_range().contains(&nivel) {
+                return Err(format!("invalid zstd level: {}", nivel).into());
+            }
+            Ok(nivel)
+        }
+        _ => Err(format!("unknown compression: {} (use zstd or zstd:<level>)", valor).into()),
+    }
+}
+
+fn verificacao_chave(chave: &[u8; 32]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"hush-split key check");
+    hasher.update(chave);
+    format!("{:x}", hasher.finalize())
+}
+
+fn deriva_chave(segredo: &Segredo, cifragem: &Cifragem) -> Result<[u8; 32], Box<dyn Error>> {
+    let sal = hex::decode(&cifragem.salt).map_err(|_| "invalid salt in split manifest")?;
+    let mut chave = [0u8; 32];
+    match (segredo, cifragem.kdf.as_str()) {
+        (Segredo::Frase(frase), KDF_FRASE) => {
+            let m_cost = cifragem.argon2_m_cost.unwrap_or(ARGON2_MEMORIA);
+            if m_cost > ARGON2_MEMORIA_MAX {
+                return Err(format!("argon2 memory cost too high: {} KiB", m_cost).into());
+            }
+            let params = Params::new(
+                m_cost,
+                cifragem.argon2_t_cost.unwrap_or(ARGON2_ITERACOES),
+                cifragem.argon2_p_cost.unwrap_or(ARGON2_PARALELISMO),
+                Some(chave.len()),
+            )
+            .map_err(|e| format!("argon2: {}", e))?;
+            Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                .hash_password_into(frase, &sal, &mut chave)
+                .map_err(|e| format!("argon2: {}", e))?;
+        }
+        (Segredo::ArquivoChave(material), KDF_ARQUIVO) => {
+            Hkdf::<Sha256>::new(Some(&sal), material)
+                .expand(b"hush-split part key", &mut chave)
+                .map_err(|e| format!("hkdf: {}", e))?;
+        }
+        (Segredo::Frase(_), kdf) => {
+            return Err(format!("parts encrypted with {}: use --key_file", kdf).into())
+        }
+        (Segredo::ArquivoChave(_), kdf) => {
+            return Err(format!(
+                "parts encrypted with {}: use --passphrase_file or --passphrase_env",
+                kdf
+            )
+            .into())
+        }
+    }
+    Ok(chave)
+}
+
+impl Embalagem {
+    // Para o split: nova cifragem (sal novo) se tiver segredo. Retorna também o que vai no manifest.
+    pub fn nova(
+        nivel_zstd: Option<i32>,
+        segredo: Option<&Segredo>,
+    ) -> Result<(Embalagem, Armazenamento), Box<dyn Error>> {
+        let mut armazenamento = Armazenamento {
+            compression: nivel_zstd.map(|_| String::from("zstd")),
+            encryption: None,
+        };
+        let mut cifra = None;
+        if let Some(segredo) = segredo {
+            let mut sal = [0u8; 16];
+            OsRng.fill_bytes(&mut sal);
+            let mut cifragem = Cifragem {
+                cipher: CIFRA.to_string(),
+                record_size: REGISTRO_CIFRA,
+                kdf: String::new(),
+                salt: hex::encode(sal),
+                argon2_m_cost: None,
+                argon2_t_cost: None,
+                argon2_p_cost: None,
+                key_check: String::new(),
+            };
+            match segredo {
+                Segredo::Frase(_) => {
+                    cifragem.kdf = KDF_FRASE.to_string();
+                    cifragem.argon2_m_cost = Some(ARGON2_MEMORIA);
+                    cifragem.argon2_t_cost = Some(ARGON2_ITERACOES);
+                    cifragem.argon2_p_cost = Some(ARGON2_PARALELISMO);
+                }
+                Segredo::ArquivoChave(_) => cifragem.kdf = KDF_ARQUIVO.to_string(),
+            }
+            let chave = deriva_chave(segredo, &cifragem)?;
+            cifragem.key_check = verificacao_chave(&chave);
+            cifra = Some(Arc::new(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(
+                &chave,
+            ))));
+            armazenamento.encryption = Some(cifragem);
+        }
+        Ok((Embalagem { nivel_zstd, cifra }, armazenamento))
+    }
+
+    // Para o rebuild: a embalagem descrita no manifest, com o segredo informado
+    pub fn do_manifest(
+        armazenamento: &Armazenamento,
+        segredo: Option<&Segredo>,
+    ) -> Result<Embalagem, Box<dyn Error>> {
+        let nivel_zstd = match armazenamento.compression.as_deref() {
+            None => None,
+            Some("zstd") => Some(NIVEL_ZSTD_PADRAO),
+            Some(outra) => return Err(format!("unknown compression in manifest: {}", outra).into()),
+        };
+        let cifra = match &armazenamento.encryption {
+            None => None,
+            Some(cifragem) => {
+                if cifragem.cipher != CIFRA || cifragem.record_size != REGISTRO_CIFRA {
+                    return Err(format!(
+                        "unsupported encryption in manifest: {} with records of {} bytes",
+                        cifragem.cipher, cifragem.record_size
+                    )
+                    .into());
+                }
+                let segredo = segredo.ok_or(
+                    "parts are encrypted: use --passphrase_file, --passphrase_env or --key_file",
+                )?;
+                let chave = deriva_chave(segredo, cifragem)?;
+                if verificacao_chave(&chave) != cifragem.key_check {
+                    return Err("wrong passphrase or key file for these parts".into());
+                }
+                Some(Arc::new(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(
+                    &chave,
+                ))))
+            }
+        };
+        Ok(Embalagem { nivel_zstd, cifra })
+    }
+
+    pub fn ativa(&self) -> bool {
+        self.nivel_zstd.is_some() || self.cifra.is_some()
+    }
+
+    // Escrita de uma parte: o que entra é o texto, o que vai para o arquivo é comprimido e cifrado
+    pub fn escrita(&self, file: File, index: u64) -> io::Result<Box<dyn EscritaParte>> {
+        let cifrada = EscritaCifrada {
+            cifra: self.cifra.clone(),
+            index,
+            registro: 0,
+            pendente: Vec::new(),
+            destino: EscritaGuardada {
+                file: BufWriter::new(file),
+                hasher: Sha256::new(),
+                bytes: 0,
+            },
+        };
+        Ok(match self.nivel_zstd {
+            Some(nivel) => Box::new(EscritaEmbalada::Zstd(zstd::stream::write::Encoder::new(
+                cifrada, nivel,
+            )?)),
+            None => Box::new(EscritaEmbalada::Direta(cifrada)),
+        })
+    }
+
+    // Lê a parte gravada e entrega o texto, em pedaços, para `destino`.
+    // Retorna o tamanho e o sha256 do que estava gravado (lido até o fim, mesmo se sobrar).
+    pub fn le_parte(
+        &self,
+        caminho: &Path,
+        index: u64,
+        destino: &mut dyn FnMut(&[u8]) -> io::Result<()>,
+    ) -> io::Result<(u64, String)> {
+        let mut decifrada = LeituraCifrada {
+            cifra: self.cifra.clone(),
+            index,
+            registro: 0,
+            texto: Vec::new(),
+            posicao: 0,
+            terminou: false,
+            origem: LeituraGuardada {
+                file: File::open(caminho)?,
+                hasher: Sha256::new(),
+                bytes: 0,
+            },
+        };
+        let mut buffer = vec![0u8; REGISTRO_CIFRA];
+        if self.nivel_zstd.is_some() {
+            let mut decoder = zstd::stream::read::Decoder::new(&mut decifrada)?;
+            copia(&mut decoder, &mut buffer, destino)?;
+        } else {
+            copia(&mut decifrada, &mut buffer, destino)?;
+        }
+        // Bytes depois do fim do zstd entram no sha256 do que estava gravado
+        io::copy(&mut decifrada.origem, &mut io::sink())?;
+        let origem = decifrada.origem;
+        Ok((origem.bytes, format!("{:x}", origem.hasher.finalize())))
+    }
+}
+
+fn copia(
+    leitura: &mut dyn Read,
+    buffer: &mut [u8],
+    destino: &mut dyn FnMut(&[u8]) -> io::Result<()>,
+) -> io::Result<()> {
+    loop {
+        let n = match leitura.read(buffer) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        destino(&buffer[..n])?;
+    }
+}
+
+fn nonce(index: u64, registro: u32, ultimo: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..8].copy_from_slice(&index.to_be_bytes());
+    let registro = if ultimo {
+        registro | ULTIMO_REGISTRO
+    } else {
+        registro
+    };
+    nonce[8..].copy_from_slice(&registro.to_be_bytes());
+    nonce
+}
+
+// Tamanho e sha256 do que foi gravado, quando diferente do texto (com embalagem)
+pub type Guardado = Option<(u64, String)>;
+
+// Parte sendo gravada. `termina` fecha a compressão e a cifra e grava tudo no disco.
+pub trait EscritaParte: Write + Send {
+    fn termina(self: Box<Self>) -> io::Result<Guardado>;
+}
+
+// Sem compressão nem cifra: o arquivo da parte é o texto
+impl EscritaParte for File {
+    fn termina(self: Box<Self>) -> io::Result<Guardado> {
+        self.sync_all()?;
+        Ok(None)
+    }
+}
+
+enum EscritaEmbalada {
+    Zstd(zstd::stream::write::Encoder<'static, EscritaCifrada>),
+    Direta(EscritaCifrada),
+}
+
+impl Write for EscritaEmbalada {
+    fn write(&mut self, dados: &[u8]) -> io::Result<usize> {
+        match self {
+            EscritaEmbalada::Zstd(encoder) => encoder.write(dados),
+            EscritaEmbalada::Direta(cifrada) => cifrada.write(dados),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            EscritaEmbalada::Zstd(encoder) => encoder.flush(),
+            EscritaEmbalada::Direta(cifrada) => cifrada.flush(),
+        }
+    }
+}
+
+impl EscritaParte for EscritaEmbalada {
+    fn termina(self: Box<Self>) -> io::Result<Guardado> {
+        let cifrada = match *self {
+            EscritaEmbalada::Zstd(encoder) => encoder.finish()?,
+            EscritaEmbalada::Direta(cifrada) => cifrada,
+        };
+        let destino = cifrada.termina()?;
+        let file = destino.file.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        Ok(Some((
+            destino.bytes,
+            format!("{:x}", destino.hasher.finalize()),
+        )))
+    }
+}
+
+// Cifra em registros; sem cifra, passa direto
+struct EscritaCifrada {
+    cifra: Option<Arc<Aes256Gcm>>,
+    index: u64,
+    registro: u32,
+    pendente: Vec<u8>,
+    destino: EscritaGuardada,
+}
+
+impl EscritaCifrada {
+    fn grava_registro(&mut self, ultimo: bool) -> io::Result<()> {
+        let Some(cifra) = &self.cifra else {
+            return Ok(());
+        };
+        let texto = if ultimo {
+            std::mem::take(&mut self.pendente)
+        } else {
+            self.pendente.drain(..REGISTRO_CIFRA).collect()
+        };
+        let nonce = nonce(self.index, self.registro, ultimo);
+        let cifrado = cifra
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &texto,
+                    aad: b"",
+                },
+            )
+            .map_err(|_| io::Error::other("encryption failed"))?;
+        self.registro = self
+            .registro
+            .checked_add(1)
+            .filter(|registro| registro & ULTIMO_REGISTRO == 0)
+            .ok_or_else(|| io::Error::other("part too big to encrypt"))?;
+        self.destino.write_all(&cifrado)
+    }
+
+    fn termina(mut self) -> io::Result<EscritaGuardada> {
+        if self.cifra.is_some() {
+            self.grava_registro(true)?;
+        }
+        self.destino.flush()?;
+        Ok(self.destino)
+    }
+}
+
+impl Write for EscritaCifrada {
+    fn write(&mut self, dados: &[u8]) -> io::Result<usize> {
+        if self.cifra.is_none() {
+            return self.destino.write(dados);
+        }
+        self.pendente.extend_from_slice(dados);
+        // Registro cheio já vai: o último (no termina) fica sempre menor, mesmo vazio
+        while self.pendente.len() >= REGISTRO_CIFRA {
+            self.grava_registro(false)?;
+        }
+        Ok(dados.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.destino.flush()
+    }
+}
+
+// O arquivo da parte, com o tamanho e o sha256 do que foi gravado
+struct EscritaGuardada {
+    file: BufWriter<File>,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl Write for EscritaGuardada {
+    fn write(&mut self, dados: &[u8]) -> io::Result<usize> {
+        let n = self.file.write(dados)?;
+        self.hasher.update(&dados[..n]);
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+struct LeituraGuardada {
+    file: File,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl Read for LeituraGuardada {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let n = self.file.read(buffer)?;
+        self.hasher.update(&buffer[..n]);
+        self.bytes += n as u64;
+        Ok(n)
+    }
+}
+
+struct LeituraCifrada {
+    cifra: Option<Arc<Aes256Gcm>>,
+    index: u64,
+    registro: u32,
+    texto: Vec<u8>,
+    posicao: usize,
+    terminou: bool,
+    origem: LeituraGuardada,
+}
+
+impl LeituraCifrada {
+    fn le_registro(&mut self, cifra: &Aes256Gcm) -> io::Result<()> {
+        let mut registro = vec![0u8; REGISTRO_CIFRA + TAMANHO_TAG];
+        let mut lido = 0;
+        while lido < registro.len() {
+            match self.origem.read(&mut registro[lido..]) {
+                Ok(0) => break,
+                Ok(n) => lido += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        if lido < TAMANHO_TAG {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "encrypted part truncated",
+            ));
+        }
+        let ultimo = lido < registro.len();
+        let nonce = nonce(self.index, self.registro, ultimo);
+        self.texto = cifra
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &registro[..lido],
+                    aad: b"",
+                },
+            )
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("record {} failed authentication", self.registro),
+                )
+            })?;
+        self.posicao = 0;
+        self.registro = self.registro.wrapping_add(1);
+        self.terminou = ultimo;
+        Ok(())
+    }
+}
+
+impl Read for LeituraCifrada {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let Some(cifra) = self.cifra.clone() else {
+            return self.origem.read(buffer);
+        };
+        while self.posicao == self.texto.len() {
+            if self.terminou {
+                return Ok(0);
+            }
+            self.le_registro(&cifra)?;
+        }
+        let n = buffer.len().min(self.texto.len() - self.posicao);
+        buffer[..n].copy_from_slice(&self.texto[self.posicao..self.posicao + n]);
+        self.posicao += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testes::PastaTeste;
+
+    // Dois registros cheios e um último pela metade
+    const TAMANHO_TEXTO: usize = 2 * REGISTRO_CIFRA + REGISTRO_CIFRA / 2;
+    const TAMANHO_REGISTRO: usize = REGISTRO_CIFRA + TAMANHO_TAG;
+
+    fn texto(index: u64) -> Vec<u8> {
+        (0..TAMANHO_TEXTO)
+            .map(|i| ((i as u64 * 13 + index) % 251) as u8)
+            .collect()
+    }
+
+    fn segredo(byte: u8) -> Segredo {
+        Segredo::ArquivoChave(vec![byte; TAMANHO_MINIMO_CHAVE])
+    }
+
+    fn grava(embalagem: &Embalagem, caminho: &Path, index: u64) {
+        let mut escrita = embalagem
+            .escrita(File::create(caminho).unwrap(), index)
+            .unwrap();
+        escrita.write_all(&texto(index)).unwrap();
+        escrita.termina().unwrap();
+    }
+
+    fn le(embalagem: &Embalagem, caminho: &Path, index: u64) -> io::Result<Vec<u8>> {
+        let mut lido = Vec::new();
+        embalagem.le_parte(caminho, index, &mut |dados| {
+            lido.extend_from_slice(dados);
+            Ok(())
+        })?;
+        Ok(lido)
+    }
+
+    fn erro_de_dados(resultado: io::Result<Vec<u8>>, mensagem: &str) {
+        let erro = resultado.expect_err("tampered part must not decrypt");
+        assert_eq!(erro.kind(), io::ErrorKind::InvalidData);
+        assert!(erro.to_string().contains(mensagem), "{}", erro);
+    }
+
+    #[test]
+    fn cifra_e_decifra() {
+        let dir = PastaTeste::nova("armazena_ida_e_volta");
+        for nivel_zstd in [None, Some(NIVEL_ZSTD_PADRAO)] {
+            let (embalagem, armazenamento) =
+                Embalagem::nova(nivel_zstd, Some(&segredo(1))).unwrap();
+            let caminho = dir.join("part_0");
+            grava(&embalagem, &caminho, 0);
+            if nivel_zstd.is_none() {
+                let tamanho = fs::metadata(&caminho).unwrap().len() as usize;
+                assert_eq!(tamanho, TAMANHO_TEXTO + 3 * TAMANHO_TAG);
+            }
+            // No rebuild, a embalagem vem do manifest
+            let lida = Embalagem::do_manifest(&armazenamento, Some(&segredo(1))).unwrap();
+            assert_eq!(le(&lida, &caminho, 0).unwrap(), texto(0));
+        }
+    }
+
+    #[test]
+    fn parte_cortada_nao_decifra() {
+        let dir = PastaTeste::nova("armazena_cortada");
+        let (embalagem, _) = Embalagem::nova(None, Some(&segredo(1))).unwrap();
+        let caminho = dir.join("part_0");
+        grava(&embalagem, &caminho, 0);
+        let gravado = fs::read(&caminho).unwrap();
+
+        // Sem o último registro: o segundo, cheio, não pode ser o último
+        fs::write(&caminho, &gravado[..2 * TAMANHO_REGISTRO]).unwrap();
+        erro_de_dados(le(&embalagem, &caminho, 0), "truncated");
+        // Cortada no meio do último registro
+        fs::write(&caminho, &gravado[..gravado.len() - 5]).unwrap();
+        erro_de_dados(
+            le(&embalagem, &caminho, 0),
+            "record 2 failed authentication",
+        );
+        // Cortada dentro do primeiro registro: lido como último, o nonce não bate
+        fs::write(&caminho, &gravado[..TAMANHO_REGISTRO - 1]).unwrap();
+        erro_de_dados(
+            le(&embalagem, &caminho, 0),
+            "record 0 failed authentication",
+        );
+    }
+
+    #[test]
+    fn parte_ou_registro_fora_de_ordem_nao_decifra() {
+        let dir = PastaTeste::nova("armazena_fora_de_ordem");
+        let (embalagem, _) = Embalagem::nova(None, Some(&segredo(1))).unwrap();
+        let (parte_0, parte_1) = (dir.join("part_0"), dir.join("part_1"));
+        grava(&embalagem, &parte_0, 0);
+        grava(&embalagem, &parte_1, 1);
+
+        // Parte 1 no lugar da 0
+        erro_de_dados(
+            le(&embalagem, &parte_1, 0),
+            "record 0 failed authentication",
+        );
+
+        // Dois registros trocados dentro da parte
+        let mut gravado = fs::read(&parte_0).unwrap();
+        let primeiro = gravado[..TAMANHO_REGISTRO].to_vec();
+        gravado.copy_within(TAMANHO_REGISTRO..2 * TAMANHO_REGISTRO, 0);
+        gravado[TAMANHO_REGISTRO..2 * TAMANHO_REGISTRO].copy_from_slice(&primeiro);
+        fs::write(&parte_0, &gravado).unwrap();
+        erro_de_dados(
+            le(&embalagem, &parte_0, 0),
+            "record 0 failed authentication",
+        );
+
+        // Um bit mudado
+        grava(&embalagem, &parte_0, 0);
+        let mut gravado = fs::read(&parte_0).unwrap();
+        gravado[TAMANHO_REGISTRO + 100] ^= 1;
+        fs::write(&parte_0, &gravado).unwrap();
+        erro_de_dados(
+            le(&embalagem, &parte_0, 0),
+            "record 1 failed authentication",
+        );
+    }
+
+    #[test]
+    fn chave_errada() {
+        let dir = PastaTeste::nova("armazena_chave_errada");
+        let (embalagem, armazenamento) = Embalagem::nova(None, Some(&segredo(1))).unwrap();
+        let caminho = dir.join("part_0");
+        grava(&embalagem, &caminho, 0);
+
+        // Avisada pelo key_check, antes de ler as partes
+        let erro = Embalagem::do_manifest(&armazenamento, Some(&segredo(2)))
+            .err()
+            .expect("wrong key must be refused");
+        assert!(erro.to_string().contains("wrong passphrase or key file"));
+        assert!(Embalagem::do_manifest(&armazenamento, None).is_err());
+
+        // Com o key_check da chave errada, a autenticação dos registros é que falha
+        let mut trocado = armazenamento.clone();
+        let cifragem = trocado.encryption.as_mut().unwrap();
+        cifragem.key_check = verificacao_chave(&deriva_chave(&segredo(2), cifragem).unwrap());
+        let errada = Embalagem::do_manifest(&trocado, Some(&segredo(2))).unwrap();
+        erro_de_dados(le(&errada, &caminho, 0), "record 0 failed authentication");
+    }
+}
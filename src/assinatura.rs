@@ -0,0 +1,189 @@
+// Assinatura destacada de manifests com chaves Ed25519 no formato do OpenSSH.
+//
+// A assinatura é um SSHSIG ("-----BEGIN SSH SIGNATURE-----", o mesmo de `ssh-keygen -Y sign`),
+// com namespace "hush-manifest", gravada ao lado do manifest (<manifest>.sig).
+// O que se assina é a forma canônica do manifest, e não o arquivo:
+//   - fim de linha sem "\r", linhas vazias fora;
+//   - cabeçalho: as linhas começando com '#' antes da primeira entrada, na ordem do arquivo;
+//   - o resto das linhas em ordem de bytes, cada linha terminada com "\n".
+// Assim o manifest do gen, que sai em ordem diferente a cada execução, pode ser reordenado
+// (sort) sem perder a assinatura, mas mudar, tirar ou juntar linha invalida.
+// Chaves confiáveis: um arquivo com uma chave pública por linha, como um .pub, authorized_keys
+// ou allowed_signers (o primeiro campo, o nome, é ignorado).
+
+use ssh_key::rand_core::OsRng;
+use ssh_key::{Algorithm, HashAlg, LineEnding, PrivateKey, PublicKey, SshSig};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+const NAMESPACE: &str = "hush-manifest";
+
+// Onde fica a assinatura, sem --sig
+pub fn caminho_assinatura(manifest: &str) -> String {
+    format!("{}.sig", manifest)
+}
+
+// Forma canônica do manifest, a que é assinada
+pub fn canonico(manifest: &str) -> io::Result<Vec<u8>> {
+    let reader = BufReader::new(File::open(manifest)?);
+    Ok(forma_canonica(reader.lines().collect::<io::Result<_>>()?))
+}
+
+// Forma canônica das linhas de um manifest (ex: as que o gen escreveu)
+pub fn forma_canonica(linhas_lidas: Vec<String>) -> Vec<u8> {
+    let mut cabecalho = Vec::new();
+    let mut linhas = Vec::new();
+    for linha in linhas_lidas {
+        let linha = linha.strip_suffix('\r').unwrap_or(&linha);
+        if linha.trim().is_empty() {
+            continue;
+        }
+        if linhas.is_empty() && linha.starts_with('#') {
+            cabecalho.push(linha.to_string());
+        } else {
+            linhas.push(linha.to_string());
+        }
+    }
+    linhas.sort();
+
+    let mut texto = Vec::new();
+    for linha in cabecalho.iter().chain(linhas.iter()) {
+        texto.extend_from_slice(linha.as_bytes());
+        texto.push(b'\n');
+    }
+    texto
+}
+
+// Par de chaves novo: <caminho> (privada) e <caminho>.pub. Volta a impressão digital.
+pub fn gera_chaves(
+    caminho: &str,
+    frase: Option<&[u8]>,
+    comentario: &str,
+) -> Result<String, Box<dyn Error>> {
+    let caminho_pub = format!("{}.pub", caminho);
+    for existente in [caminho, caminho_pub.as_str()] {
+        if Path::new(existente).exists() {
+            return Err(format!("{} already exists", existente).into());
+        }
+    }
+
+    let mut chave = PrivateKey::random(&mut OsRng, Algorithm::Ed25519)?;
+    chave.set_comment(comentario);
+    let publica = chave.public_key().clone();
+    if let Some(frase) = frase {
+        chave = chave.encrypt(&mut OsRng, frase)?;
+    }
+    chave.write_openssh_file(Path::new(caminho), LineEnding::LF)?;
+    publica.write_openssh_file(Path::new(&caminho_pub))?;
+    Ok(publica.fingerprint(HashAlg::Sha256).to_string())
+}
+
+// Assina o manifest com a chave privada (cifrada: precisa da frase). Volta a impressão digital.
+pub fn assina(
+    manifest: &str,
+    caminho_chave: &str,
+    frase: Option<&[u8]>,
+    caminho_sig: &str,
+) -> Result<String, Box<dyn Error>> {
+    let mut chave = PrivateKey::read_openssh_file(Path::new(caminho_chave))
+        .map_err(|e| format!("{}: {}", caminho_chave, e))?;
+    if chave.algorithm() != Algorithm::Ed25519 {
+        return Err(format!(
+            "{}: {} key, only Ed25519 keys are supported",
+            caminho_chave,
+            chave.algorithm()
+        )
+        .into());
+    }
+    if chave.is_encrypted() {
+        let Some(frase) = frase else {
+            return Err(format!(
+                "{}: key is encrypted, use --passphrase_file or --passphrase_env",
+                caminho_chave
+            )
+            .into());
+        };
+        chave = chave
+            .decrypt(frase)
+            .map_err(|_| format!("{}: wrong passphrase", caminho_chave))?;
+    }
+
+    let assinatura = chave.sign(NAMESPACE, HashAlg::Sha512, &canonico(manifest)?)?;
+    fs::write(caminho_sig, assinatura.to_pem(LineEnding::LF)?)?;
+    Ok(chave.public_key().fingerprint(HashAlg::Sha256).to_string())
+}
+
+// Chaves públicas Ed25519 de um arquivo, uma por linha
+pub fn le_chaves_confiaveis(caminho: &str) -> Result<Vec<PublicKey>, Box<dyn Error>> {
+    let texto = fs::read_to_string(caminho).map_err(|e| format!("{}: {}", caminho, e))?;
+    let mut chaves = Vec::new();
+    for (n_linha, linha) in texto.lines().enumerate() {
+        let linha = linha.trim();
+        if linha.is_empty() || linha.starts_with('#') {
+            continue;
+        }
+        // allowed_signers: "<nome> ssh-ed25519 AAAA..."
+        let chave = PublicKey::from_openssh(linha).or_else(|e| match linha.split_once(' ') {
+            Some((_, resto)) => PublicKey::from_openssh(resto.trim_start()),
+            None => Err(e),
+        });
+        match chave {
+            Ok(chave) if chave.algorithm() == Algorithm::Ed25519 => chaves.push(chave),
+            Ok(chave) => {
+                return Err(format!(
+                    "{}: line {}: {} key, only Ed25519 keys are supported",
+                    caminho,
+                    n_linha + 1,
+                    chave.algorithm()
+                )
+                .into())
+            }
+            Err(e) => {
+                return Err(format!("{}: line {}: {}", caminho, n_linha + 1, e).into());
+            }
+        }
+    }
+    if chaves.is_empty() {
+        return Err(format!("{}: no public key", caminho).into());
+    }
+    Ok(chaves)
+}
+
+// Confere a assinatura do manifest contra as chaves confiáveis.
+// Volta a chave que assinou: "<impressão digital> <comentário>".
+pub fn confere(
+    manifest: &str,
+    caminho_sig: &str,
+    caminho_chaves: &str,
+) -> Result<String, Box<dyn Error>> {
+    let chaves = le_chaves_confiaveis(caminho_chaves)?;
+    let pem = fs::read_to_string(caminho_sig).map_err(|e| format!("{}: {}", caminho_sig, e))?;
+    let assinatura = SshSig::from_pem(pem).map_err(|e| format!("{}: {}", caminho_sig, e))?;
+
+    let Some(chave) = chaves
+        .iter()
+        .find(|chave| chave.key_data() == assinatura.public_key())
+    else {
+        return Err(format!(
+            "{}: signed by {}, not a trusted key",
+            caminho_sig,
+            assinatura.public_key().fingerprint(HashAlg::Sha256)
+        )
+        .into());
+    };
+    chave
+        .verify(NAMESPACE, &canonico(manifest)?, &assinatura)
+        .map_err(|_| {
+            format!(
+                "{}: signature does not verify: {} changed after signing",
+                caminho_sig, manifest
+            )
+        })?;
+    Ok(
+        format!("{} {}", chave.fingerprint(HashAlg::Sha256), chave.comment())
+            .trim_end()
+            .to_string(),
+    )
+}
@@ -0,0 +1,393 @@
+// Log de auditoria da cadeia de custódia (--audit_log File, no gen e no check).
+//
+// Cada execução acrescenta uma linha JSON ao log, no fim (o último evento, summary):
+//   v, seq (1, 2, ...), time (UTC), time_ms, user, host, hush (versão), command, argv (a linha
+//   de comando), args (os Argumentos), root (pasta/arquivos do gen, pasta de trabalho do check),
+//   manifest (arquivo do check; null no gen, que escreve na saída), manifest_sha256 (sha256 da
+//   forma canônica do manifest, a mesma da assinatura e do carimbo; null se não deu para ler),
+//   case (dados do caso),
+//   totals, result, failures (os erros, path e message, até 1000) e prev.
+// prev: sha256 da linha anterior, como está no arquivo (64 zeros na primeira). Mudar ou tirar
+// uma linha quebra a corrente a partir da seguinte: `hush audit verify` acha onde.
+// Tirar as últimas linhas não quebra a corrente: para isso, guardar o head (sha256 da última
+// linha) fora do log e conferir com --head.
+// A escrita é com o log travado (flock, Unix): execuções ao mesmo tempo não se misturam.
+
+use once_cell::sync::OnceCell;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::process;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::assinatura;
+use crate::functions::Argumentos;
+
+pub const VERSAO_AUDITORIA: u64 = 1;
+const MAX_FALHAS: usize = 1000;
+const INICIO_CORRENTE: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+struct Auditoria {
+    caminho: String,
+    comando: String,
+    argv: Vec<String>,
+    argumentos: Value,
+    raiz: String,
+    manifest: Option<String>,
+    caso: Value,
+    // Erros da execução (no check, os arquivos que não bateram), até MAX_FALHAS
+    falhas: Mutex<Vec<Value>>,
+    // gen: as linhas escritas na saída, para o sha256 do manifest
+    linhas: Mutex<Vec<String>>,
+    inicio: Instant,
+}
+
+static AUDITORIA: OnceCell<Auditoria> = OnceCell::new();
+
+// Abre o log já no início: sem permissão de escrita, falha antes de ler os dados
+pub fn abre(
+    caminho: &str,
+    argumentos: &Argumentos,
+    raiz: &str,
+    manifest: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(caminho)
+        .map_err(|e| format!("{}: {}", caminho, e))?;
+    let auditoria = Auditoria {
+        caminho: caminho.to_string(),
+        comando: argumentos.sub_comando.clone(),
+        argv: std::env::args().collect(),
+        argumentos: serde_json::to_value(argumentos)?,
+        raiz: raiz.to_string(),
+        manifest: manifest.map(str::to_string),
+        caso: serde_json::to_value(&argumentos.caso)?,
+        falhas: Mutex::new(Vec::new()),
+        linhas: Mutex::new(Vec::new()),
+        inicio: Instant::now(),
+    };
+    if AUDITORIA.set(auditoria).is_err() {
+        return Err("audit log already open".into());
+    }
+    Ok(())
+}
+
+pub fn ativo() -> bool {
+    AUDITORIA.get().is_some()
+}
+
+// Linha do manifest que o gen escreveu
+pub fn linha_manifest(linha: &str) {
+    if let Some(auditoria) = AUDITORIA.get() {
+        auditoria.linhas.lock().unwrap().push(linha.to_string());
+    }
+}
+
+// Erro da execução (vem de eventos::error)
+pub fn falha(path: &str, message: &str) {
+    if let Some(auditoria) = AUDITORIA.get() {
+        let mut falhas = auditoria.falhas.lock().unwrap();
+        if falhas.len() < MAX_FALHAS {
+            falhas.push(json!({ "path": path, "message": message }));
+        }
+    }
+}
+
+// Acrescenta a entrada da execução. Sem --audit_log, nada. Se não conseguir gravar, sai com erro:
+// uma execução sem registro não pode parecer que deu certo.
+pub fn registra(result: &str, totais: Value) {
+    let Some(auditoria) = AUDITORIA.get() else {
+        return;
+    };
+    if let Err(e) = acrescenta(auditoria, result, totais) {
+        eprintln!("Audit log error! {}: {}", auditoria.caminho, e);
+        process::exit(1);
+    }
+}
+
+fn acrescenta(auditoria: &Auditoria, result: &str, totais: Value) -> Result<(), Box<dyn Error>> {
+    let manifest_sha256 = match &auditoria.manifest {
+        Some(manifest) => match assinatura::canonico(manifest) {
+            Ok(canonico) => Some(hex::encode(Sha256::digest(canonico))),
+            // Manifest que não abre: a execução é registrada assim mesmo, com o erro
+            Err(e) => {
+                let registrada = auditoria
+                    .falhas
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|f| f["path"] == *manifest);
+                if !registrada {
+                    falha(manifest, &e.to_string());
+                }
+                None
+            }
+        },
+        None => {
+            let linhas = std::mem::take(&mut *auditoria.linhas.lock().unwrap());
+            let canonico = assinatura::forma_canonica(linhas);
+            Some(hex::encode(Sha256::digest(canonico)))
+        }
+    };
+    let mut totais = totais;
+    if let Value::Object(campos) = &mut totais {
+        campos.insert(
+            "elapsed_ms".to_string(),
+            json!(auditoria.inicio.elapsed().as_millis() as u64),
+        );
+    }
+
+    let mut log = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .open(&auditoria.caminho)?;
+    trava(&log)?;
+    let mut texto = String::new();
+    log.seek(SeekFrom::Start(0))?;
+    log.read_to_string(&mut texto)?;
+    let linhas: Vec<&str> = texto.lines().filter(|linha| !linha.is_empty()).collect();
+    let prev = match linhas.last() {
+        Some(ultima) => hex::encode(Sha256::digest(ultima.as_bytes())),
+        None => INICIO_CORRENTE.to_string(),
+    };
+
+    let agora = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let entrada = json!({
+        "v": VERSAO_AUDITORIA,
+        "seq": linhas.len() + 1,
+        "time": hora_utc(agora.as_millis() as u64),
+        "time_ms": agora.as_millis() as u64,
+        "user": whoami::username(),
+        "host": whoami::fallible::hostname().unwrap_or_default(),
+        "hush": env!("CARGO_PKG_VERSION"),
+        "command": auditoria.comando,
+        "argv": auditoria.argv,
+        "args": auditoria.argumentos,
+        "root": auditoria.raiz,
+        "manifest": auditoria.manifest,
+        "manifest_sha256": manifest_sha256,
+        "case": auditoria.caso,
+        "totals": totais,
+        "result": result,
+        "failures": *auditoria.falhas.lock().unwrap(),
+        "prev": prev,
+    });
+    let mut linha = entrada.to_string();
+    linha.push('\n');
+    // Um log que não termina em fim de linha (escrita cortada) não junta duas entradas
+    if !texto.is_empty() && !texto.ends_with('\n') {
+        linha.insert(0, '\n');
+    }
+    log.write_all(linha.as_bytes())?;
+    log.sync_all()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn trava(log: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // Solta ao fechar o arquivo
+    if unsafe { libc::flock(log.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn trava(_log: &File) -> io::Result<()> {
+    Ok(())
+}
+
+// Resultado de `hush audit verify`
+pub struct Verificacao {
+    pub n_entradas: u64,
+    pub head: String,
+    pub problemas: Vec<String>,
+}
+
+// Confere a corrente do log: prev de cada entrada e seq em ordem. Com o head guardado fora do
+// log, confere também a última entrada.
+pub fn verifica(caminho: &str, head: Option<&str>) -> Result<Verificacao, Box<dyn Error>> {
+    let texto = std::fs::read_to_string(caminho).map_err(|e| format!("{}: {}", caminho, e))?;
+    let mut problemas = Vec::new();
+    let mut anterior = INICIO_CORRENTE.to_string();
+    let mut n_entradas = 0;
+    for (n_linha, linha) in texto.lines().enumerate() {
+        if linha.is_empty() {
+            continue;
+        }
+        n_entradas += 1;
+        let lugar = format!("line {}", n_linha + 1);
+        match serde_json::from_str::<Map<String, Value>>(linha) {
+            Ok(entrada) => {
+                if entrada.get("prev").and_then(Value::as_str) != Some(anterior.as_str()) {
+                    problemas.push(format!(
+                        "{}: prev does not match the entry before it (entry changed, removed or added before this line)",
+                        lugar
+                    ));
+                }
+                if entrada.get("seq").and_then(Value::as_u64) != Some(n_entradas) {
+                    problemas.push(format!(
+                        "{}: seq {} where {} was expected",
+                        lugar,
+                        entrada.get("seq").unwrap_or(&Value::Null),
+                        n_entradas
+                    ));
+                }
+            }
+            Err(e) => problemas.push(format!("{}: not an audit entry: {}", lugar, e)),
+        }
+        anterior = hex::encode(Sha256::digest(linha.as_bytes()));
+    }
+    if let Some(head) = head {
+        if head != anterior {
+            problemas.push(format!(
+                "head {} is not the last entry: entries removed from the end, or added after it",
+                head
+            ));
+        }
+    }
+    Ok(Verificacao {
+        n_entradas,
+        head: anterior,
+        problemas,
+    })
+}
+
+// "2024-05-01T12:00:00.123Z"
+pub fn hora_utc(ms: u64) -> String {
+    let segundos = ms / 1000;
+    let dias = (segundos / 86400) as i64;
+    let resto = segundos % 86400;
+
+    // Data a partir dos dias desde 1970-01-01 (calendário gregoriano)
+    let z = dias + 719468;
+    let era = z.div_euclid(146097);
+    let dia_da_era = z - era * 146097;
+    let ano_da_era =
+        (dia_da_era - dia_da_era / 1460 + dia_da_era / 36524 - dia_da_era / 146096) / 365;
+    let dia_do_ano = dia_da_era - (365 * ano_da_era + ano_da_era / 4 - ano_da_era / 100);
+    let mp = (5 * dia_do_ano + 2) / 153;
+    let dia = dia_do_ano - (153 * mp + 2) / 5 + 1;
+    let mes = if mp < 10 { mp + 3 } else { mp - 9 };
+    let ano = ano_da_era + era * 400 + if mes <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        ano,
+        mes,
+        dia,
+        resto / 3600,
+        resto % 3600 / 60,
+        resto % 60,
+        ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testes::PastaTeste;
+    use std::fs;
+
+    // Log com n entradas encadeadas, como o acrescenta escreve
+    fn corrente(n: u64) -> Vec<String> {
+        let mut linhas: Vec<String> = Vec::new();
+        for seq in 1..=n {
+            let prev = match linhas.last() {
+                Some(ultima) => hex::encode(Sha256::digest(ultima.as_bytes())),
+                None => INICIO_CORRENTE.to_string(),
+            };
+            linhas.push(
+                json!({ "v": VERSAO_AUDITORIA, "seq": seq, "result": "ok", "prev": prev })
+                    .to_string(),
+            );
+        }
+        linhas
+    }
+
+    fn verifica_linhas(dir: &PastaTeste, linhas: &[String], head: Option<&str>) -> Verificacao {
+        let caminho = dir.join("audit.jsonl");
+        fs::write(&caminho, linhas.join("\n") + "\n").unwrap();
+        verifica(caminho.to_str().unwrap(), head).unwrap()
+    }
+
+    fn head(linha: &str) -> String {
+        hex::encode(Sha256::digest(linha.as_bytes()))
+    }
+
+    #[test]
+    fn corrente_inteira() {
+        let dir = PastaTeste::nova("auditoria_inteira");
+        let linhas = corrente(4);
+        let verificacao = verifica_linhas(&dir, &linhas, Some(&head(&linhas[3])));
+        assert!(
+            verificacao.problemas.is_empty(),
+            "{:?}",
+            verificacao.problemas
+        );
+        assert_eq!(verificacao.n_entradas, 4);
+        assert_eq!(verificacao.head, head(&linhas[3]));
+    }
+
+    #[test]
+    fn linha_editada() {
+        let dir = PastaTeste::nova("auditoria_editada");
+        let mut linhas = corrente(4);
+        linhas[1] = linhas[1].replace("\"ok\"", "\"failed\"");
+        let verificacao = verifica_linhas(&dir, &linhas, None);
+        // A linha editada ainda aponta para a anterior: quem acusa é a seguinte
+        assert_eq!(verificacao.problemas.len(), 1);
+        assert!(verificacao.problemas[0].starts_with("line 3: prev"));
+    }
+
+    #[test]
+    fn linha_apagada() {
+        let dir = PastaTeste::nova("auditoria_apagada");
+        let mut linhas = corrente(4);
+        linhas.remove(1);
+        let verificacao = verifica_linhas(&dir, &linhas, None);
+        // A seguinte não aponta para a anterior, e o seq fica adiantado dali para frente
+        assert_eq!(verificacao.problemas.len(), 3);
+        assert!(verificacao.problemas[0].starts_with("line 2: prev"));
+        assert!(verificacao.problemas[1].starts_with("line 2: seq 3 where 2"));
+        assert!(verificacao.problemas[2].starts_with("line 3: seq 4 where 3"));
+    }
+
+    #[test]
+    fn seq_pulado() {
+        let dir = PastaTeste::nova("auditoria_seq");
+        let mut linhas = corrente(3);
+        // Entrada com seq errado, mas encadeada certo: só o seq acusa
+        let prev = head(&linhas[2]);
+        linhas.push(
+            json!({ "v": VERSAO_AUDITORIA, "seq": 5, "result": "ok", "prev": prev }).to_string(),
+        );
+        let verificacao = verifica_linhas(&dir, &linhas, None);
+        assert_eq!(
+            verificacao.problemas,
+            ["line 4: seq 5 where 4 was expected"]
+        );
+    }
+
+    #[test]
+    fn log_cortado_no_fim() {
+        let dir = PastaTeste::nova("auditoria_cortada");
+        let linhas = corrente(4);
+        let head_guardado = head(&linhas[3]);
+        // Sem o head, tirar as últimas linhas não aparece
+        let verificacao = verifica_linhas(&dir, &linhas[..2], None);
+        assert!(verificacao.problemas.is_empty());
+        let verificacao = verifica_linhas(&dir, &linhas[..2], Some(&head_guardado));
+        assert_eq!(verificacao.problemas.len(), 1);
+        assert!(verificacao.problemas[0].contains("is not the last entry"));
+    }
+}
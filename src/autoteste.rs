@@ -0,0 +1,449 @@
+// Autoteste (hush selftest): testes de resposta conhecida, para validar o hush na máquina em uso.
+//
+// Os valores esperados estão aqui, calculados fora do hush (sha256 dos documentos de referência,
+// HMAC-SHA256 da RFC 4231, BLAKE3 dos vetores oficiais, e o hsha256 e os MACs do hush por
+// implementações à parte, seguindo as especificações de construcao.rs e mac.rs):
+//   - sha256 de textos (FIPS 180-2) e de arquivos, pelo mesmo caminho do gen e do check;
+//   - hsha256v1 e hsha256v2 (hash_hsha256, construcao.rs) com blocksize 1KB: arquivo vazio, um
+//     bloco só (menor e igual ao blocksize), múltiplo exato do blocksize e bloco final parcial,
+//     em cada modo de leitura;
+//   - a escolha do gen: sha256 até o blocksize na v1, hsha256v2 sempre na v2;
+//   - hash_hush com o algoritmo como está no manifest ("sha256", "hsha256-1KB", "hsha256v2-1KB");
+//   - o MAC (mac.rs): os vetores de HMAC-SHA256 e BLAKE3 pelo cálculo do gen e do check, e com
+//     uma chave fixa o MAC de uma entrada (escopo entry) e de um manifest (escopo manifest),
+//     conferidos também pelo check, inteiros e com uma linha mudada.
+// Os arquivos de teste têm o byte i igual a i % 251 e ficam numa pasta temporária, apagada no fim.
+
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::auditoria;
+use crate::construcao::Construcao;
+use crate::functions::{self, ModoLeitura};
+use crate::mac::{self, AlgMac, ChaveMac, EscopoMac};
+
+const BLOCKSIZE: usize = 1024;
+// Menor que o bloco e sem dividir o bloco: leituras que cruzam o limite do bloco
+const BUFFER_SIZE: usize = 100;
+
+const SHA256_TEXTOS: [(&str, &[u8], &str); 3] = [
+    (
+        "empty",
+        b"",
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+    ),
+    (
+        "\"abc\"",
+        b"abc",
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+    ),
+    (
+        "448 bits",
+        b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+    ),
+];
+const SHA256_MILHAO_A: &str = "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0";
+
+// (nome, tamanho do arquivo, sha256, hsha256v1 e hsha256v2 com blocksize 1KB)
+const ARQUIVOS: [(&str, u64, &str, &str, &str); 6] = [
+    (
+        "empty file",
+        0,
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        "4b8cac26cc167a882f3aa4eb59b040d1d26f3a4c44c514c525deb7489dcff064",
+    ),
+    (
+        "single block, 1000 bytes",
+        1000,
+        "4e4c294b331f7a2099a379bec34b9f9fc03dc46ab465d998f4d683da53487e6d",
+        "4e4c294b331f7a2099a379bec34b9f9fc03dc46ab465d998f4d683da53487e6d",
+        "371a520a1568b6e6a71904ee14daeba1c75e1ce87e6ba00abf5ba02d1e71c3f7",
+    ),
+    (
+        "single block, exactly the blocksize",
+        1024,
+        "2bce1ba628720664be4b9fdd77aae0678e5f0f3f02fc6ff641ec879094f6a404",
+        "2bce1ba628720664be4b9fdd77aae0678e5f0f3f02fc6ff641ec879094f6a404",
+        "5ce6f4883237887aad48e4849e68209ca13e87e3f52ed186e0ea3a1200545090",
+    ),
+    (
+        "exact multiple, 3 blocks",
+        3072,
+        "5f24b2f16026ec7d0450a5a08283d3cfd47302fe859f579ed79fe7d2663b73f9",
+        "de82c15775f4ed200790e7c97337ecbc74427eb2c7998d99f8e0af1d449e08ab",
+        "a1bbe449384c62ec91b1cadb4ee0c653de5558eed70d00ea811a27f6db4c9ee6",
+    ),
+    (
+        "trailing partial block, 2600 bytes",
+        2600,
+        "d9f4bdbcb3aa9ef24b827d8378fa772a46c413d633da03d2f670d413c88d8a8d",
+        "ffcc7a4509c2a3aa4e8bf5cf02ee20537ceae7c873b0a67c7865c1ea1494012c",
+        "3e64f6303b51ea118f1c4260fda69bc5c28fbc8bd53d12a9cad0d56eca52c2d5",
+    ),
+    (
+        "98 blocks, 100000 bytes",
+        100000,
+        "cd2df694e424bc7968cc37f47751019e5ca0cd1bdf2e479ea537c3a1c32ee1aa",
+        "e5604387bdf5ab819f4a722ba884e95bce95e8e150f419f56aa67ef3ad7e0eae",
+        "11647eb867caf5d02744a37624f8f42201b722969ccba43cf8cdad03933cfd3b",
+    ),
+];
+
+// RFC 4231, casos 1 e 2: (nome, chave, mensagem, mac)
+const HMAC_SHA256: [(&str, &[u8], &[u8], &str); 2] = [
+    (
+        "RFC 4231 case 1",
+        &[0x0b; 20],
+        b"Hi There",
+        "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+    ),
+    (
+        "RFC 4231 case 2",
+        b"Jefe",
+        b"what do ya want for nothing?",
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+    ),
+];
+
+// Vetor oficial do BLAKE3 com chave, entrada vazia
+const BLAKE3_CHAVE: &[u8; 32] = b"whats the Elvish word for friend";
+const BLAKE3_KEYED: &str = "92b2b75604ed3c761f9d6f62392c8a9227ad0ea3f09573e783f1498a4ed60d26";
+
+// MAC do hush com chave fixa: entradas de um manifest e, por algoritmo, o MAC da primeira entrada
+// (escopo entry) e o do manifest com as duas (escopo manifest)
+const CHAVE_MAC: &[u8; 32] = b"hush selftest mac key, 32 bytes!";
+const ENTRADAS_MAC: [&str; 2] = [
+    "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855 ?sha256*./empty",
+    "de82c15775f4ed200790e7c97337ecbc74427eb2c7998d99f8e0af1d449e08ab ?hsha256-1KB*./data_3072",
+];
+const MACS_HUSH: [(AlgMac, &str, &str); 2] = [
+    (
+        AlgMac::HmacSha256,
+        "44098dd9942e2b2f8a6be54b9f336bcfa029545e89255091dcf82be2a7b0a752",
+        "3f0bfe9cb0fe9f0dd574226cd0a8c0e713a51cf598b2cc02379df9e56f7d822e",
+    ),
+    (
+        AlgMac::Blake3,
+        "035366d0645f4a1a2ad3084b54849daf3bcc723911a73811a73e9c943b5d44e4",
+        "ac40bed1f67a5386c9479b36c3f0b6b4635a3e807d52d30a7d78168b2f07b2ce",
+    ),
+];
+
+struct Teste {
+    grupo: &'static str,
+    nome: String,
+    esperado: String,
+    calculado: Result<String, String>,
+}
+
+impl Teste {
+    fn passou(&self) -> bool {
+        self.calculado.as_deref() == Ok(self.esperado.as_str())
+    }
+}
+
+// Roda os testes e imprime o relatório. Volta true se todos passaram.
+pub fn executa(n_max_concur: u32) -> Result<bool, Box<dyn Error>> {
+    let pasta = std::env::temp_dir().join(format!("hush-selftest-{}", std::process::id()));
+    fs::create_dir_all(&pasta).map_err(|e| format!("{}: {}", pasta.display(), e))?;
+    let testes = roda(&pasta, n_max_concur);
+    let _ = fs::remove_dir_all(&pasta);
+    let testes = testes?;
+
+    let agora = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    println!("hush selftest");
+    println!("Tool    : hush {}", env!("CARGO_PKG_VERSION"));
+    println!(
+        "Machine : {} ({} {})",
+        whoami::fallible::hostname().unwrap_or_default(),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    println!(
+        "Time    : {}",
+        auditoria::hora_utc(agora.as_millis() as u64)
+    );
+    println!(
+        "Data    : bytes i % 251, blocksize {} bytes, buffer {} bytes",
+        BLOCKSIZE, BUFFER_SIZE
+    );
+
+    let mut grupo = "";
+    for teste in &testes {
+        if teste.grupo != grupo {
+            grupo = teste.grupo;
+            println!("\n{}", grupo);
+        }
+        match &teste.calculado {
+            Ok(calculado) if teste.passou() => println!("  PASS {}: {}", teste.nome, calculado),
+            Ok(calculado) => {
+                println!("  FAIL {}", teste.nome);
+                println!("       expected:   {}", teste.esperado);
+                println!("       calculated: {}", calculado);
+            }
+            Err(e) => {
+                println!("  FAIL {}", teste.nome);
+                println!("       expected: {}", teste.esperado);
+                println!("       error: {}", e);
+            }
+        }
+    }
+
+    let n_falhas = testes.iter().filter(|teste| !teste.passou()).count();
+    println!(
+        "\nResult: {} tests, {} passed, {} failed.",
+        testes.len(),
+        testes.len() - n_falhas,
+        n_falhas
+    );
+    if n_falhas == 0 {
+        println!("Selftest passed: all results match the expected values.");
+    } else {
+        println!("Selftest FAILED: results on this machine do not match the expected values.");
+    }
+    Ok(n_falhas == 0)
+}
+
+fn roda(pasta: &Path, n_max_concur: u32) -> Result<Vec<Teste>, Box<dyn Error>> {
+    let mut testes = Vec::new();
+    let mut teste = |grupo, nome: String, esperado: &str, calculado: Result<String, String>| {
+        testes.push(Teste {
+            grupo,
+            nome,
+            esperado: esperado.to_string(),
+            calculado,
+        })
+    };
+
+    for (nome, texto, esperado) in SHA256_TEXTOS {
+        teste(
+            "sha256",
+            nome.to_string(),
+            esperado,
+            Ok(hex::encode(Sha256::digest(texto))),
+        );
+    }
+    let mut hasher = Sha256::new();
+    for _ in 0..1000 {
+        hasher.update([b'a'; 1000]);
+    }
+    teste(
+        "sha256",
+        "one million \"a\"".to_string(),
+        SHA256_MILHAO_A,
+        Ok(hex::encode(hasher.finalize())),
+    );
+
+    let mut caminhos = Vec::new();
+    for (nome, tamanho, _, _, _) in ARQUIVOS {
+        caminhos.push((nome, cria_arquivo(pasta, tamanho)?));
+    }
+
+    for ((nome, caminho), (_, _, esperado, _, _)) in caminhos.iter().zip(ARQUIVOS) {
+        teste(
+            "sha256 of files (hash_sha256)",
+            nome.to_string(),
+            esperado,
+            texto(functions::hash_sha256(caminho, BUFFER_SIZE)),
+        );
+    }
+
+    let modos = [
+        ("read", ModoLeitura::Read),
+        ("mmap", ModoLeitura::Mmap),
+        ("io_uring", ModoLeitura::IoUring),
+    ];
+    for (grupo, construcao) in [
+        ("hsha256v1 (hash_hsha256), blocksize 1KB", Construcao::V1),
+        ("hsha256v2 (hash_hsha256), blocksize 1KB", Construcao::V2),
+    ] {
+        for ((nome, caminho), (_, _, _, esperado_v1, esperado_v2)) in caminhos.iter().zip(ARQUIVOS)
+        {
+            let esperado = match construcao {
+                Construcao::V1 => esperado_v1,
+                Construcao::V2 => esperado_v2,
+            };
+            for (nome_modo, modo) in modos {
+                teste(
+                    grupo,
+                    format!("{}, {}", nome, nome_modo),
+                    esperado,
+                    texto(functions::hash_hsha256(
+                        caminho,
+                        BUFFER_SIZE,
+                        BLOCKSIZE,
+                        n_max_concur,
+                        false,
+                        modo,
+                        construcao,
+                        None,
+                    )),
+                );
+            }
+            teste(
+                grupo,
+                format!("{}, sequential (hdd)", nome),
+                esperado,
+                texto(functions::hash_hsha256_sequencial(
+                    caminho,
+                    BUFFER_SIZE,
+                    BLOCKSIZE,
+                    false,
+                    construcao,
+                    None,
+                )),
+            );
+        }
+    }
+
+    // v1: o gen usa sha256 até o blocksize; v2: hsha256v2 em tudo
+    for (tamanho, construcao, esperado) in [
+        (0, Construcao::V1, "sha256"),
+        (BLOCKSIZE as u64, Construcao::V1, "sha256"),
+        (BLOCKSIZE as u64 + 1, Construcao::V1, "hsha256"),
+        (0, Construcao::V2, "hsha256v2"),
+        (BLOCKSIZE as u64, Construcao::V2, "hsha256v2"),
+    ] {
+        let escolhido = if construcao.usa_sha256(tamanho, BLOCKSIZE as u64) {
+            "sha256"
+        } else {
+            construcao.nome()
+        };
+        teste(
+            "gen, algorithm of a file",
+            format!("{}, {} bytes", construcao.nome(), tamanho),
+            esperado,
+            Ok(escolhido.to_string()),
+        );
+    }
+
+    let alg_check = |alg: &str| format!("{}-{}KB", alg, BLOCKSIZE / 1024);
+    for ((nome, caminho), (_, _, esperado_sha256, esperado_v1, esperado_v2)) in
+        caminhos.iter().zip(ARQUIVOS)
+    {
+        for (alg, esperado) in [
+            ("sha256".to_string(), esperado_sha256),
+            (alg_check("hsha256"), esperado_v1),
+            (alg_check("hsha256v1"), esperado_v1),
+            (alg_check("hsha256v2"), esperado_v2),
+        ] {
+            teste(
+                "check (hash_hush, algorithm of the hash file)",
+                format!("{}, {}", nome, alg),
+                esperado,
+                texto(functions::hash_hush(
+                    caminho,
+                    &alg,
+                    BUFFER_SIZE,
+                    n_max_concur,
+                    false,
+                    ModoLeitura::Read,
+                )),
+            );
+        }
+    }
+
+    for (nome, chave, mensagem, esperado) in HMAC_SHA256 {
+        teste(
+            "MAC (mac.rs), hmac-sha256",
+            nome.to_string(),
+            esperado,
+            texto(mac::mac_bruto(AlgMac::HmacSha256, chave, mensagem)),
+        );
+    }
+    teste(
+        "MAC (mac.rs), blake3",
+        "keyed hash, empty input".to_string(),
+        BLAKE3_KEYED,
+        texto(mac::mac_bruto(AlgMac::Blake3, BLAKE3_CHAVE, b"")),
+    );
+
+    let chave = ChaveMac::nova(CHAVE_MAC.to_vec())?;
+    for (alg, esperado_entrada, esperado_manifest) in MACS_HUSH {
+        let grupo = match alg {
+            AlgMac::HmacSha256 => "hash file MAC (gen and check), hmac-sha256, fixed key",
+            AlgMac::Blake3 => "hash file MAC (gen and check), blake3, fixed key",
+        };
+        for (escopo, nome_escopo, esperado) in [
+            (EscopoMac::Entrada, "entry", esperado_entrada),
+            (EscopoMac::Manifest, "manifest", esperado_manifest),
+        ] {
+            // As linhas pela mesma escrita do gen
+            let mut saida = mac::EscritaManifest::nova(Vec::new(), Some((alg, escopo, &chave)))?;
+            for entrada in ENTRADAS_MAC {
+                saida.linha(entrada)?;
+            }
+            let escrito = String::from_utf8(saida.termina()?)?;
+            let linhas: Vec<String> = escrito.lines().map(str::to_string).collect();
+            // entry: a linha de MAC logo depois da primeira entrada; manifest: a última linha
+            let linha_mac = match escopo {
+                EscopoMac::Entrada => &linhas[2],
+                EscopoMac::Manifest => &linhas[linhas.len() - 1],
+            };
+            teste(
+                grupo,
+                format!("scope {}, MAC", nome_escopo),
+                esperado,
+                Ok(linha_mac.rsplit(' ').next().unwrap_or_default().to_string()),
+            );
+
+            // O check confere o manifest como o gen escreveu, e acusa uma entrada mudada
+            let mudado: Vec<String> = linhas
+                .iter()
+                .map(|linha| linha.replacen("./empty", "./emptx", 1))
+                .collect();
+            for (nome_caso, linhas, esperado) in [
+                ("as written", &linhas, "verifies"),
+                ("entry changed", &mudado, "does not verify"),
+            ] {
+                let caminho = pasta.join(format!("mac_{}_{}.txt", alg_nome(alg), nome_escopo));
+                fs::write(&caminho, linhas.join("\n") + "\n")
+                    .map_err(|e| format!("{}: {}", caminho.display(), e))?;
+                let conferido = mac::confere_com_chave(&caminho.display().to_string(), &chave)
+                    .map(|problemas| {
+                        if problemas.is_empty() {
+                            "verifies".to_string()
+                        } else {
+                            "does not verify".to_string()
+                        }
+                    })
+                    .map_err(|e| e.to_string());
+                teste(
+                    grupo,
+                    format!("scope {}, check, {}", nome_escopo, nome_caso),
+                    esperado,
+                    conferido,
+                );
+            }
+        }
+    }
+
+    Ok(testes)
+}
+
+fn alg_nome(alg: AlgMac) -> &'static str {
+    match alg {
+        AlgMac::HmacSha256 => "hmac",
+        AlgMac::Blake3 => "blake3",
+    }
+}
+
+fn cria_arquivo(pasta: &Path, tamanho: u64) -> Result<String, Box<dyn Error>> {
+    let caminho = pasta.join(format!("data_{}", tamanho));
+    let dados: Vec<u8> = (0..tamanho).map(|i| (i % 251) as u8).collect();
+    fs::write(&caminho, dados).map_err(|e| format!("{}: {}", caminho.display(), e))?;
+    Ok(caminho.display().to_string())
+}
+
+fn texto(resultado: Result<String, Box<dyn Error>>) -> Result<String, String> {
+    resultado
+        .map(|hash| hash.to_lowercase())
+        .map_err(|e| e.to_string())
+}
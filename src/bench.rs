@@ -0,0 +1,434 @@
+// Bench e ajuste automático das opções de leitura.
+//
+// Roda rodadas cronometradas sobre uma amostra dos dados (os maiores arquivos, até o tamanho da
+// amostra; um arquivo maior que a amostra entra só com o começo), mudando uma opção por vez e
+// ficando com a melhor: buffersize, threads, modo de leitura e, no bench, blocksize.
+// Antes de cada rodada a amostra sai do page cache (Linux), para cada rodada ler do disco.
+// Os hashes de todas as rodadas com o mesmo blocksize têm que ser iguais.
+
+use crossbeam_channel::unbounded;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cache;
+use crate::disco::{self, AgendamentoDisco};
+use crate::functions::{
+    hash_hsha256_parcial, hash_sha256, search_files, Argumentos, ModoLeitura, ParseSize,
+};
+use crate::pool;
+
+pub const AMOSTRA_BENCH: &str = "1GB";
+pub const AMOSTRA_AUTO: &str = "256MB";
+
+const BUFFERS: [&str; 5] = ["10KB", "64KB", "256KB", "1MB", "4MB"];
+const N_THREADS: [u32; 7] = [1, 2, 4, 8, 16, 32, 64];
+const BLOCOS: [&str; 4] = ["10MB", "50MB", "100MB", "200MB"];
+// Só troca de configuração se ganhar por mais que isto: ruído da medida não muda a recomendação
+const GANHO_MINIMO: f64 = 1.05;
+
+// Arquivo da amostra, e quantos bytes dele entram (arquivo grande entra só com o começo)
+struct ArquivoAmostra {
+    caminho: String,
+    tamanho: u64,
+    bytes_amostra: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Configuracao {
+    pub block_size_str: String,
+    pub buffer_size_str: String,
+    pub n_threads: u32,
+    pub modo_leitura: ModoLeitura,
+    // Um leitor sequencial, como em disco rotacional, em vez do pool paralelo
+    pub sequencial: bool,
+}
+
+impl Configuracao {
+    fn nome_modo(&self) -> String {
+        if self.sequencial {
+            String::from("sequential")
+        } else {
+            format!("{:?}", self.modo_leitura).to_lowercase()
+        }
+    }
+
+    // Opções do hush para usar esta configuração
+    pub fn opcoes(&self, com_blocksize: bool) -> String {
+        let mut opcoes = String::new();
+        if com_blocksize {
+            opcoes.push_str(&format!("--blocksize {} ", self.block_size_str));
+        }
+        opcoes.push_str(&format!(
+            "--buffersize {} --n_workers {} --n_max_concur {} --n_threads {}",
+            self.buffer_size_str, self.n_threads, self.n_threads, self.n_threads
+        ));
+        if self.sequencial {
+            opcoes.push_str(" --disk_sched hdd");
+        } else {
+            match self.modo_leitura {
+                ModoLeitura::Read => {}
+                ModoLeitura::Mmap => opcoes.push_str(" --mmap"),
+                ModoLeitura::IoUring => opcoes.push_str(" --io_uring"),
+            }
+        }
+        opcoes
+    }
+}
+
+struct Bench {
+    amostra: Vec<ArquivoAmostra>,
+    n_rodadas: u32,
+    // (arquivo, blocksize) -> hash da primeira rodada
+    referencias: HashMap<(String, String), String>,
+    // gen --auto: a saída padrão é o manifest, então as rodadas vão para o stderr
+    para_stderr: bool,
+    // O mmap só entra na busca do gen --auto com --mmap: ver functions::mapeia_arquivo
+    com_mmap: bool,
+}
+
+// Os maiores arquivos até completar a amostra
+fn escolhe_amostra(files: &[String], tamanho_amostra: u64) -> Vec<ArquivoAmostra> {
+    let mut com_tamanho: Vec<(u64, &String)> = files
+        .iter()
+        .filter_map(|file| Some((fs::metadata(file).ok()?.len(), file)))
+        .filter(|(tamanho, _)| *tamanho > 0)
+        .collect();
+    com_tamanho.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+
+    let mut amostra = Vec::new();
+    let mut total = 0;
+    for (tamanho, file) in com_tamanho {
+        if total >= tamanho_amostra {
+            break;
+        }
+        let bytes_amostra = tamanho.min(tamanho_amostra - total);
+        total += bytes_amostra;
+        amostra.push(ArquivoAmostra {
+            caminho: file.clone(),
+            tamanho,
+            bytes_amostra,
+        });
+    }
+    amostra
+}
+
+impl Bench {
+    fn imprime(&self, linha: &str) {
+        if self.para_stderr {
+            eprintln!("Auto: {}", linha);
+        } else {
+            println!("{}", linha);
+        }
+    }
+
+    // Uma rodada: lê a amostra com a configuração. Retorna o tempo e os bytes lidos.
+    fn executa_rodada(&mut self, config: &Configuracao) -> Result<(Duration, u64), Box<dyn Error>> {
+        let block_size = ParseSize(&config.block_size_str)?;
+        let buffer_size = ParseSize(&config.buffer_size_str)? as usize;
+        for arquivo in &self.amostra {
+            if let Err(e) = cache::esvazia_cache(&arquivo.caminho) {
+                info!("bench: page cache not dropped: {} {}", arquivo.caminho, e);
+            }
+        }
+        pool::pool().ajusta_threads(config.n_threads);
+
+        let n_workers = if config.sequencial {
+            1
+        } else {
+            config.n_threads
+        };
+        let (sender_files, receiver_files) = unbounded::<usize>();
+        for index in 0..self.amostra.len() {
+            sender_files.send(index).unwrap();
+        }
+        drop(sender_files);
+
+        let hashes: Mutex<Vec<(usize, String)>> = Mutex::new(Vec::new());
+        let erros: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        let amostra = &self.amostra;
+        let inicio = Instant::now();
+        thread::scope(|scope| {
+            for _ in 0..n_workers {
+                let receiver_files = receiver_files.clone();
+                let hashes = &hashes;
+                let erros = &erros;
+                scope.spawn(move || {
+                    while let Ok(index) = receiver_files.recv() {
+                        let arquivo = &amostra[index];
+                        // Mesma escolha do gen: hsha256 acima do blocksize, sha256 até ele
+                        let resultado = if arquivo.tamanho > block_size {
+                            hash_hsha256_parcial(
+                                &arquivo.caminho,
+                                arquivo.bytes_amostra,
+                                buffer_size,
+                                block_size as usize,
+                                config.n_threads,
+                                config.modo_leitura,
+                                config.sequencial,
+                            )
+                        } else {
+                            hash_sha256(&arquivo.caminho, buffer_size)
+                        };
+                        match resultado {
+                            Ok(hash) => hashes.lock().unwrap().push((index, hash)),
+                            Err(e) => erros
+                                .lock()
+                                .unwrap()
+                                .push(format!("{}: {}", arquivo.caminho, e)),
+                        }
+                    }
+                });
+            }
+        });
+        let duracao = inicio.elapsed();
+
+        if let Some(erro) = erros.into_inner().unwrap().first() {
+            return Err(erro.clone().into());
+        }
+        let mut bytes_lidos = 0;
+        for (index, hash) in hashes.into_inner().unwrap() {
+            let arquivo = &self.amostra[index];
+            bytes_lidos += if arquivo.tamanho > block_size {
+                // Blocos inteiros que começam dentro da amostra
+                arquivo
+                    .tamanho
+                    .min(arquivo.bytes_amostra.div_ceil(block_size) * block_size)
+            } else {
+                arquivo.tamanho
+            };
+            let chave = (arquivo.caminho.clone(), config.block_size_str.clone());
+            let referencia = self
+                .referencias
+                .entry(chave)
+                .or_insert_with(|| hash.clone());
+            if *referencia != hash {
+                return Err(format!(
+                    "{} with {}: hash {} differs from previous round {}",
+                    arquivo.caminho,
+                    config.opcoes(true),
+                    hash,
+                    referencia
+                )
+                .into());
+            }
+        }
+        Ok((duracao, bytes_lidos))
+    }
+
+    // Mede cada candidata e fica com a melhor. Retorna a configuração e o MB/s dela.
+    fn melhor_de(
+        &mut self,
+        etapa: &str,
+        candidatas: Vec<Configuracao>,
+        mut melhor: Option<(Configuracao, f64)>,
+    ) -> Result<(Configuracao, f64), Box<dyn Error>> {
+        for config in candidatas {
+            let mut melhor_rodada: Option<(Duration, u64)> = None;
+            for rodada in 0..self.n_rodadas {
+                let (duracao, bytes) = self.executa_rodada(&config)?;
+                info!(
+                    "bench: {} round {} {} {:?}",
+                    etapa,
+                    rodada + 1,
+                    config.opcoes(true),
+                    duracao
+                );
+                if melhor_rodada.is_none_or(|(melhor_duracao, _)| duracao < melhor_duracao) {
+                    melhor_rodada = Some((duracao, bytes));
+                }
+            }
+            let (duracao, bytes) = melhor_rodada.unwrap_or_default();
+            let mb_s = bytes as f64 / 1_048_576.0 / duracao.as_secs_f64().max(f64::EPSILON);
+            self.imprime(&format!(
+                "{};{};{};{};{};{:.3};{:.1}",
+                etapa,
+                config.block_size_str,
+                config.buffer_size_str,
+                config.n_threads,
+                config.nome_modo(),
+                duracao.as_secs_f64(),
+                mb_s
+            ));
+            melhor = match melhor {
+                Some((atual, mb_s_atual)) if mb_s <= mb_s_atual * GANHO_MINIMO => {
+                    Some((atual, mb_s_atual))
+                }
+                _ => Some((config, mb_s)),
+            };
+        }
+        melhor.ok_or_else(|| "no configuration measured".into())
+    }
+
+    // Uma opção por vez, a partir da configuração atual
+    fn busca(
+        &mut self,
+        inicial: Configuracao,
+        com_blocksize: bool,
+    ) -> Result<(Configuracao, f64), Box<dyn Error>> {
+        self.imprime("stage;blocksize;buffersize;n_threads;mode;best_s;MB/s");
+        let mut melhor = self.melhor_de("current", vec![inicial], None)?;
+
+        let candidatas = BUFFERS
+            .iter()
+            .filter(|buffer| **buffer != melhor.0.buffer_size_str)
+            .map(|buffer| Configuracao {
+                buffer_size_str: buffer.to_string(),
+                ..melhor.0.clone()
+            })
+            .collect();
+        melhor = self.melhor_de("buffersize", candidatas, Some(melhor))?;
+
+        let candidatas = N_THREADS
+            .iter()
+            .filter(|n_threads| **n_threads != melhor.0.n_threads)
+            .map(|n_threads| Configuracao {
+                n_threads: *n_threads,
+                ..melhor.0.clone()
+            })
+            .collect();
+        melhor = self.melhor_de("n_threads", candidatas, Some(melhor))?;
+
+        let mut candidatas: Vec<Configuracao> =
+            [ModoLeitura::Read, ModoLeitura::Mmap, ModoLeitura::IoUring]
+                .iter()
+                .filter(|modo| self.com_mmap || **modo != ModoLeitura::Mmap)
+                .map(|modo| Configuracao {
+                    modo_leitura: *modo,
+                    sequencial: false,
+                    ..melhor.0.clone()
+                })
+                .collect();
+        candidatas.push(Configuracao {
+            modo_leitura: ModoLeitura::Read,
+            sequencial: true,
+            ..melhor.0.clone()
+        });
+        candidatas.retain(|config| {
+            config.sequencial != melhor.0.sequencial || config.modo_leitura != melhor.0.modo_leitura
+        });
+        melhor = self.melhor_de("mode", candidatas, Some(melhor))?;
+
+        if com_blocksize {
+            let candidatas = BLOCOS
+                .iter()
+                .filter(|bloco| **bloco != melhor.0.block_size_str)
+                .map(|bloco| Configuracao {
+                    block_size_str: bloco.to_string(),
+                    ..melhor.0.clone()
+                })
+                .collect();
+            melhor = self.melhor_de("blocksize", candidatas, Some(melhor))?;
+        }
+        Ok(melhor)
+    }
+}
+
+fn configuracao_atual(main_args: &Argumentos) -> Configuracao {
+    Configuracao {
+        block_size_str: main_args.block_size_str.clone(),
+        buffer_size_str: main_args.buffer_size_str.clone(),
+        n_threads: main_args.n_threads,
+        modo_leitura: main_args.modo_leitura,
+        sequencial: main_args.agendamento_disco == AgendamentoDisco::Hdd,
+    }
+}
+
+fn prepara_bench(
+    files: &[String],
+    tamanho_amostra: u64,
+    n_rodadas: u32,
+    para_stderr: bool,
+) -> Result<Bench, Box<dyn Error>> {
+    let amostra = escolhe_amostra(files, tamanho_amostra);
+    if amostra.is_empty() {
+        return Err("no data to sample (no files, or only empty files)".into());
+    }
+    let bytes_amostra: u64 = amostra.iter().map(|arquivo| arquivo.bytes_amostra).sum();
+    eprintln!(
+        "{}sample of {} files, {} bytes (of {} files found)",
+        if para_stderr { "Auto: " } else { "Bench: " },
+        amostra.len(),
+        bytes_amostra,
+        files.len()
+    );
+    if !cfg!(target_os = "linux") {
+        warn!("bench: page cache is not dropped between rounds outside Linux");
+        eprintln!("Warning: page cache is not dropped between rounds: after the first round, data may come from memory.");
+    }
+    Ok(Bench {
+        amostra,
+        n_rodadas,
+        referencias: HashMap::new(),
+        para_stderr,
+        com_mmap: true,
+    })
+}
+
+// hush bench <path>: rodadas sobre uma amostra do path e a recomendação de opções
+pub fn bench(
+    main_args: &Argumentos,
+    path: &str,
+    tamanho_amostra: u64,
+    n_rodadas: u32,
+) -> Result<(), Box<dyn Error>> {
+    let files = search_files(path)?;
+    let mut bench = prepara_bench(&files, tamanho_amostra, n_rodadas, false)?;
+    let inicial = configuracao_atual(main_args);
+    let (melhor, mb_s) = bench.busca(inicial.clone(), true)?;
+
+    println!("Recommended: {} ({:.1} MB/s)", melhor.opcoes(true), mb_s);
+    if melhor.modo_leitura == ModoLeitura::Mmap {
+        eprintln!("Note: use --mmap only on files nobody is writing to: a file that shrinks while it is hashed kills hush (SIGBUS).");
+    }
+    if melhor.block_size_str != inicial.block_size_str {
+        eprintln!(
+            "Note: blocksize is part of the hash of big files (hsha256-{}). Use the same blocksize to compare with hash files made before.",
+            melhor.block_size_str
+        );
+    }
+    eprintln!("Same hash in all rounds for each blocksize.");
+    Ok(())
+}
+
+// gen --auto: busca curta antes do gen de verdade. O blocksize não muda, para o hash dos
+// arquivos grandes continuar comparável.
+pub fn auto_ajuste(
+    main_args: &mut Argumentos,
+    files: &[String],
+    tamanho_amostra: u64,
+) -> Result<(), Box<dyn Error>> {
+    let mut bench = prepara_bench(files, tamanho_amostra, 1, true)?;
+    bench.com_mmap = main_args.modo_leitura == ModoLeitura::Mmap;
+    let (melhor, mb_s) = bench.busca(configuracao_atual(main_args), false)?;
+
+    main_args.buffer_size_str = melhor.buffer_size_str.clone();
+    main_args.buffer_size = ParseSize(&melhor.buffer_size_str)? as u32;
+    main_args.n_threads = melhor.n_threads;
+    main_args.n_workers = melhor.n_threads;
+    main_args.n_max_concur = melhor.n_threads;
+    main_args.modo_leitura = melhor.modo_leitura;
+    // Medido na amostra, que é de um disco só se os arquivos estão num disco só. Com mais de
+    // um disco (HDD e SSD juntos), fica o agendamento por dispositivo.
+    let n_dispositivos = disco::conta_dispositivos(files);
+    if n_dispositivos <= 1 {
+        main_args.agendamento_disco = if melhor.sequencial {
+            AgendamentoDisco::Hdd
+        } else {
+            AgendamentoDisco::Ssd
+        };
+    }
+    pool::pool().ajusta_threads(melhor.n_threads);
+    eprintln!("Auto: using {} ({:.1} MB/s)", melhor.opcoes(false), mb_s);
+    if n_dispositivos > 1 {
+        eprintln!(
+            "Auto: files on {} devices, the disk scheduling of the sample is not used (--disk_sched stays as given)",
+            n_dispositivos
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,232 @@
+// Leitura sem estragar o page cache dos outros programas.
+//
+// --direct: abre os arquivos com O_DIRECT e lê em buffers alinhados, sem passar pelo cache.
+// --fadvise: leitura normal, avisando o kernel que é sequencial (SEQUENTIAL) e que o
+//            bloco já hasheado pode sair do cache (DONTNEED).
+// Só no Linux. Nos outros sistemas as opções são ignoradas com aviso.
+
+use log::warn;
+use once_cell::sync::OnceCell;
+use std::alloc::{self, Layout};
+use std::fs::File;
+use std::io;
+
+use crate::inteiro::FatiaInteiro;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoliticaCache {
+    #[default]
+    Normal,
+    Direct,
+    Fadvise,
+}
+
+pub static POLITICA_CACHE: OnceCell<PoliticaCache> = OnceCell::new();
+
+// Alinhamento de offset, tamanho e endereço exigido pelo O_DIRECT (cobre setores de 512 e 4096)
+const ALINHAMENTO: usize = 4096;
+
+pub fn politica() -> PoliticaCache {
+    POLITICA_CACHE.get().copied().unwrap_or_default()
+}
+
+pub fn define_politica(politica: PoliticaCache) {
+    let politica = if cfg!(target_os = "linux") {
+        politica
+    } else {
+        if politica != PoliticaCache::Normal {
+            eprintln!("--direct and --fadvise are only available on Linux. Ignored.");
+        }
+        PoliticaCache::Normal
+    };
+    POLITICA_CACHE
+        .set(politica)
+        .expect("POLITICA_CACHE can only be set once");
+}
+
+// Buffer com endereço alinhado, para leituras com O_DIRECT
+pub struct BufferAlinhado {
+    ptr: *mut u8,
+    layout: Layout,
+}
+
+impl BufferAlinhado {
+    pub fn new(tamanho: usize) -> BufferAlinhado {
+        let tamanho = tamanho.max(1).div_ceil(ALINHAMENTO) * ALINHAMENTO;
+        let layout = Layout::from_size_align(tamanho, ALINHAMENTO).unwrap();
+        // Safety: layout com tamanho maior que zero
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        if ptr.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        BufferAlinhado { ptr, layout }
+    }
+}
+
+impl std::ops::Deref for BufferAlinhado {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.layout.size()) }
+    }
+}
+
+impl std::ops::DerefMut for BufferAlinhado {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.layout.size()) }
+    }
+}
+
+impl Drop for BufferAlinhado {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr, self.layout) }
+    }
+}
+
+// Abre o arquivo para hash conforme a política. `permite_direto` é false quando quem vai ler
+// não usa buffers alinhados (mmap, io_uring).
+#[cfg(target_os = "linux")]
+pub fn abre_arquivo(file_path: &str, permite_direto: bool) -> io::Result<File> {
+    use std::fs::OpenOptions;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    match politica() {
+        PoliticaCache::Direct if permite_direto => {
+            match OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_DIRECT)
+                .open(file_path)
+            {
+                Ok(file) => Ok(file),
+                // Sistema de arquivos sem O_DIRECT (ex: tmpfs): lê normal, sem encher o cache
+                Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+                    warn!("O_DIRECT not supported, using fadvise: {}", file_path);
+                    let file = File::open(file_path)?;
+                    unsafe {
+                        libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL)
+                    };
+                    Ok(file)
+                }
+                Err(e) => Err(e),
+            }
+        }
+        PoliticaCache::Normal => File::open(file_path),
+        _ => {
+            let file = File::open(file_path)?;
+            unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL) };
+            Ok(file)
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn abre_arquivo(file_path: &str, _permite_direto: bool) -> io::Result<File> {
+    File::open(file_path)
+}
+
+// Tira do page cache um trecho já hasheado. Com len 0, até o fim do arquivo.
+#[cfg(target_os = "linux")]
+pub fn descarta_cache(file: &File, inicio: u64, len: u64) {
+    use std::os::unix::io::AsRawFd;
+
+    if politica() == PoliticaCache::Normal {
+        return;
+    }
+    let resultado = unsafe {
+        libc::posix_fadvise(
+            file.as_raw_fd(),
+            inicio as libc::off_t,
+            len as libc::off_t,
+            libc::POSIX_FADV_DONTNEED,
+        )
+    };
+    if resultado != 0 {
+        warn!("posix_fadvise DONTNEED failed: {}", resultado);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn descarta_cache(_file: &File, _inicio: u64, _len: u64) {}
+
+// Tira o arquivo todo do page cache, qualquer que seja a política (bench: cada rodada lê do disco)
+#[cfg(target_os = "linux")]
+pub fn esvazia_cache(file_path: &str) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = File::open(file_path)?;
+    let resultado =
+        unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    if resultado != 0 {
+        return Err(io::Error::from_raw_os_error(resultado));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn esvazia_cache(_file_path: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "dropping the page cache is only available on Linux",
+    ))
+}
+
+// Hash de [inicio, fim) com leituras alinhadas (funciona com ou sem O_DIRECT no descritor).
+// Lê a partir do offset alinhado anterior e pula os bytes antes de `inicio`.
+// Para no fim do arquivo, como o read() normal: com fim = u64::MAX, hasheia o arquivo todo.
+// Com `inteiro`, os mesmos bytes vão também para o sha256 do arquivo todo.
+#[cfg(target_os = "linux")]
+pub fn hash_intervalo_direto(
+    file: &File,
+    inicio: u64,
+    fim: u64,
+    buffer_size: usize,
+    mut inteiro: Option<&mut FatiaInteiro>,
+) -> io::Result<String> {
+    use sha2::{Digest, Sha256};
+    use std::os::unix::fs::FileExt;
+
+    let alinhamento = ALINHAMENTO as u64;
+    let mut buffer = BufferAlinhado::new(buffer_size);
+    let mut hasher = Sha256::new();
+    let mut offset = inicio / alinhamento * alinhamento;
+    let mut pular = (inicio - offset) as usize;
+
+    while offset < fim {
+        crate::controle::consome(buffer.len());
+        let n = match file.read_at(&mut buffer, offset) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if n <= pular {
+            break; // EOF
+        }
+        let ate = std::cmp::min(n as u64, fim - offset) as usize;
+        if ate > pular {
+            hasher.update(&buffer[pular..ate]);
+            if let Some(fatia) = &mut inteiro {
+                fatia.atualiza(&buffer[pular..ate]);
+            }
+        }
+        pular = 0;
+        offset += n as u64;
+        if n % ALINHAMENTO != 0 {
+            break; // leitura curta com O_DIRECT só acontece no fim do arquivo
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn hash_intervalo_direto(
+    _file: &File,
+    _inicio: u64,
+    _fim: u64,
+    _buffer_size: usize,
+    _inteiro: Option<&mut FatiaInteiro>,
+) -> io::Result<String> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "direct I/O is only available on Linux",
+    ))
+}
@@ -0,0 +1,584 @@
+// Carimbo de tempo RFC 3161 sobre o manifest (hush timestamp; check com --tsa_cert).
+//
+// O pedido vai por HTTP (POST, application/timestamp-query) para a TSA de --tsa_url ou da
+// variável HUSH_TSA_URL, com o sha256 da forma canônica do manifest (a mesma da assinatura),
+// um nonce e certReq. A resposta inteira (TimeStampResp, DER) fica ao lado do manifest, em
+// <manifest>.tsr: é o formato de `openssl ts -reply -in`.
+// Conferir um carimbo:
+//   - o sha256 no TSTInfo tem que ser o da forma canônica do manifest de agora;
+//   - a assinatura CMS (atributos assinados: content-type e message-digest) tem que bater com
+//     o certificado da TSA que vem no token (RSA PKCS#1 v1.5 ou ECDSA P-256);
+//   - o certificado da TSA tem que ter o uso timeStamping e valer na hora do carimbo;
+//   - e ele tem que ser um dos certificados de --tsa_cert (PEM), ou ser emitido por um deles.
+// Só um nível de cadeia, sem CRL/OCSP: quem precisa de mais usa `openssl ts -verify` no .tsr.
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use cms::cert::CertificateChoices;
+use cms::content_info::ContentInfo;
+use cms::signed_data::{SignedData, SignerIdentifier, SignerInfo};
+use der::asn1::{Any, BitString, ObjectIdentifier, OctetString, Uint};
+use der::{Decode, Encode, Sequence, Tag, Tagged};
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::error::Error;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+use x509_cert::ext::pkix::ExtendedKeyUsage;
+use x509_cert::ext::Extensions;
+use x509_cert::spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfoOwned};
+use x509_cert::Certificate;
+
+use crate::assinatura;
+
+const OID_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.1");
+const OID_SHA384: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.2");
+const OID_SHA512: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.3");
+const OID_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const OID_SHA256_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.11");
+const OID_SHA384_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.12");
+const OID_SHA512_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.13");
+const OID_EC: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+const OID_P256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+const OID_ECDSA_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+const OID_ECDSA_SHA384: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.3");
+const OID_ECDSA_SHA512: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.4");
+const OID_SIGNED_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.2");
+const OID_TST_INFO: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.16.1.4");
+const OID_CONTENT_TYPE: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.3");
+const OID_MESSAGE_DIGEST: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.4");
+const OID_TIME_STAMPING: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.5.5.7.3.8");
+const OID_EXT_SKI: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.29.14");
+const OID_EXT_EKU: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.29.37");
+
+pub const VARIAVEL_URL: &str = "HUSH_TSA_URL";
+const TAMANHO_MAXIMO_RESPOSTA: u64 = 1024 * 1024;
+
+#[derive(Clone, Debug, Sequence)]
+struct MessageImprint {
+    hash_algorithm: AlgorithmIdentifierOwned,
+    hashed_message: OctetString,
+}
+
+#[derive(Clone, Debug, Sequence)]
+struct TimeStampReq {
+    version: u8,
+    message_imprint: MessageImprint,
+    req_policy: Option<ObjectIdentifier>,
+    nonce: Option<Uint>,
+    #[asn1(default = "Default::default")]
+    cert_req: bool,
+}
+
+#[derive(Clone, Debug, Sequence)]
+struct PkiStatusInfo {
+    status: u8,
+    status_string: Option<Vec<String>>,
+    fail_info: Option<BitString>,
+}
+
+#[derive(Clone, Debug, Sequence)]
+struct TimeStampResp {
+    status: PkiStatusInfo,
+    time_stamp_token: Option<ContentInfo>,
+}
+
+#[derive(Clone, Debug, Sequence)]
+struct Accuracy {
+    seconds: Option<Uint>,
+    #[asn1(context_specific = "0", tag_mode = "IMPLICIT", optional = "true")]
+    millis: Option<Uint>,
+    #[asn1(context_specific = "1", tag_mode = "IMPLICIT", optional = "true")]
+    micros: Option<Uint>,
+}
+
+#[derive(Clone, Debug, Sequence)]
+struct TstInfo {
+    version: u8,
+    policy: ObjectIdentifier,
+    message_imprint: MessageImprint,
+    serial_number: Uint,
+    // GeneralizedTime, às vezes com fração de segundo: lido à mão
+    gen_time: Any,
+    accuracy: Option<Accuracy>,
+    #[asn1(default = "Default::default")]
+    ordering: bool,
+    nonce: Option<Uint>,
+    #[asn1(context_specific = "0", tag_mode = "EXPLICIT", optional = "true")]
+    tsa: Option<Any>,
+    #[asn1(context_specific = "1", tag_mode = "IMPLICIT", optional = "true")]
+    extensions: Option<Extensions>,
+}
+
+// O que o check e o timestamp mostram de um carimbo conferido
+pub struct Carimbo {
+    pub hora: String,
+    pub tsa: String,
+    pub serial: String,
+    // Sem --tsa_cert, o certificado da TSA não foi conferido
+    pub confiavel: bool,
+}
+
+// Onde fica o carimbo, sem --tsr
+pub fn caminho_carimbo(manifest: &str) -> String {
+    format!("{}.tsr", manifest)
+}
+
+#[derive(Clone, Copy)]
+enum AlgResumo {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl AlgResumo {
+    fn de_oid(oid: &ObjectIdentifier) -> Result<AlgResumo, Box<dyn Error>> {
+        match *oid {
+            OID_SHA256 => Ok(AlgResumo::Sha256),
+            OID_SHA384 => Ok(AlgResumo::Sha384),
+            OID_SHA512 => Ok(AlgResumo::Sha512),
+            _ => Err(format!("unsupported digest algorithm {}", oid).into()),
+        }
+    }
+
+    fn resumo(self, dados: &[u8]) -> Vec<u8> {
+        match self {
+            AlgResumo::Sha256 => Sha256::digest(dados).to_vec(),
+            AlgResumo::Sha384 => Sha384::digest(dados).to_vec(),
+            AlgResumo::Sha512 => Sha512::digest(dados).to_vec(),
+        }
+    }
+
+    fn pkcs1(self) -> Pkcs1v15Sign {
+        match self {
+            AlgResumo::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+            AlgResumo::Sha384 => Pkcs1v15Sign::new::<Sha384>(),
+            AlgResumo::Sha512 => Pkcs1v15Sign::new::<Sha512>(),
+        }
+    }
+}
+
+// Pede o carimbo do manifest à TSA e grava a resposta em caminho_tsr
+pub fn pede(
+    manifest: &str,
+    url: &str,
+    caminho_tsr: &str,
+    certificados: Option<&str>,
+) -> Result<Carimbo, Box<dyn Error>> {
+    let resumo = Sha256::digest(assinatura::canonico(manifest)?);
+    let mut nonce = [0u8; 8];
+    OsRng.fill_bytes(&mut nonce);
+    nonce[0] &= 0x7f;
+    let nonce = Uint::new(&nonce)?;
+    let pedido = TimeStampReq {
+        version: 1,
+        message_imprint: MessageImprint {
+            hash_algorithm: AlgorithmIdentifierOwned {
+                oid: OID_SHA256,
+                parameters: None,
+            },
+            hashed_message: OctetString::new(resumo.to_vec())?,
+        },
+        req_policy: None,
+        nonce: Some(nonce.clone()),
+        cert_req: true,
+    };
+
+    let resposta = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(60))
+        .build()
+        .post(url)
+        .set("Content-Type", "application/timestamp-query")
+        .send_bytes(&pedido.to_der()?)?;
+    let mut corpo = Vec::new();
+    resposta
+        .into_reader()
+        .take(TAMANHO_MAXIMO_RESPOSTA)
+        .read_to_end(&mut corpo)?;
+
+    let carimbo = confere_resposta(&corpo, &resumo, Some(&nonce), certificados)
+        .map_err(|e| format!("{}: {}", url, e))?;
+    fs::write(caminho_tsr, &corpo)?;
+    Ok(carimbo)
+}
+
+// Confere o carimbo gravado do manifest. Sem certificados, não confere em quem a TSA é.
+pub fn confere(
+    manifest: &str,
+    caminho_tsr: &str,
+    certificados: Option<&str>,
+) -> Result<Carimbo, Box<dyn Error>> {
+    let resumo = Sha256::digest(assinatura::canonico(manifest)?);
+    let corpo = fs::read(caminho_tsr).map_err(|e| format!("{}: {}", caminho_tsr, e))?;
+    confere_resposta(&corpo, &resumo, None, certificados)
+        .map_err(|e| format!("{}: {}", caminho_tsr, e).into())
+}
+
+fn confere_resposta(
+    corpo: &[u8],
+    resumo: &[u8],
+    nonce: Option<&Uint>,
+    certificados: Option<&str>,
+) -> Result<Carimbo, Box<dyn Error>> {
+    let resposta =
+        TimeStampResp::from_der(corpo).map_err(|e| format!("not a timestamp response: {}", e))?;
+    // 0 granted, 1 grantedWithMods
+    if resposta.status.status > 1 {
+        let texto = resposta.status.status_string.unwrap_or_default().join(" ");
+        return Err(format!(
+            "timestamp refused by the TSA (status {}) {}",
+            resposta.status.status, texto
+        )
+        .into());
+    }
+    let token = resposta
+        .time_stamp_token
+        .ok_or("timestamp response without token")?;
+    if token.content_type != OID_SIGNED_DATA {
+        return Err("timestamp token is not CMS SignedData".into());
+    }
+    let dados_assinados: SignedData = token.content.decode_as()?;
+    let conteudo = &dados_assinados.encap_content_info;
+    if conteudo.econtent_type != OID_TST_INFO {
+        return Err("timestamp token without TSTInfo".into());
+    }
+    let tst_der = conteudo
+        .econtent
+        .as_ref()
+        .ok_or("timestamp token without TSTInfo")?
+        .decode_as::<OctetString>()?;
+    let tst = TstInfo::from_der(tst_der.as_bytes())?;
+
+    if tst.message_imprint.hash_algorithm.oid != OID_SHA256
+        || tst.message_imprint.hashed_message.as_bytes() != resumo
+    {
+        return Err("timestamp is not of this hash file (hash file changed?)".into());
+    }
+    if let Some(nonce) = nonce {
+        if tst.nonce.as_ref() != Some(nonce) {
+            return Err("timestamp response with another nonce".into());
+        }
+    }
+    let (hora, segundos) = le_hora(&tst.gen_time)?;
+
+    let assinante = dados_assinados
+        .signer_infos
+        .0
+        .iter()
+        .next()
+        .ok_or("timestamp token without signer")?;
+    let certificados_token: Vec<&Certificate> = dados_assinados
+        .certificates
+        .as_ref()
+        .map(|conjunto| {
+            conjunto
+                .0
+                .iter()
+                .filter_map(|escolha| match escolha {
+                    CertificateChoices::Certificate(certificado) => Some(certificado),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let ancoras = match certificados {
+        Some(caminho) => Some(le_certificados(caminho)?),
+        None => None,
+    };
+    let certificado_tsa = certificados_token
+        .iter()
+        .copied()
+        .chain(ancoras.iter().flatten())
+        .find(|certificado| identifica(certificado, &assinante.sid))
+        .ok_or("TSA certificate not in the token (and not in --tsa_cert)")?;
+
+    confere_assinante(assinante, tst_der.as_bytes(), certificado_tsa)?;
+    confere_certificado_tsa(certificado_tsa, segundos)?;
+    let confiavel = match &ancoras {
+        Some(ancoras) => {
+            confere_emissor(certificado_tsa, ancoras)?;
+            true
+        }
+        None => false,
+    };
+
+    Ok(Carimbo {
+        hora,
+        tsa: certificado_tsa.tbs_certificate.subject.to_string(),
+        serial: hex::encode(tst.serial_number.as_bytes()),
+        confiavel,
+    })
+}
+
+// Certificados PEM (um ou mais) ou um DER
+fn le_certificados(caminho: &str) -> Result<Vec<Certificate>, Box<dyn Error>> {
+    let dados = fs::read(caminho).map_err(|e| format!("{}: {}", caminho, e))?;
+    let certificados = if dados.windows(5).any(|janela| janela == b"-----") {
+        Certificate::load_pem_chain(&dados).map_err(|e| format!("{}: {}", caminho, e))?
+    } else {
+        vec![Certificate::from_der(&dados).map_err(|e| format!("{}: {}", caminho, e))?]
+    };
+    if certificados.is_empty() {
+        return Err(format!("{}: no certificate", caminho).into());
+    }
+    Ok(certificados)
+}
+
+fn identifica(certificado: &Certificate, sid: &SignerIdentifier) -> bool {
+    let tbs = &certificado.tbs_certificate;
+    match sid {
+        SignerIdentifier::IssuerAndSerialNumber(emissor_serial) => {
+            tbs.issuer == emissor_serial.issuer && tbs.serial_number == emissor_serial.serial_number
+        }
+        SignerIdentifier::SubjectKeyIdentifier(ski) => extensao(certificado, OID_EXT_SKI)
+            .is_some_and(|valor| ski.to_der().is_ok_and(|der| der == valor)),
+    }
+}
+
+fn extensao(certificado: &Certificate, oid: ObjectIdentifier) -> Option<&[u8]> {
+    certificado
+        .tbs_certificate
+        .extensions
+        .as_ref()?
+        .iter()
+        .find(|extensao| extensao.extn_id == oid)
+        .map(|extensao| extensao.extn_value.as_bytes())
+}
+
+// Atributos assinados do CMS: content-type TSTInfo e message-digest do TSTInfo
+fn confere_assinante(
+    assinante: &SignerInfo,
+    tst_der: &[u8],
+    certificado: &Certificate,
+) -> Result<(), Box<dyn Error>> {
+    let alg_resumo = AlgResumo::de_oid(&assinante.digest_alg.oid)?;
+    let atributos = assinante
+        .signed_attrs
+        .as_ref()
+        .ok_or("timestamp token without signed attributes")?;
+    let valor = |oid: ObjectIdentifier| {
+        atributos
+            .iter()
+            .find(|atributo| atributo.oid == oid)
+            .and_then(|atributo| atributo.values.iter().next())
+    };
+    let tipo = valor(OID_CONTENT_TYPE).ok_or("timestamp token without content-type")?;
+    if tipo.decode_as::<ObjectIdentifier>()? != OID_TST_INFO {
+        return Err("timestamp token content-type is not TSTInfo".into());
+    }
+    let resumo = valor(OID_MESSAGE_DIGEST).ok_or("timestamp token without message-digest")?;
+    if resumo.decode_as::<OctetString>()?.as_bytes() != alg_resumo.resumo(tst_der).as_slice() {
+        return Err("timestamp token message-digest does not match TSTInfo".into());
+    }
+    confere_assinatura(
+        &certificado.tbs_certificate.subject_public_key_info,
+        &assinante.signature_algorithm,
+        alg_resumo,
+        &atributos.to_der()?,
+        assinante.signature.as_bytes(),
+    )
+    .map_err(|e| format!("timestamp token signature: {}", e).into())
+}
+
+// Uso timeStamping, e valendo na hora do carimbo
+fn confere_certificado_tsa(certificado: &Certificate, hora: u64) -> Result<(), Box<dyn Error>> {
+    let uso = extensao(certificado, OID_EXT_EKU)
+        .map(ExtendedKeyUsage::from_der)
+        .transpose()?;
+    if !uso.is_some_and(|uso| uso.0.contains(&OID_TIME_STAMPING)) {
+        return Err("TSA certificate without timeStamping usage".into());
+    }
+    let validade = &certificado.tbs_certificate.validity;
+    if hora < validade.not_before.to_unix_duration().as_secs()
+        || hora > validade.not_after.to_unix_duration().as_secs()
+    {
+        return Err("TSA certificate not valid at the time of the timestamp".into());
+    }
+    Ok(())
+}
+
+// O certificado da TSA é um dos confiáveis, ou foi emitido (e assinado) por um deles
+fn confere_emissor(
+    certificado: &Certificate,
+    ancoras: &[Certificate],
+) -> Result<(), Box<dyn Error>> {
+    if ancoras.iter().any(|ancora| ancora == certificado) {
+        return Ok(());
+    }
+    let tbs = certificado.tbs_certificate.to_der()?;
+    for ancora in ancoras {
+        if ancora.tbs_certificate.subject != certificado.tbs_certificate.issuer {
+            continue;
+        }
+        let alg_resumo = match certificado.signature_algorithm.oid {
+            OID_SHA256_RSA | OID_ECDSA_SHA256 => AlgResumo::Sha256,
+            OID_SHA384_RSA | OID_ECDSA_SHA384 => AlgResumo::Sha384,
+            OID_SHA512_RSA | OID_ECDSA_SHA512 => AlgResumo::Sha512,
+            oid => return Err(format!("unsupported certificate signature {}", oid).into()),
+        };
+        let assinatura = certificado
+            .signature
+            .as_bytes()
+            .ok_or("invalid certificate signature")?;
+        if confere_assinatura(
+            &ancora.tbs_certificate.subject_public_key_info,
+            &certificado.signature_algorithm,
+            alg_resumo,
+            &tbs,
+            assinatura,
+        )
+        .is_ok()
+        {
+            return Ok(());
+        }
+    }
+    Err(format!(
+        "TSA certificate ({}) not trusted: not in --tsa_cert, nor issued by one of them",
+        certificado.tbs_certificate.subject
+    )
+    .into())
+}
+
+fn confere_assinatura(
+    chave: &SubjectPublicKeyInfoOwned,
+    alg_assinatura: &AlgorithmIdentifierOwned,
+    alg_resumo: AlgResumo,
+    dados: &[u8],
+    assinatura: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    let resumo = alg_resumo.resumo(dados);
+    match chave.algorithm.oid {
+        OID_RSA => {
+            if !matches!(
+                alg_assinatura.oid,
+                OID_RSA | OID_SHA256_RSA | OID_SHA384_RSA | OID_SHA512_RSA
+            ) {
+                return Err(format!("unsupported signature {}", alg_assinatura.oid).into());
+            }
+            RsaPublicKey::from_public_key_der(&chave.to_der()?)?
+                .verify(alg_resumo.pkcs1(), &resumo, assinatura)
+                .map_err(|_| "does not verify".into())
+        }
+        OID_EC => {
+            let curva = chave
+                .algorithm
+                .parameters
+                .as_ref()
+                .map(|parametros| parametros.decode_as::<ObjectIdentifier>())
+                .transpose()?;
+            if curva != Some(OID_P256) {
+                return Err("unsupported elliptic curve (only P-256)".into());
+            }
+            let chave =
+                p256::ecdsa::VerifyingKey::from_sec1_bytes(chave.subject_public_key.raw_bytes())?;
+            let assinatura = p256::ecdsa::Signature::from_der(assinatura)?;
+            chave
+                .verify_prehash(&resumo, &assinatura)
+                .map_err(|_| "does not verify".into())
+        }
+        oid => Err(format!("unsupported key {}", oid).into()),
+    }
+}
+
+// GeneralizedTime "AAAAMMDDhhmmss[.f]Z": texto para mostrar e segundos desde 1970
+fn le_hora(valor: &Any) -> Result<(String, u64), Box<dyn Error>> {
+    let invalida = || format!("invalid timestamp time: {:?}", valor.value());
+    if valor.tag() != Tag::GeneralizedTime {
+        return Err(invalida().into());
+    }
+    let texto = std::str::from_utf8(valor.value()).map_err(|_| invalida())?;
+    let texto = texto.strip_suffix('Z').ok_or_else(invalida)?;
+    let (inteiro, fracao) = texto.split_once('.').unwrap_or((texto, ""));
+    if inteiro.len() != 14
+        || !inteiro.bytes().all(|b| b.is_ascii_digit())
+        || !fracao.bytes().all(|b| b.is_ascii_digit())
+    {
+        return Err(invalida().into());
+    }
+    let campo = |de: usize, ate: usize| inteiro[de..ate].parse::<u64>().unwrap_or(0);
+    let (ano, mes, dia) = (campo(0, 4), campo(4, 6), campo(6, 8));
+    let (hora, minuto, segundo) = (campo(8, 10), campo(10, 12), campo(12, 14));
+    if !(1..=12).contains(&mes) || !(1..=31).contains(&dia) || ano < 1970 {
+        return Err(invalida().into());
+    }
+
+    // Dias desde 1970-01-01 (calendário gregoriano)
+    let (a, m) = if mes <= 2 {
+        (ano - 1, mes + 9)
+    } else {
+        (ano, mes - 3)
+    };
+    let era = a / 400;
+    let ano_da_era = a - era * 400;
+    let dia_do_ano = (153 * m + 2) / 5 + dia - 1;
+    let dia_da_era = ano_da_era * 365 + ano_da_era / 4 - ano_da_era / 100 + dia_do_ano;
+    let dias = era * 146097 + dia_da_era - 719468;
+    let segundos = dias * 86400 + hora * 3600 + minuto * 60 + segundo;
+
+    let fracao = if fracao.is_empty() {
+        String::new()
+    } else {
+        format!(".{}", fracao)
+    };
+    Ok((
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}{} UTC",
+            ano, mes, dia, hora, minuto, segundo, fracao
+        ),
+        segundos,
+    ))
+}
+
+// Sem --tsa_cert, o check só avisa que há um carimbo
+pub fn existe(caminho_tsr: &str) -> bool {
+    Path::new(caminho_tsr).is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Resposta da etc/tsa_local.py a um pedido sem nonce, do sha256 de DADOS
+    const RESPOSTA: &[u8] = include_bytes!("../etc/testdata/carimbo.tsr");
+    const CA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/etc/testdata/tsa_ca.pem");
+    const DADOS: &[u8] = b"hush timestamp test\n";
+
+    #[test]
+    fn confere_resumo_certo() {
+        let resumo = Sha256::digest(DADOS);
+        let carimbo = confere_resposta(RESPOSTA, &resumo, None, None).unwrap();
+        assert!(!carimbo.confiavel);
+        assert_eq!(carimbo.serial, "02");
+
+        let carimbo = confere_resposta(RESPOSTA, &resumo, None, Some(CA)).unwrap();
+        assert!(carimbo.confiavel);
+        assert!(carimbo.tsa.contains("hush"), "{}", carimbo.tsa);
+    }
+
+    #[test]
+    fn recusa_resumo_errado() {
+        let resumo = Sha256::digest(b"hush timestamp test, changed\n");
+        for certificados in [None, Some(CA)] {
+            let erro = confere_resposta(RESPOSTA, &resumo, None, certificados)
+                .err()
+                .unwrap()
+                .to_string();
+            assert!(erro.contains("not of this hash file"), "{}", erro);
+        }
+    }
+
+    #[test]
+    fn recusa_outro_nonce() {
+        let resumo = Sha256::digest(DADOS);
+        let nonce = Uint::new(&[1, 2, 3]).unwrap();
+        let erro = confere_resposta(RESPOSTA, &resumo, Some(&nonce), Some(CA))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(erro.contains("another nonce"), "{}", erro);
+    }
+}
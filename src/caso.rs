@@ -0,0 +1,122 @@
+// Dados do caso: número, perito, item de evidência, órgão e notas.
+//
+// Vêm de --case_file File, um arquivo pequeno com uma linha "chave: valor" por dado:
+//   case: 2024/0123
+//   examiner: Fulano de Tal
+//   evidence: Item 04 - HD Seagate 1TB, serial Z1D2...
+//   agency: Instituto de Criminalística
+//   notes: qualquer texto
+// e de --case, --examiner, --evidence, --agency e --notes, que valem mais que o arquivo.
+// No gen e no check vão para o log de auditoria (case); no report, para o cabeçalho.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct Caso {
+    #[serde(rename = "case", skip_serializing_if = "Option::is_none")]
+    pub numero: Option<String>,
+    #[serde(rename = "examiner", skip_serializing_if = "Option::is_none")]
+    pub perito: Option<String>,
+    #[serde(rename = "evidence", skip_serializing_if = "Option::is_none")]
+    pub evidencia: Option<String>,
+    #[serde(rename = "agency", skip_serializing_if = "Option::is_none")]
+    pub orgao: Option<String>,
+    #[serde(rename = "notes", skip_serializing_if = "Option::is_none")]
+    pub notas: Option<String>,
+}
+
+const OPCOES: [&str; 5] = ["--case", "--examiner", "--evidence", "--agency", "--notes"];
+
+impl Caso {
+    pub fn vazio(&self) -> bool {
+        self.campos().iter().all(|(_, valor)| valor.is_none())
+    }
+
+    // (nome, valor), na ordem do relatório
+    pub fn campos(&self) -> [(&'static str, &Option<String>); 5] {
+        [
+            ("Case", &self.numero),
+            ("Examiner", &self.perito),
+            ("Evidence item", &self.evidencia),
+            ("Agency", &self.orgao),
+            ("Notes", &self.notas),
+        ]
+    }
+
+    fn campo(&mut self, chave: &str) -> Option<&mut Option<String>> {
+        match chave {
+            "case" | "--case" => Some(&mut self.numero),
+            "examiner" | "--examiner" => Some(&mut self.perito),
+            "evidence" | "--evidence" => Some(&mut self.evidencia),
+            "agency" | "--agency" => Some(&mut self.orgao),
+            "notes" | "--notes" => Some(&mut self.notas),
+            _ => None,
+        }
+    }
+
+    pub fn de_arquivo(caminho: &str) -> Result<Caso, Box<dyn Error>> {
+        let texto = fs::read_to_string(caminho).map_err(|e| format!("{}: {}", caminho, e))?;
+        let mut caso = Caso::default();
+        for (n_linha, linha) in texto.lines().enumerate() {
+            let linha = linha.trim();
+            if linha.is_empty() || linha.starts_with('#') {
+                continue;
+            }
+            let Some((chave, valor)) = linha.split_once(':') else {
+                return Err(format!("{}: line {}: not 'key: value'", caminho, n_linha + 1).into());
+            };
+            let chave = chave.trim().to_lowercase();
+            let Some(campo) = caso.campo(&chave) else {
+                return Err(format!(
+                    "{}: line {}: unknown key {} (use case, examiner, evidence, agency or notes)",
+                    caminho,
+                    n_linha + 1,
+                    chave
+                )
+                .into());
+            };
+            *campo = Some(valor.trim().to_string());
+        }
+        Ok(caso)
+    }
+
+    // --case_file e as opções de cada dado, que valem mais
+    pub fn de_args(args: &[String]) -> Result<Caso, Box<dyn Error>> {
+        let mut caso = match args.iter().position(|x| x == "--case_file") {
+            Some(index) => match args.get(index + 1) {
+                Some(caminho) => Caso::de_arquivo(caminho)?,
+                None => return Err("--case_file provided without a file.".into()),
+            },
+            None => Caso::default(),
+        };
+        for opcao in OPCOES {
+            if let Some(index) = args.iter().position(|x| x == opcao) {
+                let Some(valor) = args.get(index + 1) else {
+                    return Err(format!("{} provided without a value.", opcao).into());
+                };
+                if let Some(campo) = caso.campo(opcao) {
+                    *campo = Some(valor.to_string());
+                }
+            }
+        }
+        Ok(caso)
+    }
+
+    // Os dados que faltam aqui vêm de outro (ex: do log de auditoria)
+    pub fn completa(&mut self, outro: &Caso) {
+        let campos = [
+            (&mut self.numero, &outro.numero),
+            (&mut self.perito, &outro.perito),
+            (&mut self.evidencia, &outro.evidencia),
+            (&mut self.orgao, &outro.orgao),
+            (&mut self.notas, &outro.notas),
+        ];
+        for (campo, valor) in campos {
+            if campo.is_none() {
+                campo.clone_from(valor);
+            }
+        }
+    }
+}
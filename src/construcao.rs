@@ -0,0 +1,156 @@
+// Construção do hush: como o arquivo, dividido em blocos, vira um hash. Versionada.
+//
+// Blocos (as duas versões): arquivo de L bytes e blocksize B. Bloco i = bytes [i*B, min((i+1)*B, L)),
+// para i = 0 .. n-1, n = ceil(L / B) (arquivo vazio: n = 0). Di = sha256(bloco i).
+//
+// No manifest, o algoritmo é "<nome>-<B>", com B como foi dado no --blocksize (ex: hsha256-50MB).
+// A forma antiga "<nome>|<B>" ainda é aceita na leitura (parse_line troca o "|" por "-"), mas o gen
+// não a escreve.
+//
+// hsha256v1, no manifest "hsha256-<B>" (ou "hsha256v1-<B>"). As regras de sempre, fixadas:
+//   - n = 1: o hash é D0 em hex, o sha256 do arquivo;
+//   - senão: sha256(hex(D0) || hex(D1) || ... || hex(Dn-1)), com os hex em minúsculas, 64 bytes
+//     ASCII cada, sem separador (n = 0: sha256 de nada);
+//   - o gen usa "sha256" (o arquivo inteiro) quando L <= B, e hsha256 só quando L > B.
+//
+// hsha256v2, no manifest "hsha256v2-<B>":
+//   sha256("hush-hsha256-v2" || 0x00 || u64be(L) || u64be(B) || u64be(n) || D0 || ... || Dn-1)
+//   com os Di em bytes (32 cada). Vale para qualquer L, inclusive 0 e um bloco só: o gen usa
+//   hsha256v2 em todos os arquivos. Tamanho, blocksize e número de blocos entram no hash: o
+//   mesmo conjunto de digests não dá o mesmo valor para arquivos diferentes.
+//
+// O check confere as duas: a versão vem do nome do algoritmo, linha a linha.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+
+const DOMINIO_V2: &[u8] = b"hush-hsha256-v2\x00";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Construcao {
+    #[default]
+    V1,
+    V2,
+}
+
+impl Construcao {
+    // Nome no manifest e no --hash_alg. "hsha256" sem versão é a v1.
+    pub fn parse(alg: &str) -> Result<Construcao, Box<dyn Error>> {
+        match alg.to_lowercase().as_str() {
+            "hsha256" | "hsha256v1" => Ok(Construcao::V1),
+            "hsha256v2" => Ok(Construcao::V2),
+            _ => Err(format!("unknown algorithm: {} (use hsha256 or hsha256v2)", alg).into()),
+        }
+    }
+
+    // Nome que o gen escreve. A v1 continua "hsha256", como nos manifests de antes.
+    pub fn nome(&self) -> &'static str {
+        match self {
+            Construcao::V1 => "hsha256",
+            Construcao::V2 => "hsha256v2",
+        }
+    }
+
+    // v1: até o blocksize, o gen usa sha256 do arquivo inteiro
+    pub fn usa_sha256(&self, tamanho: u64, blocksize: u64) -> bool {
+        match self {
+            Construcao::V1 => tamanho <= blocksize,
+            Construcao::V2 => false,
+        }
+    }
+
+    // Hash do arquivo a partir dos hashes (hex) dos blocos, na ordem
+    pub fn combina(&self, hashes_blocos: &[&str], tamanho: u64, blocksize: u64) -> String {
+        match self {
+            Construcao::V1 => {
+                if hashes_blocos.len() == 1 {
+                    return hashes_blocos[0].to_string();
+                }
+                let mut hasher = Sha256::new();
+                for hash_bloco in hashes_blocos {
+                    hasher.update(hash_bloco.as_bytes());
+                }
+                format!("{:x}", hasher.finalize())
+            }
+            Construcao::V2 => {
+                let mut hasher = Sha256::new();
+                hasher.update(DOMINIO_V2);
+                hasher.update(tamanho.to_be_bytes());
+                hasher.update(blocksize.to_be_bytes());
+                hasher.update((hashes_blocos.len() as u64).to_be_bytes());
+                for hash_bloco in hashes_blocos {
+                    hasher.update(hex::decode(hash_bloco).expect("block hash is hex"));
+                }
+                format!("{:x}", hasher.finalize())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCKSIZE: u64 = 1024;
+
+    fn hex_sha256(dados: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(dados))
+    }
+
+    #[test]
+    fn v1_um_bloco_e_o_sha256_do_bloco() {
+        let d0 = hex_sha256(b"um bloco so");
+        assert_eq!(Construcao::V1.combina(&[&d0], 11, BLOCKSIZE), d0);
+    }
+
+    #[test]
+    fn v1_sem_blocos_e_o_sha256_de_nada() {
+        assert_eq!(
+            Construcao::V1.combina(&[], 0, BLOCKSIZE),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn v1_varios_blocos_e_o_sha256_dos_hex() {
+        let (d0, d1) = (hex_sha256(b"bloco 0"), hex_sha256(b"bloco 1"));
+        assert_eq!(
+            Construcao::V1.combina(&[&d0, &d1], BLOCKSIZE + 7, BLOCKSIZE),
+            hex_sha256(format!("{}{}", d0, d1).as_bytes())
+        );
+    }
+
+    #[test]
+    fn v2_separa_dominio_tamanho_e_blocksize() {
+        let (d0, d1) = (hex_sha256(b"bloco 0"), hex_sha256(b"bloco 1"));
+        let tamanho = BLOCKSIZE + 7;
+        let v2 = Construcao::V2.combina(&[&d0, &d1], tamanho, BLOCKSIZE);
+
+        let mut esperado = b"hush-hsha256-v2\x00".to_vec();
+        esperado.extend_from_slice(&tamanho.to_be_bytes());
+        esperado.extend_from_slice(&BLOCKSIZE.to_be_bytes());
+        esperado.extend_from_slice(&2u64.to_be_bytes());
+        esperado.extend_from_slice(&hex::decode(&d0).unwrap());
+        esperado.extend_from_slice(&hex::decode(&d1).unwrap());
+        assert_eq!(v2, hex_sha256(&esperado));
+
+        // Os mesmos digests não dão o valor da v1, nem o de outro tamanho ou blocksize
+        assert_ne!(v2, Construcao::V1.combina(&[&d0, &d1], tamanho, BLOCKSIZE));
+        assert_ne!(
+            v2,
+            Construcao::V2.combina(&[&d0, &d1], tamanho + 1, BLOCKSIZE)
+        );
+        assert_ne!(
+            v2,
+            Construcao::V2.combina(&[&d0, &d1], tamanho, BLOCKSIZE * 2)
+        );
+        // Um bloco só não é o sha256 do bloco, e arquivo vazio não é o sha256 de nada
+        assert_ne!(Construcao::V2.combina(&[&d0], 7, BLOCKSIZE), d0);
+        assert_ne!(
+            Construcao::V2.combina(&[], 0, BLOCKSIZE),
+            Construcao::V1.combina(&[], 0, BLOCKSIZE)
+        );
+    }
+}
@@ -0,0 +1,235 @@
+// Controles para não atrapalhar a máquina onde o hush roda.
+//
+// --max_rate: limite de leitura (ex: 200MB/s), um balde de fichas único para todas as
+//             threads que leem. Cada leitura consome as fichas antes de ler; sem fichas, dorme.
+// --nice / --ioprio: prioridade de CPU e de I/O do processo (Linux).
+// --control: arquivo lido a cada segundo enquanto o hush roda, para mudar os três valores
+//            sem parar um job longo. Uma linha por valor, ex:
+//                max_rate 50MB/s
+//                nice 19
+//                ioprio idle
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::functions::ParseSize;
+
+// Bytes por segundo. 0: sem limite.
+static TAXA_MAXIMA: AtomicU64 = AtomicU64::new(0);
+
+// Fichas disponíveis (bytes). Fica negativo quando uma leitura grande pega emprestado.
+struct Balde {
+    fichas: f64,
+    ultimo: Instant,
+}
+
+static BALDE: Lazy<Mutex<Balde>> = Lazy::new(|| {
+    Mutex::new(Balde {
+        fichas: 0.0,
+        ultimo: Instant::now(),
+    })
+});
+
+// Rajada máxima: um décimo de segundo de leitura
+const RAJADA_SEGUNDOS: f64 = 0.1;
+
+// "200MB/s", "200MB", "0" ou "off"
+pub fn parse_taxa(valor: &str) -> Result<u64, Box<dyn Error>> {
+    let valor = valor.trim();
+    if valor.eq_ignore_ascii_case("off") {
+        return Ok(0);
+    }
+    let sem_segundos = valor
+        .strip_suffix("/s")
+        .or_else(|| valor.strip_suffix("/S"))
+        .unwrap_or(valor);
+    if sem_segundos == "0" {
+        return Ok(0);
+    }
+    ParseSize(sem_segundos)
+}
+
+pub fn define_taxa(bytes_por_segundo: u64) {
+    TAXA_MAXIMA.store(bytes_por_segundo, Ordering::Relaxed);
+    if bytes_por_segundo == 0 {
+        info!("max rate: unlimited");
+    } else {
+        info!("max rate: {} bytes/s", bytes_por_segundo);
+    }
+}
+
+// Chamado antes de cada leitura, por todas as threads que leem dados para hash
+pub fn consome(bytes: usize) {
+    let taxa = TAXA_MAXIMA.load(Ordering::Relaxed);
+    if taxa == 0 || bytes == 0 {
+        return;
+    }
+    let taxa = taxa as f64;
+    let espera = {
+        let mut balde = BALDE.lock().unwrap();
+        let agora = Instant::now();
+        let decorrido = agora.duration_since(balde.ultimo).as_secs_f64();
+        balde.ultimo = agora;
+        balde.fichas = (balde.fichas + decorrido * taxa).min(taxa * RAJADA_SEGUNDOS);
+        balde.fichas -= bytes as f64;
+        if balde.fichas < 0.0 {
+            Duration::from_secs_f64(-balde.fichas / taxa)
+        } else {
+            Duration::ZERO
+        }
+    };
+    if !espera.is_zero() {
+        thread::sleep(espera);
+    }
+}
+
+// Classe de prioridade de I/O (ioprio_set). O nível vai de 0 (mais alta) a 7.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClasseIo {
+    RealTime(u8),
+    BestEffort(u8),
+    Idle,
+}
+
+impl ClasseIo {
+    // "idle", "be", "be:7", "rt:0"
+    pub fn from_arg(valor: &str) -> Option<ClasseIo> {
+        let valor = valor.trim().to_lowercase();
+        let (classe, nivel) = match valor.split_once(':') {
+            Some((classe, nivel)) => (classe.to_string(), nivel.parse::<u8>().ok()?),
+            None => (valor.clone(), 4),
+        };
+        if nivel > 7 {
+            return None;
+        }
+        match classe.as_str() {
+            "idle" => Some(ClasseIo::Idle),
+            "be" | "best-effort" => Some(ClasseIo::BestEffort(nivel)),
+            "rt" | "realtime" => Some(ClasseIo::RealTime(nivel)),
+            _ => None,
+        }
+    }
+}
+
+// Ids das threads do processo. No Linux, nice e ioprio valem por thread: mudar só o
+// processo deixaria as threads do pool com o valor antigo.
+#[cfg(target_os = "linux")]
+fn threads_do_processo() -> Vec<i32> {
+    match fs::read_dir("/proc/self/task") {
+        Ok(entradas) => entradas
+            .filter_map(|entrada| entrada.ok()?.file_name().to_str()?.parse::<i32>().ok())
+            .collect(),
+        Err(_) => vec![0],
+    }
+}
+
+// Threads criadas depois herdam o valor de quem as cria
+#[cfg(target_os = "linux")]
+pub fn define_nice(nice: i32) -> io::Result<()> {
+    for tid in threads_do_processo() {
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    info!("nice: {}", nice);
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub fn define_ioprio(classe: ClasseIo) -> io::Result<()> {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: u32 = 13;
+    let valor = match classe {
+        ClasseIo::RealTime(nivel) => (1 << IOPRIO_CLASS_SHIFT) | nivel as libc::c_int,
+        ClasseIo::BestEffort(nivel) => (2 << IOPRIO_CLASS_SHIFT) | nivel as libc::c_int,
+        ClasseIo::Idle => 3 << IOPRIO_CLASS_SHIFT,
+    };
+    for tid in threads_do_processo() {
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid, valor) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    info!("ioprio: {:?}", classe);
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn define_nice(_nice: i32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--nice is only available on Linux",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn define_ioprio(_classe: ClasseIo) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--ioprio is only available on Linux",
+    ))
+}
+
+// Aplica as linhas do arquivo de controle. Linha inválida: aviso, e o resto continua valendo.
+fn aplica_controle(conteudo: &str, caminho: &str) {
+    for linha in conteudo.lines() {
+        let linha = linha.trim();
+        if linha.is_empty() || linha.starts_with('#') {
+            continue;
+        }
+        let (chave, valor) = match linha.split_once(char::is_whitespace) {
+            Some((chave, valor)) => (chave.trim(), valor.trim()),
+            None => (linha, ""),
+        };
+        let resultado: Result<(), Box<dyn Error>> = match chave {
+            "max_rate" | "max-rate" => parse_taxa(valor).map(define_taxa),
+            "nice" => valor
+                .parse::<i32>()
+                .map_err(|e| e.into())
+                .and_then(|nice| define_nice(nice).map_err(|e| e.into())),
+            "ioprio" => match ClasseIo::from_arg(valor) {
+                Some(classe) => define_ioprio(classe).map_err(|e| e.into()),
+                None => Err("use idle, be[:0-7] or rt[:0-7]".into()),
+            },
+            _ => Err("unknown setting".into()),
+        };
+        match resultado {
+            Ok(()) => eprintln!("Control file {}: {}", caminho, linha),
+            Err(e) => {
+                warn!("Control file {}: '{}': {}", caminho, linha, e);
+                eprintln!("Control file {}: ignored '{}': {}", caminho, linha, e);
+            }
+        }
+    }
+}
+
+// Lê o arquivo de controle agora e de novo sempre que ele mudar (conferido a cada segundo)
+pub fn vigia_controle(caminho: &str) {
+    let modificado =
+        |caminho: &str| -> Option<SystemTime> { fs::metadata(caminho).ok()?.modified().ok() };
+    let mut ultima_versao = modificado(caminho);
+    if let Ok(conteudo) = fs::read_to_string(caminho) {
+        aplica_controle(&conteudo, caminho);
+    }
+
+    let caminho = caminho.to_string();
+    thread::Builder::new()
+        .name("hush-control".to_string())
+        .spawn(move || loop {
+            thread::sleep(Duration::from_secs(1));
+            let versao = modificado(&caminho);
+            if versao.is_some() && versao != ultima_versao {
+                ultima_versao = versao;
+                if let Ok(conteudo) = fs::read_to_string(&caminho) {
+                    aplica_controle(&conteudo, &caminho);
+                }
+            }
+        })
+        .expect("Failed to spawn control file thread");
+}
@@ -0,0 +1,152 @@
+// Agendamento por dispositivo.
+//
+// Em SSD/NVMe, muitas leituras ao mesmo tempo aumentam a vazão. Em disco de prato (HDD),
+// cada leitura concorrente faz a cabeça pular de um lugar para outro e a vazão despenca.
+// Por isso os arquivos são agrupados pelo dispositivo onde estão: os de disco rotacional
+// são lidos por um único leitor sequencial por disco, que alimenta as threads de hash;
+// os demais vão para o pool normal. Discos diferentes continuam em paralelo entre si.
+// A detecção usa /sys/dev/block/<major>:<minor>/queue/rotational (Linux).
+
+use log::info;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgendamentoDisco {
+    // Detecta pelo sysfs: rotacional => leitor sequencial, o resto => pool paralelo
+    #[default]
+    Auto,
+    // Tudo no pool paralelo (comportamento antigo)
+    Ssd,
+    // Todo dispositivo tratado como rotacional
+    Hdd,
+}
+
+impl AgendamentoDisco {
+    pub fn from_arg(valor: &str) -> Option<AgendamentoDisco> {
+        match valor.to_lowercase().as_str() {
+            "auto" => Some(AgendamentoDisco::Auto),
+            "ssd" => Some(AgendamentoDisco::Ssd),
+            "hdd" => Some(AgendamentoDisco::Hdd),
+            _ => None,
+        }
+    }
+}
+
+// Arquivos de um disco rotacional, na ordem em que o leitor sequencial vai passar
+pub struct GrupoDisco {
+    pub nome: String,
+    pub arquivos: Vec<String>,
+}
+
+// Separa os arquivos em: os do pool paralelo, e um grupo por disco rotacional
+pub fn agrupa_por_disco(
+    files: Vec<String>,
+    agendamento: AgendamentoDisco,
+) -> (Vec<String>, Vec<GrupoDisco>) {
+    if agendamento == AgendamentoDisco::Ssd {
+        return (files, Vec::new());
+    }
+
+    let mut paralelos = Vec::new();
+    // dispositivo -> (nome, [(inode, arquivo)])
+    let mut por_disco: BTreeMap<u64, (String, Vec<(u64, String)>)> = BTreeMap::new();
+    let mut rotacional_por_dispositivo: BTreeMap<u64, Option<String>> = BTreeMap::new();
+
+    for file in files {
+        let (dispositivo, inode) = match identifica_arquivo(&file) {
+            Some(identificacao) => identificacao,
+            None => {
+                paralelos.push(file);
+                continue;
+            }
+        };
+        let disco_rotacional = rotacional_por_dispositivo
+            .entry(dispositivo)
+            .or_insert_with(|| disco_rotacional(dispositivo, agendamento));
+        match disco_rotacional {
+            Some(nome) => por_disco
+                .entry(dispositivo)
+                .or_insert_with(|| (nome.clone(), Vec::new()))
+                .1
+                .push((inode, file)),
+            None => paralelos.push(file),
+        }
+    }
+
+    // Ordem dos inodes: aproxima a ordem física no disco na maioria dos sistemas de arquivos
+    let grupos: Vec<GrupoDisco> = por_disco
+        .into_values()
+        .map(|(nome, mut arquivos)| {
+            arquivos.sort();
+            GrupoDisco {
+                nome,
+                arquivos: arquivos.into_iter().map(|(_, file)| file).collect(),
+            }
+        })
+        .collect();
+
+    for grupo in &grupos {
+        info!(
+            "Rotational device {}: {} files, one sequential reader",
+            grupo.nome,
+            grupo.arquivos.len()
+        );
+    }
+    info!("Parallel pool: {} files", paralelos.len());
+    (paralelos, grupos)
+}
+
+// Quantos dispositivos têm os arquivos (os que não dá para identificar não contam)
+pub fn conta_dispositivos(files: &[String]) -> usize {
+    let dispositivos: std::collections::BTreeSet<u64> = files
+        .iter()
+        .filter_map(|file| identifica_arquivo(file))
+        .map(|(dispositivo, _)| dispositivo)
+        .collect();
+    dispositivos.len()
+}
+
+#[cfg(unix)]
+fn identifica_arquivo(file: &str) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = fs::metadata(file).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn identifica_arquivo(_file: &str) -> Option<(u64, u64)> {
+    None
+}
+
+// Nome do disco se ele deve ter leitor sequencial, None se vai para o pool paralelo
+fn disco_rotacional(dispositivo: u64, agendamento: AgendamentoDisco) -> Option<String> {
+    // major/minor no formato do glibc
+    let major = ((dispositivo >> 8) & 0xfff) | ((dispositivo >> 32) & !0xfff);
+    let minor = (dispositivo & 0xff) | ((dispositivo >> 12) & !0xff);
+    let nome_dispositivo = format!("{}:{}", major, minor);
+
+    if agendamento == AgendamentoDisco::Hdd {
+        return Some(nome_dispositivo);
+    }
+
+    // Partição (sda1) não tem queue/: o atributo fica no disco pai (sda)
+    let dir = fs::canonicalize(format!("/sys/dev/block/{}", nome_dispositivo)).ok()?;
+    let rotacional = match fs::read_to_string(dir.join("queue/rotational")) {
+        Ok(rotacional) => rotacional,
+        Err(_) => fs::read_to_string(dir.parent()?.join("queue/rotational")).ok()?,
+    };
+    let nome = dir
+        .file_name()
+        .map(|nome| nome.to_string_lossy().to_string())
+        .unwrap_or(nome_dispositivo);
+    info!("Device {}: rotational {}", nome, rotacional.trim());
+    if rotacional.trim() == "1" {
+        Some(nome)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,244 @@
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use log::info;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::process;
+use std::thread;
+
+use crate::functions::{
+    gera_caminho_completo, gera_caminho_relativo, hash_hush, ler_manifest, normaliza_alg,
+    Argumentos,
+};
+use crate::progresso;
+
+// Um arquivo candidato a duplicado: caminho mostrado, tamanho (se conhecido), algoritmo e hash
+struct ArquivoDupe {
+    caminho: String,
+    tamanho: Option<u64>,
+    alg_hash: String,
+    valor_hash: String,
+}
+
+// (algoritmo, blocksize em bytes) + hash
+type ChaveDupe = ((String, Option<u64>), String);
+
+// Agrupa por (algoritmo, hash) e imprime os conjuntos com mais de um arquivo,
+// do que mais desperdiça espaço para o que menos desperdiça.
+fn imprime_duplicados(arquivos: Vec<ArquivoDupe>) {
+    let mut grupos: HashMap<ChaveDupe, Vec<ArquivoDupe>> = HashMap::new();
+    for arquivo in arquivos {
+        grupos
+            .entry((normaliza_alg(&arquivo.alg_hash), arquivo.valor_hash.clone()))
+            .or_default()
+            .push(arquivo);
+    }
+
+    let mut conjuntos: Vec<(Option<u64>, Vec<ArquivoDupe>)> = grupos
+        .into_values()
+        .filter(|grupo| grupo.len() > 1)
+        .map(|mut grupo| {
+            grupo.sort_by(|a, b| a.caminho.cmp(&b.caminho));
+            let tamanho = grupo.iter().find_map(|arquivo| arquivo.tamanho);
+            (tamanho, grupo)
+        })
+        .collect();
+    conjuntos.sort_by(|(tamanho_a, grupo_a), (tamanho_b, grupo_b)| {
+        let desperdicio_a = tamanho_a.unwrap_or(0) * (grupo_a.len() as u64 - 1);
+        let desperdicio_b = tamanho_b.unwrap_or(0) * (grupo_b.len() as u64 - 1);
+        desperdicio_b
+            .cmp(&desperdicio_a)
+            .then_with(|| grupo_a[0].caminho.cmp(&grupo_b[0].caminho))
+    });
+
+    let mut n_arquivos_duplicados = 0;
+    let mut total_desperdicado = 0;
+    let mut n_tamanho_desconhecido = 0;
+    for (n_conjunto, (tamanho, grupo)) in conjuntos.iter().enumerate() {
+        let n_copias = grupo.len() as u64 - 1;
+        n_arquivos_duplicados += n_copias;
+        match tamanho {
+            Some(tamanho) => {
+                total_desperdicado += tamanho * n_copias;
+                println!(
+                    "Duplicate set {}: {} files, {} bytes each, wasted {} bytes, {} ?{}",
+                    n_conjunto + 1,
+                    grupo.len(),
+                    tamanho,
+                    tamanho * n_copias,
+                    grupo[0].valor_hash,
+                    grupo[0].alg_hash
+                );
+            }
+            None => {
+                n_tamanho_desconhecido += 1;
+                println!(
+                    "Duplicate set {}: {} files, size unknown, {} ?{}",
+                    n_conjunto + 1,
+                    grupo.len(),
+                    grupo[0].valor_hash,
+                    grupo[0].alg_hash
+                );
+            }
+        }
+        for arquivo in grupo {
+            println!("    {}", arquivo.caminho);
+        }
+    }
+
+    eprintln!(
+        "Duplicate sets:{} Duplicate files (besides one of each set):{} Wasted bytes:{}",
+        conjuntos.len(),
+        n_arquivos_duplicados,
+        total_desperdicado
+    );
+    if n_tamanho_desconhecido > 0 {
+        eprintln!(
+            "{} sets with size unknown (files not found in work dir), not counted in wasted bytes.",
+            n_tamanho_desconhecido
+        );
+    }
+}
+
+// Duplicados a partir de um manifest já gerado. Os tamanhos vêm dos arquivos no work_dir.
+pub fn dupes_manifest(manifest: &str, work_dir: &str) -> io::Result<()> {
+    info!("dupes: manifest {} work_dir {}", manifest, work_dir);
+    let (entradas, erros) = ler_manifest(manifest)?;
+    for erro in &erros {
+        eprintln!("Error! {}", erro);
+    }
+
+    let arquivos = entradas
+        .into_iter()
+        .map(|entrada| {
+            let tamanho = fs::metadata(gera_caminho_completo(&entrada.path, work_dir))
+                .ok()
+                .map(|metadata| metadata.len());
+            ArquivoDupe {
+                caminho: entrada.path,
+                tamanho,
+                alg_hash: entrada.alg_hash,
+                valor_hash: entrada.valor_hash,
+            }
+        })
+        .collect();
+    imprime_duplicados(arquivos);
+    Ok(())
+}
+
+// Duplicados numa árvore de arquivos. Primeiro agrupa por tamanho:
+// arquivo com tamanho único não tem duplicado e nem chega a ser lido.
+pub fn dupes_tree(main_args: Argumentos, files: Vec<String>) -> io::Result<()> {
+    let caminho_pai_full = main_args
+        .in_file_path
+        .to_str()
+        .unwrap()
+        .replace("\\", "/")
+        .to_string();
+
+    let mut por_tamanho: HashMap<u64, Vec<String>> = HashMap::new();
+    for file in files {
+        match fs::metadata(&file) {
+            Ok(metadata) => por_tamanho.entry(metadata.len()).or_default().push(file),
+            Err(e) => eprintln!("Failed to get file metadata: {} with: {}", e, file),
+        }
+    }
+
+    // (caminho, tamanho) de cada arquivo que tem o tamanho igual ao de outro
+    let (sender_files, receiver_files) = unbounded::<(String, u64)>();
+    let mut n_candidatos = 0;
+    let mut bytes_candidatos = 0;
+    for (tamanho, arquivos) in por_tamanho {
+        if arquivos.len() < 2 {
+            continue;
+        }
+        for file in arquivos {
+            n_candidatos += 1;
+            bytes_candidatos += tamanho;
+            sender_files.send((file, tamanho)).unwrap();
+        }
+    }
+    drop(sender_files);
+    if main_args.flag_show_progress {
+        progresso::inicia(n_candidatos as u64, bytes_candidatos);
+    }
+    info!(
+        "dupes: {} files with same size of another file",
+        n_candidatos
+    );
+
+    let (sender_calculados, receiver_calculados): (Sender<ArquivoDupe>, Receiver<ArquivoDupe>) =
+        unbounded();
+
+    let mut handles = Vec::new();
+    for _ in 0..main_args.n_workers {
+        let receiver_files_clone = receiver_files.clone();
+        let sender_calculados_clone = sender_calculados.clone();
+        let block_size_str_clone = main_args.block_size_str.clone();
+        let caminho_pai_clone = caminho_pai_full.clone();
+        let block_size = main_args.block_size;
+        let construcao = main_args.construcao;
+        let buffer_size = main_args.buffer_size as usize;
+        let n_max_concur = main_args.n_max_concur;
+        let flag_show_progress = main_args.flag_show_progress;
+        let modo_leitura = main_args.modo_leitura;
+
+        handles.push(thread::spawn(move || {
+            while let Ok((file, tamanho)) = receiver_files_clone.recv() {
+                // Mesma escolha do gen: hsha256 acima do blocksize, sha256 até ele (v1); v2 sempre
+                let alg_hash = if construcao.usa_sha256(tamanho, block_size) {
+                    String::from("sha256")
+                } else {
+                    format!("{}-{}", construcao.nome(), block_size_str_clone)
+                };
+                let resultado = hash_hush(
+                    &file,
+                    &alg_hash,
+                    buffer_size,
+                    n_max_concur,
+                    flag_show_progress,
+                    modo_leitura,
+                );
+                if flag_show_progress {
+                    progresso::arquivo_pronto(&file);
+                }
+                match resultado {
+                    Ok(valor_hash) => {
+                        let caminho = gera_caminho_relativo(&file, &caminho_pai_clone)
+                            .map(|caminho| caminho.display().to_string())
+                            .unwrap_or(file);
+                        sender_calculados_clone
+                            .send(ArquivoDupe {
+                                caminho,
+                                tamanho: Some(tamanho),
+                                alg_hash,
+                                valor_hash,
+                            })
+                            .unwrap();
+                    }
+                    Err(e) => progresso::erro(&format!("Error {}: {} with: {}", alg_hash, e, file)),
+                }
+            }
+        }));
+    }
+    drop(sender_calculados);
+
+    let arquivos: Vec<ArquivoDupe> = receiver_calculados.iter().collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    progresso::termina();
+
+    info!(
+        "dupes: {} files hashed in {}",
+        arquivos.len(),
+        caminho_pai_full
+    );
+    let n_nao_lidos = n_candidatos - arquivos.len();
+    imprime_duplicados(arquivos);
+    if n_nao_lidos > 0 {
+        eprintln!("Error. {} files could not be hashed!", n_nao_lidos);
+        process::exit(1);
+    }
+    Ok(())
+}
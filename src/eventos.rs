@@ -0,0 +1,208 @@
+// Eventos estruturados para GUIs e agendadores (--events jsonl <path|fd>).
+//
+// Um objeto JSON por linha, sempre com "v" (versão do formato), "event" e "time" (ms desde
+// 1970). Campos novos podem aparecer numa mesma versão; mudar ou tirar campo muda a versão.
+// Eventos da versão 1:
+//   run_start     command, hush (versão do programa), format
+//   walk_start    root
+//   walk_finish   root, files, bytes
+//   file_start    path, size, alg
+//   block_done    path, block, start, end, hash
+//   file_done     path, size, alg, hash (no check, também expected e match)
+//   error         path, offset (null se não for de um bloco), message
+//   summary       command, files, files_done, errors, bytes, elapsed_ms, result
+// A escrita é uma linha inteira por vez, com flush: quem lê pode acompanhar o arquivo ou o fd.
+
+use once_cell::sync::OnceCell;
+use serde_json::{json, Map, Value};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::auditoria;
+
+pub const VERSAO_EVENTOS: u64 = 1;
+
+struct Eventos {
+    saida: Mutex<Box<dyn Write + Send>>,
+    comando: String,
+    inicio: Instant,
+}
+
+static EVENTOS: OnceCell<Eventos> = OnceCell::new();
+
+// Totais da execução, contados mesmo sem --events: vão também para o log de auditoria
+struct Totais {
+    total_arquivos: AtomicU64,
+    arquivos_prontos: AtomicU64,
+    erros: AtomicU64,
+    bytes: AtomicU64,
+}
+
+static TOTAIS: Totais = Totais {
+    total_arquivos: AtomicU64::new(0),
+    arquivos_prontos: AtomicU64::new(0),
+    erros: AtomicU64::new(0),
+    bytes: AtomicU64::new(0),
+};
+
+// "--events jsonl <destino>": destino só com dígitos é um descritor já aberto (ex: 3), o resto
+// é um arquivo, criado ou truncado.
+pub fn abre(formato: &str, destino: &str, comando: &str) -> Result<(), Box<dyn Error>> {
+    if formato != "jsonl" {
+        return Err(format!("unknown events format: {} (use jsonl)", formato).into());
+    }
+    let saida: Box<dyn Write + Send> = if destino.chars().all(|c| c.is_ascii_digit()) {
+        abre_descritor(destino.parse()?)?
+    } else {
+        Box::new(File::create(destino)?)
+    };
+    let eventos = Eventos {
+        saida: Mutex::new(saida),
+        comando: comando.to_string(),
+        inicio: Instant::now(),
+    };
+    if EVENTOS.set(eventos).is_err() {
+        return Err("events already open".into());
+    }
+    emite(
+        "run_start",
+        json!({
+            "command": comando,
+            "hush": env!("CARGO_PKG_VERSION"),
+            "format": format!("hush-events-v{}", VERSAO_EVENTOS),
+        }),
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+fn abre_descritor(fd: i32) -> io::Result<Box<dyn Write + Send>> {
+    use std::os::unix::io::FromRawFd;
+
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // Safety: descritor aberto (conferido acima), herdado de quem chamou o hush
+    Ok(Box::new(unsafe { File::from_raw_fd(fd) }))
+}
+
+#[cfg(not(unix))]
+fn abre_descritor(_fd: i32) -> io::Result<Box<dyn Write + Send>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--events to a file descriptor is only available on Unix. Use a path.",
+    ))
+}
+
+pub fn ativo() -> bool {
+    EVENTOS.get().is_some()
+}
+
+// Uma linha com o evento e os campos (objeto JSON). Sem --events, não faz nada.
+pub fn emite(evento: &str, campos: Value) {
+    let Some(eventos) = EVENTOS.get() else {
+        return;
+    };
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duracao| duracao.as_millis() as u64)
+        .unwrap_or(0);
+    let mut objeto = Map::new();
+    objeto.insert("v".to_string(), json!(VERSAO_EVENTOS));
+    objeto.insert("event".to_string(), json!(evento));
+    objeto.insert("time".to_string(), json!(time));
+    if let Value::Object(campos) = campos {
+        objeto.extend(campos);
+    }
+    let mut linha = Value::Object(objeto).to_string();
+    linha.push('\n');
+
+    let mut saida = eventos.saida.lock().unwrap();
+    // Quem lê os eventos pode ter saído: o hash continua
+    if saida
+        .write_all(linha.as_bytes())
+        .and_then(|_| saida.flush())
+        .is_err()
+    {
+        log::warn!("events: write failed");
+    }
+}
+
+pub fn walk_start(root: &str) {
+    emite("walk_start", json!({ "root": root }));
+}
+
+// No gen, os arquivos achados pela busca; no check, as linhas do manifest
+pub fn walk_finish(root: &str, files: u64, bytes: u64) {
+    TOTAIS.total_arquivos.store(files, Ordering::Relaxed);
+    emite(
+        "walk_finish",
+        json!({ "root": root, "files": files, "bytes": bytes }),
+    );
+}
+
+pub fn file_start(path: &str, size: u64, alg: &str) {
+    emite(
+        "file_start",
+        json!({ "path": path, "size": size, "alg": alg }),
+    );
+}
+
+pub fn block_done(path: &str, block: u64, start: u64, end: u64, hash: &str) {
+    emite(
+        "block_done",
+        json!({ "path": path, "block": block, "start": start, "end": end, "hash": hash }),
+    );
+}
+
+// Arquivo com hash. `extra`: campos a mais (no check, o hash esperado e se bateu).
+pub fn file_done(path: &str, size: u64, alg: &str, hash: &str, extra: Value) {
+    TOTAIS.arquivos_prontos.fetch_add(1, Ordering::Relaxed);
+    TOTAIS.bytes.fetch_add(size, Ordering::Relaxed);
+    if !ativo() {
+        return;
+    }
+    let mut campos = json!({ "path": path, "size": size, "alg": alg, "hash": hash });
+    if let (Value::Object(campos), Value::Object(extra)) = (&mut campos, extra) {
+        campos.extend(extra);
+    }
+    emite("file_done", campos);
+}
+
+pub fn error(path: &str, offset: Option<u64>, message: &str) {
+    TOTAIS.erros.fetch_add(1, Ordering::Relaxed);
+    auditoria::falha(path, message);
+    emite(
+        "error",
+        json!({ "path": path, "offset": offset, "message": message }),
+    );
+}
+
+// Último evento da execução. result: "ok", "error", "known_bad", "mac_failed" (check com
+// --mac_key_*, manifest com MAC inválido), "signature_failed", "timestamp_failed" ou "aborted".
+// Com --audit_log, também a entrada da execução no log de auditoria.
+pub fn summary(result: &str) {
+    let totais = json!({
+        "files": TOTAIS.total_arquivos.load(Ordering::Relaxed),
+        "files_done": TOTAIS.arquivos_prontos.load(Ordering::Relaxed),
+        "errors": TOTAIS.erros.load(Ordering::Relaxed),
+        "bytes": TOTAIS.bytes.load(Ordering::Relaxed),
+    });
+    auditoria::registra(result, totais.clone());
+    let Some(eventos) = EVENTOS.get() else {
+        return;
+    };
+    let mut campos = json!({
+        "command": eventos.comando,
+        "elapsed_ms": eventos.inicio.elapsed().as_millis() as u64,
+        "result": result,
+    });
+    if let (Value::Object(campos), Value::Object(totais)) = (&mut campos, totais) {
+        campos.extend(totais);
+    }
+    emite("summary", campos);
+}
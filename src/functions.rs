@@ -1,13 +1,30 @@
 use std::error::Error;
 use clap::{Arg, Command};
 
-use crossbeam_channel::{unbounded, Receiver, Sender};
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
 use glob::glob;
 use log::{error, warn, info, debug, trace, LevelFilter};
-use num_cpus;
 use once_cell::sync::OnceCell;
+use memmap2::Mmap;
+use serde::Serialize;
+use serde_json::{json, Value};
 use sha2::{Digest, Sha256};
+use std::sync::Arc;
 use std::env;
+use crate::armazena::{EscritaParte, Guardado};
+use crate::auditoria;
+use crate::caso;
+use crate::construcao::Construcao;
+use crate::cache::{self, PoliticaCache};
+use crate::controle;
+use crate::disco::{agrupa_por_disco, AgendamentoDisco};
+use crate::eventos;
+use crate::hashset::{ListasConhecidos, StatusConhecido};
+use crate::inteiro::{FatiaInteiro, Sha256Inteiro};
+use crate::mac;
+use crate::pool;
+use crate::progresso;
+use crate::uring::{self, ServicoUring};
 use std::fs::{self, DirEntry, File};
 use std::io::{self, BufRead, BufReader, Read, Seek, Write};
 use std::path::{Path, PathBuf};
@@ -18,13 +35,28 @@ use std::time::Instant;
 use std::time::SystemTime;
 
 #[derive(Debug, Clone)]
-struct ChunkBloco {
-    n_bloco: u64,
-    inicio_bloco: u64,
-    fim_bloco: u64,
-    hash_bloco: String,
+pub struct ChunkBloco {
+    pub n_bloco: u64,
+    pub inicio_bloco: u64,
+    pub fim_bloco: u64,
+    pub hash_bloco: String,
 }
 
+// Como os blocos são lidos para o cálculo do hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModoLeitura {
+    // read() com BufReader, um descritor por thread
+    #[default]
+    Read,
+    // Arquivo mapeado em memória: cada bloco é hasheado direto do mapa, sem cópia
+    Mmap,
+    // io_uring (Linux): um anel compartilhado lê pedaços de vários blocos e arquivos ao mesmo tempo
+    IoUring,
+}
+
+// Também vai, inteiro, para o log de auditoria (args)
+#[derive(Clone, Serialize)]
 pub struct Argumentos {
     pub name: String,
     pub n_workers: u32,
@@ -41,13 +73,27 @@ pub struct Argumentos {
     pub out_file_path: PathBuf,
     pub flag_show_progress:bool,
     pub flag_stop_on_first_error: bool,
+    pub known_good_files: Vec<String>,
+    pub known_bad_files: Vec<String>,
+    pub flag_omit_known_good: bool,
+    pub modo_leitura: ModoLeitura,
+    pub agendamento_disco: AgendamentoDisco,
+    pub n_threads: u32,
+    pub max_in_flight: u64,
+    // Construção do hush dos arquivos grandes (--hash_alg)
+    pub construcao: Construcao,
+    // Vai para o log de auditoria à parte (case)
+    #[serde(skip)]
+    pub caso: caso::Caso,
 }
 
 pub struct TFileHash {
-    n_arquivo: u64,
-    path: String,
-    alg_hash: String,
-    valor_hash: String,
+    pub n_arquivo: u64,
+    pub path: String,
+    pub alg_hash: String,
+    pub valor_hash: String,
+    // sha256 do arquivo inteiro, além do hsha256, para buscar nas listas de conhecidos só com sha256
+    pub sha256_inteiro: Option<String>,
 }
 
 pub fn process_files(main_args: Argumentos, files: Vec<String>) {
@@ -70,6 +116,36 @@ pub fn process_files(main_args: Argumentos, files: Vec<String>) {
 
     );
     let caminho_pai_full=main_args.in_file_path.to_str().unwrap().replace("\\", "/").to_string();    
+
+    // Listas de hashes conhecidos (NSRL, listas simples, manifests do hush)
+    let listas_conhecidos = if main_args.known_good_files.is_empty() && main_args.known_bad_files.is_empty() {
+        None
+    } else {
+        match ListasConhecidos::carrega(&main_args.known_good_files, &main_args.known_bad_files) {
+            Ok(listas) => {
+                info!(
+                    "Known-good: {} unique hashes, known-bad: {} unique hashes",
+                    listas.known_good.len(),
+                    listas.known_bad.len()
+                );
+                Some(listas)
+            }
+            Err(e) => {
+                let error_msg = format!("Error loading hash set: {}", e);
+                eprintln!("{}", error_msg);
+                eventos::error(&caminho_pai_full, None, &error_msg);
+                eventos::summary("error");
+                process::exit(1);
+            }
+        }
+    };
+    let mut n_known_good = 0;
+    let mut n_known_bad = 0;
+    // NSRL e listas simples só têm sha256: os arquivos com hsha256 também levam o sha256 inteiro
+    let sha256_inteiro = listas_conhecidos.as_ref().is_some_and(|listas| listas.tem_sha256());
+    if sha256_inteiro {
+        info!("Hash sets have sha256: files hashed with {} also get the sha256 of the whole file", main_args.construcao.nome());
+    }
      
     let (sender_files, receiver_files): (Sender<String>, Receiver<String>) = unbounded();
 
@@ -78,14 +154,18 @@ pub fn process_files(main_args: Argumentos, files: Vec<String>) {
         Receiver<TFileHash>,
     ) = unbounded();
 
+    // Arquivos em disco rotacional: um leitor sequencial por disco. O resto: pool paralelo.
+    if main_args.flag_show_progress {
+        progresso::inicia(files.len() as u64, progresso::soma_tamanhos(&files));
+    }
+    let (files_paralelos, grupos_disco) = agrupa_por_disco(files, main_args.agendamento_disco);
+    let main_args = Arc::new(main_args);
+
     //Enviar todos arquivos para o canal que sera processado
-    for file in files {
+    for file in files_paralelos {
         //  println!("Enviado Bloco {} -> p/ calculo", bloco.n_bloco);
         info!("=>{}", file);
         sender_files.send(file).unwrap();
-        if main_args.flag_show_progress {
-            eprint!("+");
-        }
     }
 
     drop(sender_files);
@@ -95,109 +175,39 @@ pub fn process_files(main_args: Argumentos, files: Vec<String>) {
     for n_worker in 0..main_args.n_workers {
         let receiver_files_clone = receiver_files.clone();
         let sender_files_calculados_clone = sender_files_calculados.clone();
+        let main_args_clone = Arc::clone(&main_args);
 
-        let handle = thread::spawn({
-            let block_size_str_clone = main_args.block_size_str.clone();
-            move || {
-                while let Ok(file_input) = receiver_files_clone.recv() {
-                    let arquivo_chegada = file_input.clone();
-                  
-
-                    info!("<= {}", arquivo_chegada);
-                    // Aqui envia pra calculo de hashe!
-
-                    let mut file_size = 0;
-
-                    match fs::metadata(arquivo_chegada.clone()) {
-                        Ok(metadata) => {
-                            file_size = metadata.len(); // Get the size in bytes
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to get file metadata: {}", e);
-                        }
-                    }
-
-                    if file_size > main_args.block_size {
-                        // ===> hsha
-
-                        let mut algor_hash_tmp_hsha256_type = String::from("hsha256");
-                        let mut algor_hash_tmp_hsha256 =
-                            format!("{}-{}", algor_hash_tmp_hsha256_type, block_size_str_clone);
-
-                        info!(
-                            "{} -> {}",
-                            arquivo_chegada.clone(),
-                            algor_hash_tmp_hsha256.clone()
-                        );
-
-                        match hash_hsha256(
-                            &arquivo_chegada.clone(),
-                            main_args.buffer_size as usize,
-                            main_args.block_size as usize,
-                            main_args.n_max_concur,
-                            main_args.flag_show_progress
-
-                        ) {
-                            Ok(hash_calculado) => {
-                                let fileCalculado = TFileHash {
-                                    n_arquivo: 0,
-                                    path: arquivo_chegada.clone(),
-                                    alg_hash: algor_hash_tmp_hsha256.clone(),
-                                    valor_hash: hash_calculado.clone(),
-                                };
-                                sender_files_calculados_clone.send(fileCalculado).unwrap();
-                                info!(
-                                    "{} {}?{} ",
-                                    arquivo_chegada.clone(),
-                                    algor_hash_tmp_hsha256.clone(),
-                                    hash_calculado.clone()
-                                )
-                        
-                            }
-                            Err(e) => {
-                                eprintln!("Error hsha256: {} with: {}", e, arquivo_chegada);
-                            }
-                        }
-                    } else {
-                        // ===> SHA256 normal
+        let handle = thread::spawn(move || {
+            while let Ok(file_input) = receiver_files_clone.recv() {
+                if let Some(fileCalculado) = hash_arquivo(&file_input, &main_args_clone, false, sha256_inteiro) {
+                    sender_files_calculados_clone.send(fileCalculado).unwrap();
+                }
+                if main_args_clone.flag_show_progress {
+                    progresso::arquivo_pronto(&file_input);
+                }
+            }
+        });
+        handles.push(handle);
+    }
 
-                        let algor_hash_tmp_sha256 = String::from("sha256");
-                        info!(
-                            "{} -> {}",
-                            arquivo_chegada.clone(),
-                            algor_hash_tmp_sha256.clone()
-                        );
+    // Um leitor sequencial por disco rotacional, todos os discos ao mesmo tempo
+    for grupo in grupos_disco {
+        let sender_files_calculados_clone = sender_files_calculados.clone();
+        let main_args_clone = Arc::clone(&main_args);
+        eprintln!(
+            "Rotational disk {}: {} files read sequentially",
+            grupo.nome,
+            grupo.arquivos.len()
+        );
 
-                        match hash_sha256(
-                            &arquivo_chegada.clone(),
-                            main_args.buffer_size as usize,
-                        ) {
-                            Ok(hash_calculado) => {
-                                let fileCalculado = TFileHash {
-                                    n_arquivo: 0,
-                                    path: arquivo_chegada.clone(),
-                                    alg_hash: algor_hash_tmp_sha256.clone(),
-                                    valor_hash: hash_calculado.clone(),
-                                };
-                                sender_files_calculados_clone.send(fileCalculado).unwrap();
-
-                                //   println!("{0} ?{1}*{2}",filePronto.valor_hash,filePronto.alg_hash,filePronto.path);
-
-                                info!(
-                                    "{} ?{}*{} ",
-                                    arquivo_chegada.clone(),
-                                    algor_hash_tmp_sha256.clone(),
-                                    hash_calculado.clone()
-                                )
-                            }
-                            Err(e) => {
-                                eprintln!("Error sha256: {} with: {}", e, arquivo_chegada);
-                            }
-                        }
-                    }
-                    if main_args.flag_show_progress {
-                        eprint!("*");
-                    }
+        let handle = thread::spawn(move || {
+            for file_input in grupo.arquivos {
+                info!("=>{} (disk {})", file_input, grupo.nome);
+                if let Some(fileCalculado) = hash_arquivo(&file_input, &main_args_clone, true, sha256_inteiro) {
+                    sender_files_calculados_clone.send(fileCalculado).unwrap();
+                }
+                if main_args_clone.flag_show_progress {
+                    progresso::arquivo_pronto(&file_input);
                 }
             }
         });
@@ -208,18 +218,45 @@ pub fn process_files(main_args: Argumentos, files: Vec<String>) {
     drop(sender_files_calculados); // Dropar após o término das threads
 
     let mut resultados = Vec::new();
+    let mut saida = mac::saida_gen().expect("Failed to write hash file");
     while let Ok(filePronto) = receiver_files_calculados.recv() {
         //  println!("Recebido Bloco {} no resultado", bloco.n_bloco);
         info!("+");
         io::stdout().flush().expect("Failed to flush stdout");
         match gera_caminho_relativo(&filePronto.path.clone(), &caminho_pai_full.clone()) {
             Some(caminho_relativo) => {
-                println!(
+                let linha = format!(
                     "{0} ?{1}*{2}",
                     filePronto.valor_hash,
                     filePronto.alg_hash,
                     caminho_relativo.display()
                 );
+                let status = match &listas_conhecidos {
+                    Some(listas) => listas.classifica_arquivo(
+                        &filePronto.alg_hash,
+                        &filePronto.valor_hash,
+                        filePronto.sha256_inteiro.as_deref(),
+                    ),
+                    None => StatusConhecido::Unknown,
+                };
+                match status {
+                    StatusConhecido::KnownBad => {
+                        n_known_bad += 1;
+                        match &filePronto.sha256_inteiro {
+                            Some(sha256) => eprintln!("[known-bad] {} (sha256 {})", linha, sha256),
+                            None => eprintln!("[known-bad] {}", linha),
+                        }
+                        saida.linha(&linha).expect("Failed to write hash file");
+                    }
+                    StatusConhecido::KnownGood => {
+                        n_known_good += 1;
+                        info!("[known-good] {}", linha);
+                        if !main_args.flag_omit_known_good {
+                            saida.linha(&linha).expect("Failed to write hash file");
+                        }
+                    }
+                    StatusConhecido::Unknown => saida.linha(&linha).expect("Failed to write hash file"),
+                }
             }
             None => {
                 eprintln!(
@@ -228,9 +265,6 @@ pub fn process_files(main_args: Argumentos, files: Vec<String>) {
                 );
             }
         }
-        if(main_args.flag_show_progress){
-            eprint!("-");
-        }
 
         resultados.push(filePronto);
     }
@@ -238,6 +272,8 @@ pub fn process_files(main_args: Argumentos, files: Vec<String>) {
     for handle in handles {
         handle.join().unwrap();
     }
+    saida.termina().expect("Failed to write hash file");
+    progresso::termina();
     /* */
     //resultados.sort_by_key(|filePronto| filePronto.path);
     let n_files_prontos = resultados.len();
@@ -249,6 +285,16 @@ pub fn process_files(main_args: Argumentos, files: Vec<String>) {
     eprintln!("Total files to process:{}", n_files_a_processar);
     eprintln!("Total files hashed:{}", n_files_prontos);
 
+    if listas_conhecidos.is_some() {
+        eprintln!(
+            "Known-good:{}{} Known-bad:{} Unknown:{}",
+            n_known_good,
+            if main_args.flag_omit_known_good { " (omitted from output)" } else { "" },
+            n_known_bad,
+            n_files_prontos - n_known_good - n_known_bad
+        );
+    }
+
     if n_files_prontos == n_files_a_processar {
         eprintln!("Sucess. Hashed all files: {}", n_files_a_processar);
     } else {
@@ -256,8 +302,149 @@ pub fn process_files(main_args: Argumentos, files: Vec<String>) {
             "Error. Not all files hashed! To hash:{} / Hashed:{}",
             n_files_a_processar, n_files_prontos
         );
+        eventos::summary("error");
         process::exit(1);
     }
+    // Código de saída próprio para achados da lista de conhecidos ruins
+    if n_known_bad > 0 {
+        eprintln!("Known-bad files found: {}", n_known_bad);
+        eventos::summary("known_bad");
+        process::exit(2);
+    }
+    eventos::summary("ok");
+}
+
+// Hash de um arquivo do gen: hsha256 acima do blocksize, sha256 até ele (v1); hsha256v2 sempre.
+// `sequencial`: o arquivo está num disco rotacional e é lido por um leitor só.
+// `sha256_inteiro`: com hsha256, calcula também o sha256 do arquivo inteiro (listas de conhecidos).
+fn hash_arquivo(file_input: &str, main_args: &Argumentos, sequencial: bool, sha256_inteiro: bool) -> Option<TFileHash> {
+    let arquivo_chegada = file_input.to_string();
+
+    info!("<= {}", arquivo_chegada);
+    // Aqui envia pra calculo de hashe!
+
+    let mut file_size = 0;
+
+    match fs::metadata(arquivo_chegada.clone()) {
+        Ok(metadata) => {
+            file_size = metadata.len(); // Get the size in bytes
+        }
+        Err(e) => {
+            progresso::erro(&format!("Failed to get file metadata: {} with: {}", e, arquivo_chegada));
+        }
+    }
+
+    if !main_args.construcao.usa_sha256(file_size, main_args.block_size) {
+        // ===> hsha
+
+        let mut algor_hash_tmp_hsha256_type = String::from(main_args.construcao.nome());
+        let mut algor_hash_tmp_hsha256 =
+            format!("{}-{}", algor_hash_tmp_hsha256_type, main_args.block_size_str);
+
+        info!(
+            "{} -> {}",
+            arquivo_chegada.clone(),
+            algor_hash_tmp_hsha256.clone()
+        );
+        eventos::file_start(&arquivo_chegada, file_size, &algor_hash_tmp_hsha256);
+
+        // Para os conjuntos de hashes com sha256: o sha256 do arquivo todo sai da mesma leitura
+        let inteiro = if sha256_inteiro { Some(Arc::new(Sha256Inteiro::novo())) } else { None };
+        let resultado_hsha256 = if sequencial {
+            hash_hsha256_sequencial(
+                &arquivo_chegada,
+                main_args.buffer_size as usize,
+                main_args.block_size as usize,
+                main_args.flag_show_progress,
+                main_args.construcao,
+                inteiro.clone(),
+            )
+        } else {
+            hash_hsha256(
+                &arquivo_chegada,
+                main_args.buffer_size as usize,
+                main_args.block_size as usize,
+                main_args.n_max_concur,
+                main_args.flag_show_progress,
+                main_args.modo_leitura,
+                main_args.construcao,
+                inteiro.clone(),
+            )
+        };
+        match resultado_hsha256 {
+            Ok(hash_calculado) => {
+                let sha256_inteiro = match inteiro.map(|inteiro| inteiro.finaliza()) {
+                    Some(Some(sha256)) => Some(sha256),
+                    Some(None) => {
+                        let e = "file changed while hashing, no whole-file sha256 (hash sets)";
+                        progresso::erro(&format!("Error sha256: {} with: {}", e, arquivo_chegada));
+                        eventos::error(&arquivo_chegada, None, e);
+                        return None;
+                    }
+                    None => None,
+                };
+                let fileCalculado = TFileHash {
+                    n_arquivo: 0,
+                    path: arquivo_chegada.clone(),
+                    alg_hash: algor_hash_tmp_hsha256.clone(),
+                    valor_hash: hash_calculado.clone(),
+                    sha256_inteiro,
+                };
+                info!(
+                    "{} {}?{} ",
+                    arquivo_chegada.clone(),
+                    algor_hash_tmp_hsha256.clone(),
+                    hash_calculado.clone()
+                );
+                eventos::file_done(&arquivo_chegada, file_size, &algor_hash_tmp_hsha256, &hash_calculado, Value::Null);
+                return Some(fileCalculado);
+            }
+            Err(e) => {
+                progresso::erro(&format!("Error hsha256: {} with: {}", e, arquivo_chegada));
+                eventos::error(&arquivo_chegada, None, &e.to_string());
+            }
+        }
+    } else {
+        // ===> SHA256 normal
+
+        let algor_hash_tmp_sha256 = String::from("sha256");
+        info!(
+            "{} -> {}",
+            arquivo_chegada.clone(),
+            algor_hash_tmp_sha256.clone()
+        );
+        eventos::file_start(&arquivo_chegada, file_size, &algor_hash_tmp_sha256);
+
+        match hash_sha256(
+            &arquivo_chegada.clone(),
+            main_args.buffer_size as usize,
+        ) {
+            Ok(hash_calculado) => {
+                let fileCalculado = TFileHash {
+                    n_arquivo: 0,
+                    path: arquivo_chegada.clone(),
+                    alg_hash: algor_hash_tmp_sha256.clone(),
+                    valor_hash: hash_calculado.clone(),
+                    sha256_inteiro: None,
+                };
+                //   println!("{0} ?{1}*{2}",filePronto.valor_hash,filePronto.alg_hash,filePronto.path);
+
+                info!(
+                    "{} ?{}*{} ",
+                    arquivo_chegada.clone(),
+                    algor_hash_tmp_sha256.clone(),
+                    hash_calculado.clone()
+                );
+                eventos::file_done(&arquivo_chegada, file_size, &algor_hash_tmp_sha256, &hash_calculado, Value::Null);
+                return Some(fileCalculado);
+            }
+            Err(e) => {
+                progresso::erro(&format!("Error sha256: {} with: {}", e, arquivo_chegada));
+                eventos::error(&arquivo_chegada, None, &e.to_string());
+            }
+        }
+    }
+    None
 }
 
 pub fn search_files(pattern: &str) -> Result<Vec<String>, io::Error> {
@@ -282,7 +469,8 @@ pub fn search_files(pattern: &str) -> Result<Vec<String>, io::Error> {
     }
 
     info!("Glob to use: {}", glob_pattern);
-    for entry in glob(&glob_pattern).expect("Failed to read glob pattern") {
+    let entradas = glob(&glob_pattern).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    for entry in entradas {
         match entry {
             Ok(path) => {
                 if path.is_file() {
@@ -429,6 +617,7 @@ pub fn hash_hush(
     buffer_size: usize,    
     n_max_concur: u32,
     flag_show_progress: bool,
+    modo_leitura: ModoLeitura,
 ) -> Result<String, Box<dyn std::error::Error>> {
     /* 
     info!(
@@ -438,8 +627,9 @@ pub fn hash_hush(
     */
     info!("hash_hush: file: {}", file_path);
 
-    // Se tem - no algoritmo, é pq é hush
+    // Se tem - no algoritmo, é pq é hush. A versão da construção vem do nome.
     if let Some((algorithmRash, blocksize_str)) = alg_str.split_once('-') {        
+        let construcao = Construcao::parse(algorithmRash)?;
         match ParseSize(blocksize_str) {
             Ok(blocksize_recovered) => {
                 return hash_hsha256(
@@ -447,7 +637,10 @@ pub fn hash_hush(
                     buffer_size,
                     blocksize_recovered as usize,
                     n_max_concur,
-                    flag_show_progress
+                    flag_show_progress,
+                    modo_leitura,
+                    construcao,
+                    None,
                 ); 
             }
             Err(e) => return Err(e),
@@ -458,14 +651,18 @@ pub fn hash_hush(
 
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn hash_hsha256(
     file_path: &str,
     buffer_size: usize,
     chunk_size: usize,
     n_max_concur: u32,
-    flag_show_progress:bool
+    flag_show_progress:bool,
+    modo_leitura: ModoLeitura,
+    construcao: Construcao,
+    inteiro: Option<Arc<Sha256Inteiro>>,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let hash_alg = "hsha256".to_string();
+    let hash_alg = construcao.nome().to_string();
 
     info!(
         "alg:{} file: {} BlokSize:{} BufferSize:{} ",
@@ -473,268 +670,394 @@ pub fn hash_hsha256(
     );
 
     let blocos = calcular_blocos(file_path, buffer_size, chunk_size)?; // Função que calcula os blocos
+    let resultados = hash_hsha256_blocos(
+        file_path,
+        blocos,
+        buffer_size,
+        chunk_size,
+        n_max_concur,
+        flag_show_progress,
+        modo_leitura,
+        inteiro,
+    );
+    Ok(combina_hashes_blocos(file_path, resultados, chunk_size, construcao))
+}
 
-    let (sender_calculo, receiver_calculo): (Sender<ChunkBloco>, Receiver<ChunkBloco>) =
-        unbounded();
-    let (sender_resultado, receiver_resultado): (Sender<ChunkBloco>, Receiver<ChunkBloco>) =
+// Só para o bench: hsha256 dos blocos que começam antes de `limite`, para medir num pedaço
+// de um arquivo grande. Não é o hash do arquivo.
+pub fn hash_hsha256_parcial(
+    file_path: &str,
+    limite: u64,
+    buffer_size: usize,
+    chunk_size: usize,
+    n_max_concur: u32,
+    modo_leitura: ModoLeitura,
+    sequencial: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let blocos: Vec<ChunkBloco> = calcular_blocos(file_path, buffer_size, chunk_size)?
+        .into_iter()
+        .filter(|bloco| bloco.inicio_bloco < limite)
+        .collect();
+    if sequencial {
+        hash_hsha256_sequencial_blocos(file_path, blocos, buffer_size, chunk_size, false, Construcao::V1, None)
+    } else {
+        let resultados = hash_hsha256_blocos(file_path, blocos, buffer_size, chunk_size, n_max_concur, false, modo_leitura, None);
+        Ok(combina_hashes_blocos(file_path, resultados, chunk_size, Construcao::V1))
+    }
+}
+
+// Blocos hasheados no pool, ainda sem combinar
+#[allow(clippy::too_many_arguments)]
+fn hash_hsha256_blocos(
+    file_path: &str,
+    blocos: Vec<ChunkBloco>,
+    buffer_size: usize,
+    chunk_size: usize,
+    n_max_concur: u32,
+    flag_show_progress: bool,
+    modo_leitura: ModoLeitura,
+    inteiro: Option<Arc<Sha256Inteiro>>,
+) -> Vec<ChunkBloco> {
+    // Com --mmap, o arquivo é mapeado uma vez e compartilhado pelas tarefas.
+    // Se não der para mapear (pipe, device que recusa mmap...), segue com read().
+    let mapa = match modo_leitura {
+        ModoLeitura::Mmap => match mapeia_arquivo(file_path) {
+            Ok(mapa) => Some(Arc::new(mapa)),
+            Err(e) => {
+                warn!("mmap failed, using read: {} File:{}", e, file_path);
+                None
+            }
+        },
+        _ => None,
+    };
+    // Com --io_uring, os blocos são lidos pelo anel compartilhado por todos os arquivos
+    let servico_uring: Option<&'static ServicoUring> = match modo_leitura {
+        ModoLeitura::IoUring => uring::servico(buffer_size),
+        _ => None,
+    };
+
+    // Os blocos vão para o pool de hash compartilhado, no máximo n_max_concur deste arquivo
+    // ao mesmo tempo. Cada bloco reserva o seu buffer no limite global de bytes em voo.
+    let (sender_resultado, receiver_resultado): (Sender<ResultadoBloco>, Receiver<ResultadoBloco>) =
         unbounded();
+    let n_max_concur = n_max_concur.max(1) as usize;
+    let mut resultados = Vec::new();
+    let mut pendentes = 0;
 
-    // Enviar os blocos para o canal de cálculo
     for bloco in blocos {
-        //  println!("Enviado Bloco {} -> p/ calculo", bloco.n_bloco);
-        info!("+");
-        io::stdout().flush().expect("Failed to flush stdout");
-        sender_calculo.send(bloco).unwrap();
-        if flag_show_progress {
-            eprint!(">");
+        if pendentes >= n_max_concur {
+            resultados.push(recebe_bloco(&receiver_resultado, file_path, flag_show_progress));
+            pendentes -= 1;
         }
-    }
-    drop(sender_calculo); // Dropar após envio
-
-    let num_cpus = num_cpus::get();
-
-    // Criar threads para calcular o hash dos blocos usando BufReader
-    let mut handles = Vec::new();
-
-    for n_cpu in 0..n_max_concur {
-        //let file_path_clone: String = file_path.to_string();
-        info!("Cpu {} start running", n_cpu);
-        let file_path_clone: String = String::from(file_path);
-
-        let file = File::open(file_path)?;
-        let receiver_calculo_clone = receiver_calculo.clone();
+        let reserva = pool::reserva(buffer_size as u64);
         let sender_resultado_clone = sender_resultado.clone();
-        let mut reader = BufReader::new(file); // BufReader associado ao descritor clonado
-
-        let handle = thread::spawn(move || {
-            while let Ok(bloco) = receiver_calculo_clone.recv() {
-                let n_bloco = bloco.n_bloco;
-                //println!("Recebido Bloco {} para cálculo de hash", n_bloco);
-                //calcular_hash_bloco(&mut reader, &mut bloco).unwrap(); // Passar o BufReader para calcular o hash
-                match calcular_hash_bloco(&mut reader, bloco, buffer_size, chunk_size) {
-                    
-                    Ok(bloco_recebido) => {
-                        info!("*");
-                        io::stdout().flush().expect("Failed to flush stdout");
-                        sender_resultado_clone.send(bloco_recebido).unwrap();
-                        //  println!("Bloco {} calculado e enviado para resultado", n_bloco);
-                    }
-                    Err(e) => {
-                        eprintln!(
-                            "Error hush Block: {}: {:?} File:{} ",
-                            n_bloco, e, file_path_clone
-                        );
-                        process::exit(1);
-                    }
-                }
-                if flag_show_progress {
-                    eprint!("<");
-                }
-            }
+        let file_path_clone: String = String::from(file_path);
+        let mapa_clone = mapa.clone();
+        let inteiro_clone = inteiro.clone();
+        pool::pool().executa(move || {
+            let (n_bloco, inicio_bloco) = (bloco.n_bloco, bloco.inicio_bloco);
+            let resultado = calcular_hash_bloco_tarefa(
+                &file_path_clone,
+                bloco,
+                buffer_size,
+                chunk_size,
+                mapa_clone.as_deref(),
+                servico_uring,
+                inteiro_clone.as_deref(),
+            );
+            drop(reserva);
+            let _ = sender_resultado_clone.send((n_bloco, inicio_bloco, resultado));
         });
-        handles.push(handle);
+        pendentes += 1;
+        info!("+");
     }
-
-    drop(sender_resultado); // Dropar após o término das threads
+    drop(sender_resultado);
 
     // Coletar os resultados dos hashes
-    let mut resultados = Vec::new();
-    while let Ok(bloco) = receiver_resultado.recv() {
-        //  println!("Recebido Bloco {} no resultado", bloco.n_bloco);
-        info!("-");
-        io::stdout().flush().expect("Failed to flush stdout");
-        resultados.push(bloco);
+    while pendentes > 0 {
+        resultados.push(recebe_bloco(&receiver_resultado, file_path, flag_show_progress));
+        pendentes -= 1;
     }
 
-    // Esperar todas as threads de cálculo terminarem
-    for handle in handles {
-        handle.join().unwrap();
+    resultados
+}
+
+// Número e início do bloco, e o bloco hasheado (ou o erro), de uma tarefa do pool
+type ResultadoBloco = (u64, u64, io::Result<ChunkBloco>);
+
+// Espera o próximo bloco pronto do arquivo. Erro de leitura em bloco encerra, como sempre.
+fn recebe_bloco(
+    receiver_resultado: &Receiver<ResultadoBloco>,
+    file_path: &str,
+    flag_show_progress: bool,
+) -> ChunkBloco {
+    let (n_bloco, inicio_bloco, resultado) = receiver_resultado
+        .recv()
+        .expect("Hash pool stopped");
+    match resultado {
+        Ok(bloco) => {
+            info!("-");
+            if flag_show_progress {
+                progresso::bloco_pronto(file_path, bloco.fim_bloco - bloco.inicio_bloco);
+            }
+            eventos::block_done(
+                file_path,
+                bloco.n_bloco,
+                bloco.inicio_bloco,
+                bloco.fim_bloco,
+                &bloco.hash_bloco,
+            );
+            bloco
+        }
+        Err(e) => {
+            eprintln!("Error hush Block: {}: {:?} File:{} ", n_bloco, e, file_path);
+            eventos::error(file_path, Some(inicio_bloco), &format!("block {}: {}", n_bloco, e));
+            eventos::summary("aborted");
+            process::exit(1);
+        }
     }
+}
+
+// Tarefa do pool: lê e hasheia um bloco, com um descritor só dela.
+// Com `inteiro`, os mesmos pedaços também vão para o sha256 do arquivo todo.
+fn calcular_hash_bloco_tarefa(
+    file_path: &str,
+    bloco: ChunkBloco,
+    buffer_size: usize,
+    chunk_size: usize,
+    mapa: Option<&Mmap>,
+    servico_uring: Option<&ServicoUring>,
+    inteiro: Option<&Sha256Inteiro>,
+) -> io::Result<ChunkBloco> {
+    let mut fatia = inteiro.map(|inteiro| inteiro.fatia(bloco.inicio_bloco, bloco.fim_bloco));
+    let file = cache::abre_arquivo(file_path, mapa.is_none() && servico_uring.is_none())?;
+    let mut reader = BufReader::new(file);
+    let bloco_calculado = match (mapa, servico_uring) {
+        (Some(mapa), _) => calcular_hash_bloco_mmap(mapa, &mut reader, bloco, buffer_size, chunk_size, fatia.as_mut())?,
+        // Com io_uring, o descritor da tarefa vai nos pedidos ao anel
+        (None, Some(servico)) => {
+            let file_uring = Arc::new(reader.get_ref().try_clone()?);
+            calcular_hash_bloco_uring(servico, &file_uring, bloco, fatia.as_mut())?
+        }
+        (None, None) => calcular_hash_bloco(&mut reader, bloco, buffer_size, chunk_size, fatia.as_mut())?,
+    };
+    // Bloco hasheado não precisa mais ficar no page cache
+    cache::descarta_cache(
+        reader.get_ref(),
+        bloco_calculado.inicio_bloco,
+        bloco_calculado.fim_bloco - bloco_calculado.inicio_bloco,
+    );
+    Ok(bloco_calculado)
+}
 
+// Hash do arquivo a partir dos hashes dos blocos, pela construção (v1 ou v2, em construcao.rs)
+fn combina_hashes_blocos(
+    file_path: &str,
+    mut resultados: Vec<ChunkBloco>,
+    chunk_size: usize,
+    construcao: Construcao,
+) -> String {
     // Ordenar os resultados para garantir a ordem correta
     resultados.sort_by_key(|bloco| bloco.n_bloco);
 
     // Imprimir os resultados ordenados
     info!("\n");
-
-    let hash_final: String;
-    if resultados.len() == 1 {
-        let bloco = &resultados[0];
-        hash_final = bloco.hash_bloco.clone();
-    } else {
-        let mut hasher_cumulativo = Sha256::new();        
-        info!("File;Block;bytes_start;byte_end;hash");
-        for bloco in resultados {
-            info!(
-                "{0}{1:10};{2:10};{3:10};{4}",
-                file_path,
-                bloco.n_bloco, bloco.inicio_bloco, bloco.fim_bloco, bloco.hash_bloco
-            );
-            hasher_cumulativo.update(bloco.hash_bloco.as_bytes());
-        }
-        let hash_cumulativo_result = hasher_cumulativo.finalize();
-        hash_final = format!("{:x}", hash_cumulativo_result);
-        info!("File:{} => Hush:{}",file_path,hash_final);
+    info!("File;Block;bytes_start;byte_end;hash");
+    for bloco in &resultados {
+        info!(
+            "{0}{1:10};{2:10};{3:10};{4}",
+            file_path,
+            bloco.n_bloco, bloco.inicio_bloco, bloco.fim_bloco, bloco.hash_bloco
+        );
     }
+    let tamanho = resultados.last().map(|bloco| bloco.fim_bloco).unwrap_or(0);
+    let hashes_blocos: Vec<&str> = resultados.iter().map(|bloco| bloco.hash_bloco.as_str()).collect();
+    let hash_final = construcao.combina(&hashes_blocos, tamanho, chunk_size as u64);
+    info!("File:{} => Hush:{}",file_path,hash_final);
 
-    Ok(hash_final)
+    hash_final
 }
 
-pub fn hash_sha256(
+// Pedaços de um bloco na fila da sua tarefa de hash: segura o leitor se o hash ficar para trás
+const PEDACOS_EM_FILA: usize = 64;
+
+// hsha256 com um leitor só, do começo ao fim do arquivo (disco rotacional, sem pular a cabeça).
+// O leitor corta o fluxo nos limites de calcular_blocos e manda os pedaços de cada bloco para
+// uma tarefa no pool de hash, então o resultado é o do hash_hsha256.
+pub fn hash_hsha256_sequencial(
     file_path: &str,
     buffer_size: usize,
+    chunk_size: usize,
+    flag_show_progress: bool,
+    construcao: Construcao,
+    inteiro: Option<Arc<Sha256Inteiro>>,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    // Abrindo o arquivo para leitura
-    let mut file = File::open(file_path)?;
-    let mut hasher = Sha256::new();
-    let mut buffer = vec![0; buffer_size];
-
-    // Lendo o arquivo em blocos e alimentando o hasher
-    loop {
-        let bytes_read = file.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break; // Fim do arquivo
-        }
-        hasher.update(&buffer[..bytes_read]);
-    }
+    info!(
+        "alg:{} (sequential) file: {} BlokSize:{} BufferSize:{} ",
+        construcao.nome(), file_path, chunk_size, buffer_size
+    );
 
-    // Calculando o hash final e convertendo para string hexadecimal
-    let hash_result = hasher.finalize();
-    Ok(format!("{:x}", hash_result))
+    let blocos = calcular_blocos(file_path, buffer_size, chunk_size)?;
+    hash_hsha256_sequencial_blocos(file_path, blocos, buffer_size, chunk_size, flag_show_progress, construcao, inteiro.as_deref())
 }
-// Função que reconstrói o arquivo a partir das partes, usando um buffer menor
-pub fn rebuild(
-    dir_destino: &str,
-    file: &str,
+
+fn hash_hsha256_sequencial_blocos(
+    file_path: &str,
+    blocos: Vec<ChunkBloco>,
     buffer_size: usize,
-    _chunk_size: usize,
-) -> io::Result<()> {
-    // Listando todos os arquivos no diretório
-    let mut parts: Vec<_> = fs::read_dir(dir_destino)?
-        .filter_map(|entry| {
-            let entry = entry.ok()?;
-            let path = entry.path();
-            let filename = path.file_name()?.to_str()?.to_string();
-
-            // Verificando se o nome do arquivo termina com "_<número>"
-            if filename.contains("_") {
-                Some((path, filename))
-            } else {
-                None
-            }
-        })
-        .collect();
+    chunk_size: usize,
+    flag_show_progress: bool,
+    construcao: Construcao,
+    inteiro: Option<&Sha256Inteiro>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let n_blocos = blocos.len();
 
-    // Ordenando os arquivos com base no sufixo numérico
-    parts.sort_by_key(|(_, filename)| {
-        filename
-            .rsplit('_')
-            .next()
-            .and_then(|s| s.parse::<u32>().ok())
-            .unwrap_or(0)
-    });
+    let (sender_resultado, receiver_resultado): (Sender<ResultadoBloco>, Receiver<ResultadoBloco>) =
+        unbounded();
+    let leitura = le_blocos_sequencial(file_path, buffer_size, blocos, &sender_resultado, inteiro);
+    drop(sender_resultado);
 
-    // Criando o arquivo final onde o conteúdo será reconstituído
-    let mut output_file = File::create(file)?;
-    let mut buffer = vec![0; buffer_size];
+    // Com erro na leitura, as tarefas já enviadas terminam sozinhas (o canal do bloco fecha)
+    leitura?;
+
+    let resultados: Vec<ChunkBloco> = (0..n_blocos)
+        .map(|_| recebe_bloco(&receiver_resultado, file_path, flag_show_progress))
+        .collect();
+    Ok(combina_hashes_blocos(file_path, resultados, chunk_size, construcao))
+}
 
-    // Lendo os arquivos de pedaço na ordem e escrevendo no arquivo final
-    for (path, _) in parts {
-        let mut part_file = File::open(path)?;
+// Lê o arquivo do começo ao fim e manda os pedaços de cada bloco para a tarefa dele no pool.
+// Se o arquivo acabar antes (diminuiu), os blocos restantes ficam com o que foi lido, como no read().
+// Com `inteiro`, o leitor passa os pedaços em ordem para o sha256 do arquivo todo.
+fn le_blocos_sequencial(
+    file_path: &str,
+    buffer_size: usize,
+    blocos: Vec<ChunkBloco>,
+    sender_resultado: &Sender<ResultadoBloco>,
+    inteiro: Option<&Sha256Inteiro>,
+) -> io::Result<()> {
+    let mut file = cache::abre_arquivo(file_path, true)?;
+    // Com --direct, o offset anda de buffer alinhado em buffer alinhado: continua alinhado
+    let mut buffer_alinhado = match cache::politica() {
+        PoliticaCache::Direct => Some(cache::BufferAlinhado::new(buffer_size)),
+        _ => None,
+    };
+    let mut pendente: Vec<u8> = Vec::new(); // lido e ainda não enviado
+    let mut fim_arquivo = false;
 
-        // Lendo o arquivo de pedaço em blocos pequenos e escrevendo no arquivo final
-        loop {
-            let bytes_read = part_file.read(&mut buffer)?;
-            if bytes_read == 0 {
-                break; // Fim do pedaço
+    for bloco in blocos {
+        let (inicio_bloco, fim_bloco) = (bloco.inicio_bloco, bloco.fim_bloco);
+        let mut fatia = inteiro.map(|inteiro| inteiro.fatia(inicio_bloco, fim_bloco));
+        // Os pedaços na fila do bloco são os bytes em voo deste leitor
+        let reserva = pool::reserva((PEDACOS_EM_FILA * buffer_size) as u64);
+        let (sender_pedacos, receiver_pedacos) = bounded::<Vec<u8>>(PEDACOS_EM_FILA);
+        let sender_resultado_clone = sender_resultado.clone();
+        pool::pool().executa(move || {
+            let mut hasher = Sha256::new();
+            for dados in receiver_pedacos.iter() {
+                hasher.update(&dados);
+            }
+            drop(reserva);
+            let hash_bloco = format!("{:x}", hasher.finalize());
+            let _ = sender_resultado_clone.send((
+                bloco.n_bloco,
+                bloco.inicio_bloco,
+                Ok(ChunkBloco { hash_bloco, ..bloco }),
+            ));
+        });
+
+        let mut restante = fim_bloco - inicio_bloco;
+        while restante > 0 && !fim_arquivo {
+            if pendente.is_empty() {
+                controle::consome(buffer_size);
+                let n = loop {
+                    let lido = match &mut buffer_alinhado {
+                        Some(buffer) => file.read(buffer),
+                        None => {
+                            pendente.resize(buffer_size.max(1), 0);
+                            file.read(&mut pendente)
+                        }
+                    };
+                    match lido {
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        lido => break lido?,
+                    }
+                };
+                match &buffer_alinhado {
+                    Some(buffer) => pendente.extend_from_slice(&buffer[..n]),
+                    None => pendente.truncate(n),
+                }
+                if n == 0 {
+                    fim_arquivo = true;
+                    break;
+                }
+            }
+            let dados = if pendente.len() as u64 > restante {
+                let resto = pendente.split_off(restante as usize);
+                std::mem::replace(&mut pendente, resto)
+            } else {
+                std::mem::take(&mut pendente)
+            };
+            restante -= dados.len() as u64;
+            if let Some(fatia) = &mut fatia {
+                fatia.atualiza(&dados);
             }
-            output_file.write_all(&buffer[..bytes_read])?;
+            sender_pedacos
+                .send(dados)
+                .map_err(|_| io::Error::other("hash task stopped"))?;
         }
+        // Bloco inteiro já lido: pode sair do page cache
+        cache::descarta_cache(&file, inicio_bloco, fim_bloco - inicio_bloco);
     }
-
     Ok(())
 }
 
-// Função que divide o arquivo original em partes de 20 MB, calculando o hash SHA-256 de cada parte
-pub fn split(
-    file: &str,
-    dir_destino: &str,
+pub fn hash_sha256(
+    file_path: &str,
     buffer_size: usize,
-    chunk_size: usize,
-) -> io::Result<()> {
-    // Abrindo o arquivo original para leitura
-    let mut input_file = File::open(file)?;
-    let mut buffer = vec![0; buffer_size];
-    let mut index = 0;
-    let mut bytes_written = 0;
-
-    let path_dir = Path::new(dir_destino);
+) -> Result<String, Box<dyn std::error::Error>> {
+    // A leitura roda no pool de hash, com o buffer reservado no limite de bytes em voo
+    let reserva = pool::reserva(buffer_size as u64);
+    let (sender_resultado, receiver_resultado) = bounded::<io::Result<String>>(1);
+    let file_path_clone: String = String::from(file_path);
+    pool::pool().executa(move || {
+        let resultado = hash_sha256_leitura(&file_path_clone, buffer_size);
+        drop(reserva);
+        let _ = sender_resultado.send(resultado);
+    });
+    let hash_calculado = receiver_resultado
+        .recv()
+        .map_err(|_| io::Error::other("Hash pool stopped"))??;
+    Ok(hash_calculado)
+}
 
-    if !path_dir.exists() {
-        // Cria o diretório, incluindo diretórios intermediários, se necessário
-        fs::create_dir_all(path_dir)?;
-        info!("Dir created: {}", dir_destino);
+fn hash_sha256_leitura(file_path: &str, buffer_size: usize) -> io::Result<String> {
+    // Abrindo o arquivo para leitura
+    let mut file = cache::abre_arquivo(file_path, true)?;
+    if cache::politica() == PoliticaCache::Direct {
+        let hash_direto = cache::hash_intervalo_direto(&file, 0, u64::MAX, buffer_size, None)?;
+        cache::descarta_cache(&file, 0, 0);
+        return Ok(hash_direto);
     }
-
-    // Nomeando o primeiro arquivo de saída
-    let mut part_filename = format!(
-        "{}/{}_{}",
-        dir_destino,
-        Path::new(file).file_name().unwrap().to_str().unwrap(),
-        index
-    );
-    let mut part_file = File::create(&part_filename)?;
     let mut hasher = Sha256::new();
+    let mut buffer = vec![0; buffer_size];
 
-    // Lendo o arquivo em pedaços pequenos e escrevendo nos arquivos menores
+    // Lendo o arquivo em blocos e alimentando o hasher
     loop {
-        let bytes_read = input_file.read(&mut buffer)?;
+        controle::consome(buffer.len());
+        let bytes_read = file.read(&mut buffer)?;
         if bytes_read == 0 {
             break; // Fim do arquivo
         }
-
-        // Escrevendo o buffer no arquivo de saída atual
-        part_file.write_all(&buffer[..bytes_read])?;
         hasher.update(&buffer[..bytes_read]);
-        bytes_written += bytes_read;
-
-        // Se o tamanho total escrito atingir o limite do pedaço, calcular o hash e passar para o próximo arquivo
-        if bytes_written >= chunk_size as usize {
-            // Calcular o hash SHA-256 do bloco
-            let hash_result = hasher.finalize();
-            let hash_hex = format!("{:x}", hash_result);
-
-            // Escrever o hash em um arquivo com a extensão .sha256
-            let hash_filename = format!("{}.sha256.txt", part_filename);
-            let mut hash_file = File::create(&hash_filename)?;
-            hash_file.write_all(hash_hex.as_bytes())?;
-
-            // Reiniciar para o próximo bloco
-            index += 1;
-            bytes_written = 0;
-            part_filename = format!(
-                "{}/{}_{}",
-                dir_destino,
-                Path::new(file).file_name().unwrap().to_str().unwrap(),
-                index
-            );
-            part_file = File::create(&part_filename)?;
-            hasher = Sha256::new(); // Reiniciar o hasher para o próximo bloco
-        }
     }
 
-    // Calcular e salvar o hash do último bloco se ele não for vazio
-    if bytes_written > 0 {
-        let hash_result = hasher.finalize();
-        let hash_hex = format!("{:x}", hash_result);
-        let hash_filename = format!("{}.sha256.txt", part_filename);
-        let mut hash_file = File::create(&hash_filename)?;
-        hash_file.write_all(hash_hex.as_bytes())?;
-    }
+    cache::descarta_cache(&file, 0, 0);
 
-    Ok(())
+    // Calculando o hash final e convertendo para string hexadecimal
+    let hash_result = hasher.finalize();
+    Ok(format!("{:x}", hash_result))
 }
-
 // Função que divide o arquivo original em partes de 20 MB, usando um buffer menor
 pub fn divide_sem_hash(
     file: &str,
@@ -785,6 +1108,118 @@ pub fn divide_sem_hash(
     Ok(())
 }
 
+// Bloco do split e o que foi gravado na parte dele
+pub type ParteDividida = (ChunkBloco, Guardado);
+
+// Split em paralelo: cada bloco de calcular_blocos vira uma parte (aberta por abre_parte), escrita
+// e hasheada pela mesma tarefa no pool de hash, no máximo n_max_concur ao mesmo tempo.
+// O hash de cada parte é o hash do bloco, e o hush sai dos blocos, sem ler o arquivo de novo.
+// Retorna os blocos, na ordem, com o tamanho e o sha256 do que foi gravado quando a parte é
+// comprimida ou cifrada, e o hush.
+pub fn divide_hsha256(
+    file_path: &str,
+    abre_parte: &dyn Fn(u64) -> io::Result<Box<dyn EscritaParte>>,
+    buffer_size: usize,
+    chunk_size: usize,
+    n_max_concur: u32,
+    construcao: Construcao,
+) -> Result<(Vec<ParteDividida>, String), Box<dyn std::error::Error>> {
+    info!(
+        "split: file: {} BlokSize:{} BufferSize:{} ",
+        file_path, chunk_size, buffer_size
+    );
+    let blocos = calcular_blocos(file_path, buffer_size, chunk_size)?;
+
+    type ResultadoParte = (u64, io::Result<ParteDividida>);
+    let (sender_resultado, receiver_resultado): (Sender<ResultadoParte>, Receiver<ResultadoParte>) =
+        unbounded();
+    let n_max_concur = n_max_concur.max(1) as usize;
+    let mut resultados = Vec::new();
+    let mut erros = Vec::new();
+    let mut pendentes = 0;
+    // Erro numa parte não para as outras: todas são esperadas e os erros vão juntos
+    let recebe = |resultados: &mut Vec<ParteDividida>, erros: &mut Vec<String>| {
+        let (n_bloco, resultado) = receiver_resultado.recv().expect("Hash pool stopped");
+        match resultado {
+            Ok(parte) => resultados.push(parte),
+            Err(e) => erros.push(format!("part {}: {}", n_bloco, e)),
+        }
+    };
+
+    for bloco in blocos {
+        if pendentes >= n_max_concur {
+            recebe(&mut resultados, &mut erros);
+            pendentes -= 1;
+        }
+        let escrita = match abre_parte(bloco.n_bloco) {
+            Ok(escrita) => escrita,
+            Err(e) => {
+                erros.push(format!("part {}: {}", bloco.n_bloco, e));
+                continue;
+            }
+        };
+        let reserva = pool::reserva(buffer_size as u64);
+        let sender_resultado_clone = sender_resultado.clone();
+        let file_path_clone = String::from(file_path);
+        pool::pool().executa(move || {
+            let n_bloco = bloco.n_bloco;
+            let resultado = divide_bloco_tarefa(&file_path_clone, escrita, bloco, buffer_size);
+            drop(reserva);
+            let _ = sender_resultado_clone.send((n_bloco, resultado));
+        });
+        pendentes += 1;
+    }
+    drop(sender_resultado);
+    while pendentes > 0 {
+        recebe(&mut resultados, &mut erros);
+        pendentes -= 1;
+    }
+
+    if !erros.is_empty() {
+        erros.sort();
+        return Err(erros.join("; ").into());
+    }
+    resultados.sort_by_key(|(bloco, _)| bloco.n_bloco);
+    let blocos = resultados.iter().map(|(bloco, _)| bloco.clone()).collect();
+    let hush = combina_hashes_blocos(file_path, blocos, chunk_size, construcao);
+    Ok((resultados, hush))
+}
+
+// Tarefa do pool: copia um bloco para a sua parte, com o sha256 do que foi copiado
+fn divide_bloco_tarefa(
+    file_path: &str,
+    mut escrita: Box<dyn EscritaParte>,
+    bloco: ChunkBloco,
+    buffer_size: usize,
+) -> io::Result<ParteDividida> {
+    // Sem O_DIRECT: as leituras do buffer não são alinhadas
+    let mut file = cache::abre_arquivo(file_path, false)?;
+    file.seek(io::SeekFrom::Start(bloco.inicio_bloco))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; buffer_size.max(1)];
+    let mut bytes_restantes = bloco.fim_bloco - bloco.inicio_bloco;
+
+    while bytes_restantes > 0 {
+        let bytes_a_ler = std::cmp::min(buffer.len() as u64, bytes_restantes) as usize;
+        controle::consome(bytes_a_ler);
+        let n = match file.read(&mut buffer[..bytes_a_ler]) {
+            Ok(0) => return Err(io::Error::other("file shrank while splitting")),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        escrita.write_all(&buffer[..n])?;
+        hasher.update(&buffer[..n]);
+        bytes_restantes -= n as u64;
+    }
+    let guardado = escrita.termina()?;
+    cache::descarta_cache(&file, bloco.inicio_bloco, bloco.fim_bloco - bloco.inicio_bloco);
+
+    let hash_bloco = format!("{:x}", hasher.finalize());
+    info!("part {} offset:{} size:{} sha256:{}", bloco.n_bloco, bloco.inicio_bloco, bloco.fim_bloco - bloco.inicio_bloco, hash_bloco);
+    Ok((ChunkBloco { hash_bloco, ..bloco }, guardado))
+}
+
 // Estrutura para armazenar as informações de cada bloco
 
 // Função para calcular os blocos de 50 MB de um arquivo
@@ -822,7 +1257,20 @@ fn calcular_hash_bloco(
     bloco: ChunkBloco,
     buffer_size: usize,
     _chunk_size: usize,
+    mut inteiro: Option<&mut FatiaInteiro>,
 ) -> Result<ChunkBloco, io::Error> {
+    // Com --direct, leitura alinhada direto do descritor (o BufReader não é alinhado)
+    if cache::politica() == PoliticaCache::Direct {
+        let hash_bloco = cache::hash_intervalo_direto(
+            reader.get_ref(),
+            bloco.inicio_bloco,
+            bloco.fim_bloco,
+            buffer_size,
+            inteiro,
+        )?;
+        return Ok(ChunkBloco { hash_bloco, ..bloco });
+    }
+
     let mut hasher = Sha256::new();
     reader.seek(io::SeekFrom::Start(bloco.inicio_bloco))?; // Ir até o offset do bloco
 
@@ -831,10 +1279,14 @@ fn calcular_hash_bloco(
 
     while bytes_restantes > 0 {
         let bytes_a_ler = std::cmp::min(buffer.len() as u64, bytes_restantes) as usize;
+        controle::consome(bytes_a_ler);
         match reader.read(&mut buffer[..bytes_a_ler]) {
             Ok(0) => break, // EOF
             Ok(n) => {
                 hasher.update(&buffer[..n]); // Atualiza o hash com os dados lidos
+                if let Some(fatia) = &mut inteiro {
+                    fatia.atualiza(&buffer[..n]);
+                }
                 bytes_restantes -= n as u64;
             }
             Err(e) => return Err(e), // Retorna um erro se a leitura falhar
@@ -851,22 +1303,133 @@ fn calcular_hash_bloco(
     })
 }
 
-fn parse_line(line: &str) -> Option<(String, String, String, String)> {
-    // Split the line by the specific characters ? | *
-    let parts: Vec<&str> = line.split(&['?', '|', '*'][..]).collect();
-
-    // Check if we have exactly 4 parts
-    if parts.len() == 4 {
-        let hash_final = parts[0].to_string();
-        let hash_alg = parts[1].to_string();
-        let chunk_size_str = parts[2].to_string();
-        let file_path = parts[3].to_string();
-        Some((hash_final, hash_alg, chunk_size_str, file_path))
-    } else {
-        None
+fn mapeia_arquivo(file_path: &str) -> io::Result<Mmap> {
+    let file = File::open(file_path)?;
+    if !file.metadata()?.is_file() {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "not a regular file"));
+    }
+    // Safety: o mapa é só leitura, mas não protege de quem escreve no arquivo. Se o arquivo
+    // diminuir enquanto um bloco é lido do mapa, o processo recebe SIGBUS e morre: conferir o
+    // tamanho antes do bloco não evita isso (o arquivo pode diminuir depois da conferência).
+    // Por isso --mmap é só para arquivos que ninguém está escrevendo, e o gen --auto não o escolhe.
+    unsafe { Mmap::map(&file) }
+}
+
+// Hash do bloco direto do arquivo mapeado, sem copiar para buffer.
+// Se o arquivo já diminuiu antes do bloco, volta para o read() no descritor da thread (se
+// diminuir durante o bloco, é SIGBUS: ver mapeia_arquivo).
+fn calcular_hash_bloco_mmap(
+    mapa: &Mmap,
+    reader: &mut BufReader<File>,
+    bloco: ChunkBloco,
+    buffer_size: usize,
+    chunk_size: usize,
+    mut inteiro: Option<&mut FatiaInteiro>,
+) -> Result<ChunkBloco, io::Error> {
+    let tamanho_atual = reader.get_ref().metadata()?.len();
+    if bloco.fim_bloco > tamanho_atual || bloco.fim_bloco > mapa.len() as u64 {
+        warn!(
+            "File shrank while hashing ({} < {}), using read in block {}",
+            tamanho_atual, bloco.fim_bloco, bloco.n_bloco
+        );
+        return calcular_hash_bloco(reader, bloco, buffer_size, chunk_size, inteiro);
+    }
+
+    let mut hasher = Sha256::new();
+    for pedaco in mapa[bloco.inicio_bloco as usize..bloco.fim_bloco as usize].chunks(buffer_size.max(1)) {
+        controle::consome(pedaco.len()); // a leitura do disco acontece aqui, nas faltas de página
+        hasher.update(pedaco);
+        if let Some(fatia) = &mut inteiro {
+            fatia.atualiza(pedaco);
+        }
+    }
+
+    let result = hasher.finalize();
+    Ok(ChunkBloco {
+        n_bloco: bloco.n_bloco,
+        inicio_bloco: bloco.inicio_bloco,
+        fim_bloco: bloco.fim_bloco,
+        hash_bloco: format!("{:x}", result),
+    })
+}
+
+// Hash do bloco com os pedaços lidos pelo anel do io_uring
+fn calcular_hash_bloco_uring(
+    servico: &ServicoUring,
+    file: &Arc<File>,
+    bloco: ChunkBloco,
+    inteiro: Option<&mut FatiaInteiro>,
+) -> Result<ChunkBloco, io::Error> {
+    let hash_bloco = uring::hash_intervalo_uring(servico, file, bloco.inicio_bloco, bloco.fim_bloco, inteiro)?;
+    Ok(ChunkBloco {
+        n_bloco: bloco.n_bloco,
+        inicio_bloco: bloco.inicio_bloco,
+        fim_bloco: bloco.fim_bloco,
+        hash_bloco,
+    })
+}
+
+// Lê uma linha de manifest: "<hash> ?<alg>*<caminho>".
+// Aceita também "<hash> ?<alg>|<blocksize>*<caminho>", que é a saída do subcomando hsha256.
+pub fn parse_line(line: &str) -> Result<TFileHash, String> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let (hash_lido, rest) = line
+        .split_once('?')
+        .ok_or_else(|| "not contain '?' char.".to_string())?;
+    let (alg_lido, caminho_relativo) = rest
+        .split_once('*')
+        .ok_or_else(|| "not contain '*' char.".to_string())?;
+
+    Ok(TFileHash {
+        n_arquivo: 0,
+        path: caminho_relativo.to_string(),
+        alg_hash: alg_lido.trim().replacen('|', "-", 1),
+        valor_hash: hash_lido.trim().to_lowercase(),
+        sha256_inteiro: None,
+    })
+}
+
+// Lê um manifest inteiro. Linhas inválidas voltam como mensagens de erro, sem parar a leitura.
+pub fn ler_manifest(file_path: &str) -> io::Result<(Vec<TFileHash>, Vec<String>)> {
+    let reader = BufReader::new(File::open(file_path)?);
+    let mut entradas = Vec::new();
+    let mut erros = Vec::new();
+
+    for (n_linha, line_result) in reader.lines().enumerate() {
+        let line = line_result?;
+        // Cabeçalho e linhas de MAC (#...) não são entradas. Outras linhas com "#" são erro.
+        if mac::linha_do_hush(&line) {
+            continue;
+        }
+        if line.starts_with('#') {
+            erros.push(format!("Line:{} File:{} not a hush line (starts with '#')", n_linha + 1, file_path));
+            continue;
+        }
+        match parse_line(&line) {
+            Ok(mut entrada) => {
+                entrada.n_arquivo = n_linha as u64 + 1;
+                entradas.push(entrada);
+            }
+            Err(e) => erros.push(format!("Line:{} File:{} {}", n_linha + 1, file_path, e)),
+        }
     }
+    Ok((entradas, erros))
 }
 
+// Separa "hsha256-50MB" em ("hsha256", Some(52428800)). Sem blocksize, volta None.
+// Com o blocksize em bytes, "50MB" e "51200KB" são o mesmo algoritmo; "hsha256v1" é o "hsha256".
+pub fn normaliza_alg(alg_hash: &str) -> (String, Option<u64>) {
+    match alg_hash.split_once('-') {
+        Some((alg, blocksize_str)) => match ParseSize(blocksize_str) {
+            Ok(blocksize) => match Construcao::parse(alg) {
+                Ok(construcao) => (construcao.nome().to_string(), Some(blocksize)),
+                Err(_) => (alg.to_lowercase(), Some(blocksize)),
+            },
+            Err(_) => (alg_hash.to_lowercase(), None),
+        },
+        None => (alg_hash.to_lowercase(), None),
+    }
+}
 
 pub fn check_hash(
     main_args: Argumentos,
@@ -893,6 +1456,21 @@ pub fn check_hash(
     let mut n_errors=0;
     let mut n_acertos=0;
     let mut n_linhas=0;
+    let mut n_comentarios=0;
+
+    // MAC do manifest, antes de ler qualquer dado
+    let problemas_mac = mac::confere_manifest(file_path)?;
+    if !problemas_mac.is_empty() {
+        for problema in &problemas_mac {
+            let error_msg = format!("MAC Error! {}", problema);
+            error!("{}", error_msg);
+            eprintln!("{}", error_msg);
+            eventos::error(file_path, None, &error_msg);
+        }
+        eprintln!("Manifest MAC does not verify. No file was checked.");
+        eventos::summary("mac_failed");
+        process::exit(3);
+    }
 
     let path = Path::new(file_path);
     let file = File::open(&path)?;
@@ -900,62 +1478,75 @@ pub fn check_hash(
 
     let mut error_results = Vec::new();
 
+    if main_args.flag_show_progress || eventos::ativo() || auditoria::ativo() {
+        // Total de arquivos e bytes a conferir, antes de começar
+        eventos::walk_start(work_dir);
+        let (mut n_arquivos, mut n_bytes) = (0, 0);
+        for entrada in BufReader::new(File::open(path)?)
+            .lines()
+            .map_while(Result::ok)
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| parse_line(&line).ok())
+        {
+            n_arquivos += 1;
+            if let Ok(metadata) = fs::metadata(gera_caminho_completo(&entrada.path, work_dir)) {
+                n_bytes += metadata.len();
+            }
+        }
+        if main_args.flag_show_progress {
+            progresso::inicia(n_arquivos, n_bytes);
+        }
+        eventos::walk_finish(work_dir, n_arquivos, n_bytes);
+    }
+
     // Read the file line by line
     for line_result in reader.lines() {
         match line_result {
             Ok(line) => {
                 // Parse each line
                 n_linhas+=1;
-                let split_result = line.split_once('?');
-                if split_result.is_none() { // Erro lendo ?
-                    n_errors+=1;
-                    let error_msg = format!(
-                        "Error {}! Line:{} File:{} mot contain '?' char.",
-                         n_errors, n_linhas, file_path
-                    );
-                    error_results.push(error_msg.clone());
-                    error!("{}",error_msg);
-                    eprintln!("{}",error_msg);
-                    if main_args.flag_stop_on_first_error {                        
-                        process::exit(1);                            
-                    }                            
-                    continue;
-                }
-                let (hash_lido_tmp, rest) = split_result.unwrap();
-                
-                    // Split the remaining part by `*` to separate algorithm and path
-                //info!("Hash Lido raw:*{}*", hash_lido_tmp);                
-                let mut hash_lido = hash_lido_tmp.to_string();
-                let mut hash_lido2 = hash_lido.trim().to_string();                
-                info!("Hash Lido:*{}*", hash_lido2.trim());
-                
-                let split_result2 = line.split_once('*');
-                if split_result2.is_none() { // Erro lendo ?
-                    n_errors+=1;
-                    let error_msg = format!(
-                        "Error {}! Line:{} File:{} mot contain '*' char.",
-                         n_errors, n_linhas, file_path
-                    );
-                    error_results.push(error_msg.clone());
-                    error!("{}",error_msg);
-                    eprintln!("{}",error_msg);
-                    if main_args.flag_stop_on_first_error {
-                        
-                        process::exit(1);                            
-                    }                            
+                if mac::linha_do_hush(&line) {
+                    n_comentarios+=1;
                     continue;
                 }
-
-                 let (algorithm, file_to_check_path_relativo) = split_result2.unwrap();
-                 /* 
-                info!("Algorithm:*{}*", algorithm);
-                info!("File Relative Path:*{}*", file_path_relativo); */
+                // Outra linha com "#" não é entrada nem do hush: erro, não comentário
+                let lida = if line.starts_with('#') {
+                    Err("not a hush line (starts with '#')".to_string())
+                } else {
+                    parse_line(&line)
+                };
+                let entrada = match lida {
+                    Ok(entrada) => entrada,
+                    Err(e) => {
+                        n_errors+=1;
+                        let error_msg = format!(
+                            "Error {}! Line:{} File:{} {}",
+                             n_errors, n_linhas, file_path, e
+                        );
+                        error_results.push(error_msg.clone());
+                        error!("{}",error_msg);
+                        progresso::erro(&error_msg);
+                        eventos::error(file_path, None, &error_msg);
+                        if main_args.flag_stop_on_first_error {
+                            eventos::summary("error");
+                            process::exit(1);
+                        }
+                        continue;
+                    }
+                };
+                let hash_lido = entrada.valor_hash.clone();
+                info!("Hash Lido:*{}*", hash_lido);
+                let algorithm = entrada.alg_hash.as_str();
+                let file_to_check_path_relativo = entrada.path.as_str();
                 let file_to_check_path_completo_tmp =
                     gera_caminho_completo(file_to_check_path_relativo, work_dir);
                 let file_to_check_path_completo = file_to_check_path_completo_tmp.to_str().unwrap();
              /*    info!("File Path Completo:*{}*", file_path_completo_dados); */
                 /* Testa se arquivo existe! */
                 if ! Path::new(file_to_check_path_completo).is_file() {                                                        
+                    if main_args.flag_show_progress {
+                        progresso::arquivo_pronto(file_to_check_path_completo);
+                    }
                     n_errors+=1;
                     let error_msg = format!(
                         "Error {}! Line:{} File:{} does not exist!",
@@ -963,24 +1554,41 @@ pub fn check_hash(
                     );
                     error_results.push(error_msg.clone());
                     error!("{}",error_msg);
-                    eprintln!("{}",error_msg);
+                    progresso::erro(&error_msg);
+                    eventos::error(file_to_check_path_completo, None, &error_msg);
                     if main_args.flag_stop_on_first_error {                        
+                        eventos::summary("error");
                         process::exit(1);                            
                     }                            
                     continue;
                 }
-                match hash_hush(
+                let tamanho_arquivo = fs::metadata(file_to_check_path_completo)
+                    .map(|metadata| metadata.len())
+                    .unwrap_or(0);
+                eventos::file_start(file_to_check_path_completo, tamanho_arquivo, algorithm);
+                let resultado_hash = hash_hush(
                     file_to_check_path_completo,
                     algorithm,
                     main_args.buffer_size as usize,
                     main_args.n_max_concur,
                     main_args.flag_show_progress,
-
-                ) {
+                    main_args.modo_leitura,
+                );
+                if main_args.flag_show_progress {
+                    progresso::arquivo_pronto(file_to_check_path_completo);
+                }
+                match resultado_hash {
                     Ok(hash_calculado) => {
                         info!("Hash Calculated:*{}*", hash_calculado);                         
                         let hash_calc=hash_calculado.to_lowercase().trim().to_string();
                         let hash_to_check = hash_lido.to_lowercase().trim().to_string();
+                        eventos::file_done(
+                            file_to_check_path_completo,
+                            tamanho_arquivo,
+                            algorithm,
+                            &hash_calc,
+                            json!({ "expected": hash_to_check, "match": hash_to_check == hash_calc }),
+                        );
                         if hash_to_check== hash_calc {
                             info!("Hashes matched!");
                             n_acertos+=1;
@@ -993,8 +1601,10 @@ pub fn check_hash(
                             );
                             error_results.push(error_msg.clone());
                             error!("{}",error_msg);
-                            eprintln!("{}",error_msg);
+                            progresso::erro(&error_msg);
+                            eventos::error(file_to_check_path_completo, None, &error_msg);
                             if main_args.flag_stop_on_first_error {                                
+                                eventos::summary("error");
                                 process::exit(1);                            
                             }      
                             continue;
@@ -1007,9 +1617,10 @@ pub fn check_hash(
                             "Error {}: Line:{} File:{} Algorithm:{} : error{}:",
                                 n_errors, n_linhas,file_to_check_path_completo, algorithm,e );
                                 error_results.push(error_msg.clone());
-                        eprintln!("{}",error_msg);
+                        progresso::erro(&error_msg);
+                        eventos::error(file_to_check_path_completo, None, &error_msg);
                         if main_args.flag_stop_on_first_error {
-                            
+                            eventos::summary("error");
                             process::exit(1);                            
                         }   
                         continue;
@@ -1024,27 +1635,32 @@ pub fn check_hash(
                 n_errors+=1;        
                 let error_msg = format!("Error {}! Line:{} File:{} => {}",
                      n_errors, n_linhas,file_path, e );
-                eprintln!("{}",error_msg);
+                progresso::erro(&error_msg);
+                eventos::error(file_path, None, &error_msg);
                 if main_args.flag_stop_on_first_error {                    
+                    eventos::summary("error");
                     process::exit(1);                            
                 }   
                 continue;
             }
         }
     }
-    
-    
-    if n_linhas == n_acertos {
-        let sucess_msg = format!("Success! Total lines:{} matches! No errors.",n_linhas);
+    progresso::termina();
+
+    let n_entradas = n_linhas - n_comentarios;
+    if n_entradas == n_acertos {
+        let sucess_msg = format!("Success! Total lines:{} matches! No errors.",n_entradas);
         info!("{}",sucess_msg);          
         println!("{}",sucess_msg);          
+        eventos::summary("ok");
     } else{
-        let sucess_msg = format!("Results: Total lines:{}, matches:{}, errors:{}. ",n_linhas, n_acertos, n_errors);          
+        let sucess_msg = format!("Results: Total lines:{}, matches:{}, errors:{}. ",n_entradas, n_acertos, n_errors);          
         info!("{}",sucess_msg);          
         println!("{}",sucess_msg);                  
         if n_errors > 0 {
             error!("{} errors found", n_errors);                
         }        
+        eventos::summary("error");
         process::exit(1);        
     }        
     
@@ -0,0 +1,287 @@
+use log::{info, warn};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::mem;
+
+use crate::functions::{normaliza_alg, parse_line};
+
+// Classificação de um arquivo contra as listas de hashes conhecidos
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusConhecido {
+    KnownGood,
+    KnownBad,
+    Unknown,
+}
+
+// Um conjunto de hashes conhecidos, separado por algoritmo ("sha256", "hsha256-52428800").
+// Cada algoritmo guarda os digests binários num Vec ordenado: 32 bytes por entrada
+// e busca binária, o que aguenta centenas de milhões de entradas.
+#[derive(Default)]
+pub struct ConjuntoHashes {
+    por_alg: HashMap<String, Vec<[u8; 32]>>,
+}
+
+// Chave do algoritmo com o blocksize em bytes, para "50MB" e "51200KB" serem iguais
+fn chave_alg(alg_hash: &str) -> String {
+    match normaliza_alg(alg_hash) {
+        (alg, Some(blocksize)) => format!("{}-{}", alg, blocksize),
+        (alg, None) => alg,
+    }
+}
+
+fn hex_para_digest(hex: &str) -> Option<[u8; 32]> {
+    let hex = hex.trim().trim_matches('"');
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut digest = [0u8; 32];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(digest)
+}
+
+// Separa uma linha CSV do NSRL: campos separados por vírgula, que podem vir entre aspas.
+// Entre aspas a vírgula faz parte do campo ("a,b.dll") e "" é uma aspa.
+fn campos_csv(line: &str) -> Vec<String> {
+    let mut campos = Vec::new();
+    let mut campo = String::new();
+    let mut entre_aspas = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if entre_aspas && chars.peek() == Some(&'"') => {
+                campo.push('"');
+                chars.next();
+            }
+            '"' => entre_aspas = !entre_aspas,
+            ',' if !entre_aspas => campos.push(mem::take(&mut campo).trim().to_string()),
+            _ => campo.push(c),
+        }
+    }
+    campos.push(campo.trim().to_string());
+    campos
+}
+
+impl ConjuntoHashes {
+    pub fn len(&self) -> usize {
+        self.por_alg.values().map(|hashes| hashes.len()).sum()
+    }
+
+    fn insere(&mut self, alg_hash: &str, digest: [u8; 32]) {
+        self.por_alg
+            .entry(chave_alg(alg_hash))
+            .or_default()
+            .push(digest);
+    }
+
+    // Lê um arquivo de hashes. O formato é detectado pelo conteúdo:
+    //  - export NSRL RDS (texto/CSV com cabeçalho): usa a coluna SHA-256;
+    //  - manifest do hush: "<hash> ?<alg>*<caminho>";
+    //  - lista simples: um hash por linha (só SHA-256, que é o que o hush calcula).
+    // Retorna quantos hashes foram lidos.
+    pub fn carrega(&mut self, file_path: &str) -> io::Result<usize> {
+        let file = File::open(file_path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", file_path, e)))?;
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+        let mut n_lidos = 0;
+        let mut n_ignorados = 0;
+        let mut coluna_nsrl: Option<usize> = None;
+        let mut primeira_linha = true;
+
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            let linha = line.trim();
+            if linha.is_empty() || linha.starts_with('#') {
+                continue;
+            }
+
+            // Cabeçalho NSRL: "SHA-1","MD5","CRC32","FileName",... ou "sha256","sha1",...
+            if primeira_linha {
+                primeira_linha = false;
+                if linha.starts_with('"') && linha.to_uppercase().contains("SHA") {
+                    let colunas = campos_csv(linha);
+                    let coluna = colunas
+                        .iter()
+                        .position(|nome| nome.to_uppercase().replace(['-', '_'], "") == "SHA256");
+                    match coluna {
+                        Some(coluna) => {
+                            info!("{}: NSRL file, SHA-256 in column {}", file_path, coluna + 1);
+                            coluna_nsrl = Some(coluna);
+                            continue;
+                        }
+                        None => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!(
+                                    "{}: NSRL file without SHA-256 column (hush hashes with sha256). Columns: {}",
+                                    file_path,
+                                    colunas.join(",")
+                                ),
+                            ));
+                        }
+                    }
+                }
+            }
+
+            let lido = match coluna_nsrl {
+                Some(coluna) => campos_csv(linha)
+                    .get(coluna)
+                    .and_then(|hex| hex_para_digest(hex))
+                    .map(|digest| (String::from("sha256"), digest)),
+                None => match parse_line(linha) {
+                    Ok(entrada) => hex_para_digest(&entrada.valor_hash)
+                        .map(|digest| (entrada.alg_hash, digest)),
+                    Err(_) => linha
+                        .split_whitespace()
+                        .next()
+                        .and_then(hex_para_digest)
+                        .map(|digest| (String::from("sha256"), digest)),
+                },
+            };
+            match lido {
+                Some((alg_hash, digest)) => {
+                    self.insere(&alg_hash, digest);
+                    n_lidos += 1;
+                }
+                None => n_ignorados += 1,
+            }
+        }
+
+        if n_ignorados > 0 {
+            warn!(
+                "{}: {} lines ignored (not a SHA-256 / hush hash)",
+                file_path, n_ignorados
+            );
+            eprintln!(
+                "Warning: {}: {} lines ignored (not a SHA-256 / hush hash)",
+                file_path, n_ignorados
+            );
+        }
+        Ok(n_lidos)
+    }
+
+    // Ordena e tira repetidos. Tem que ser chamado depois de carregar e antes de buscar.
+    pub fn finaliza(&mut self) {
+        for hashes in self.por_alg.values_mut() {
+            hashes.sort_unstable();
+            hashes.dedup();
+            hashes.shrink_to_fit();
+        }
+    }
+
+    pub fn tem_alg(&self, alg_hash: &str) -> bool {
+        self.por_alg.contains_key(&chave_alg(alg_hash))
+    }
+
+    pub fn contem(&self, alg_hash: &str, valor_hash: &str) -> bool {
+        let digest = match hex_para_digest(valor_hash) {
+            Some(digest) => digest,
+            None => return false,
+        };
+        self.por_alg
+            .get(&chave_alg(alg_hash))
+            .map(|hashes| hashes.binary_search(&digest).is_ok())
+            .unwrap_or(false)
+    }
+}
+
+// Listas de conhecidos bons (ex: arquivos do sistema) e ruins (ex: contrabando)
+#[derive(Default)]
+pub struct ListasConhecidos {
+    pub known_good: ConjuntoHashes,
+    pub known_bad: ConjuntoHashes,
+}
+
+impl ListasConhecidos {
+    pub fn carrega(known_good_files: &[String], known_bad_files: &[String]) -> io::Result<Self> {
+        let mut listas = ListasConhecidos::default();
+        for file in known_good_files {
+            let n_lidos = listas.known_good.carrega(file)?;
+            eprintln!("Known-good hash set: {} hashes from {}", n_lidos, file);
+        }
+        for file in known_bad_files {
+            let n_lidos = listas.known_bad.carrega(file)?;
+            eprintln!("Known-bad hash set: {} hashes from {}", n_lidos, file);
+        }
+        listas.known_good.finaliza();
+        listas.known_bad.finaliza();
+        Ok(listas)
+    }
+
+    // NSRL e listas simples: só sha256. Os arquivos com hsha256 precisam do sha256 inteiro.
+    pub fn tem_sha256(&self) -> bool {
+        self.known_good.tem_alg("sha256") || self.known_bad.tem_alg("sha256")
+    }
+
+    // Um hash nas duas listas é tratado como ruim: é o que precisa ser visto
+    pub fn classifica(&self, alg_hash: &str, valor_hash: &str) -> StatusConhecido {
+        if self.known_bad.contem(alg_hash, valor_hash) {
+            StatusConhecido::KnownBad
+        } else if self.known_good.contem(alg_hash, valor_hash) {
+            StatusConhecido::KnownGood
+        } else {
+            StatusConhecido::Unknown
+        }
+    }
+
+    // Arquivo do gen: pelo hash do manifest e, se veio, pelo sha256 do arquivo inteiro.
+    // Ruim em qualquer um dos dois é ruim.
+    pub fn classifica_arquivo(
+        &self,
+        alg_hash: &str,
+        valor_hash: &str,
+        sha256_inteiro: Option<&str>,
+    ) -> StatusConhecido {
+        let status = self.classifica(alg_hash, valor_hash);
+        match sha256_inteiro {
+            Some(sha256) if status != StatusConhecido::KnownBad => {
+                match self.classifica("sha256", sha256) {
+                    StatusConhecido::Unknown => status,
+                    outro => outro,
+                }
+            }
+            _ => status,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testes::PastaTeste;
+    use std::fs;
+
+    #[test]
+    fn csv_com_virgula_entre_aspas() {
+        assert_eq!(
+            campos_csv(r#""abc","a,b.dll", 12 ,"diz ""oi""",,"#),
+            vec!["abc", "a,b.dll", "12", r#"diz "oi""#, "", ""]
+        );
+    }
+
+    #[test]
+    fn nsrl_com_virgula_no_nome() {
+        let dir = PastaTeste::nova("nsrl");
+        let sha256 = "a".repeat(64);
+        let caminho = dir.join("nsrl.csv");
+        fs::write(
+            &caminho,
+            format!(
+                "\"MD5\",\"FileName\",\"SHA-256\"\n\"{}\",\"a,b.dll\",\"{}\"\n",
+                "b".repeat(32),
+                sha256
+            ),
+        )
+        .unwrap();
+        let mut conjunto = ConjuntoHashes::default();
+        assert_eq!(conjunto.carrega(caminho.to_str().unwrap()).unwrap(), 1);
+        conjunto.finaliza();
+        assert!(conjunto.contem("sha256", &sha256));
+    }
+}
@@ -0,0 +1,176 @@
+// sha256 do arquivo inteiro na mesma leitura do hsha256 (conjuntos de hashes com sha256).
+//
+// As tarefas dos blocos leem em paralelo, mas o sha256 só anda em ordem. Cada bloco recebe uma
+// FatiaInteiro e passa para ela os mesmos pedaços que vão para o hash do bloco. O pedaço que
+// chega na vez entra no sha256 na hora (com os adiantados que vêm logo depois dele); o que chega
+// antes da vez é copiado e espera. Com MAX_ADIANTADOS copiados, quem está adiantado espera a
+// vez: o bloco mais atrasado nunca espera, então sempre há quem ande.
+// Se um bloco termina sem ler tudo (o arquivo diminuiu, erro), o sha256 fica incompleto e os
+// que esperam desistem: finaliza retorna None.
+
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::{Condvar, Mutex};
+
+const MAX_ADIANTADOS: usize = 256 * 1024 * 1024;
+
+pub struct Sha256Inteiro {
+    estado: Mutex<EstadoInteiro>,
+    andou: Condvar,
+}
+
+#[derive(Default)]
+struct EstadoInteiro {
+    hasher: Sha256,
+    // Bytes já no sha256: o próximo pedaço na vez começa aqui
+    proximo: u64,
+    adiantados: BTreeMap<u64, Vec<u8>>,
+    bytes_adiantados: usize,
+    incompleto: bool,
+}
+
+// Os pedaços de um bloco, em ordem, a partir do início do bloco
+pub struct FatiaInteiro<'a> {
+    inteiro: &'a Sha256Inteiro,
+    offset: u64,
+    fim: u64,
+}
+
+impl Sha256Inteiro {
+    pub fn novo() -> Sha256Inteiro {
+        Sha256Inteiro {
+            estado: Mutex::new(EstadoInteiro::default()),
+            andou: Condvar::new(),
+        }
+    }
+
+    pub fn fatia(&self, inicio: u64, fim: u64) -> FatiaInteiro<'_> {
+        FatiaInteiro {
+            inteiro: self,
+            offset: inicio,
+            fim,
+        }
+    }
+
+    fn atualiza(&self, offset: u64, dados: &[u8]) {
+        let mut estado = self.estado.lock().unwrap();
+        while !estado.incompleto
+            && offset != estado.proximo
+            && estado.bytes_adiantados + dados.len() > MAX_ADIANTADOS
+        {
+            estado = self.andou.wait(estado).unwrap();
+        }
+        if estado.incompleto {
+            return;
+        }
+        if offset != estado.proximo {
+            estado.bytes_adiantados += dados.len();
+            estado.adiantados.insert(offset, dados.to_vec());
+            return;
+        }
+        estado.hasher.update(dados);
+        estado.proximo += dados.len() as u64;
+        loop {
+            let proximo = estado.proximo;
+            let Some(adiantado) = estado.adiantados.remove(&proximo) else {
+                break;
+            };
+            estado.bytes_adiantados -= adiantado.len();
+            estado.hasher.update(&adiantado);
+            estado.proximo += adiantado.len() as u64;
+        }
+        self.andou.notify_all();
+    }
+
+    fn marca_incompleto(&self) {
+        let mut estado = self.estado.lock().unwrap();
+        estado.incompleto = true;
+        estado.adiantados.clear();
+        estado.bytes_adiantados = 0;
+        self.andou.notify_all();
+    }
+
+    // sha256 de tudo o que os blocos leram. None se faltou algum pedaço.
+    pub fn finaliza(&self) -> Option<String> {
+        let estado = self.estado.lock().unwrap();
+        if estado.incompleto || !estado.adiantados.is_empty() {
+            return None;
+        }
+        Some(format!("{:x}", estado.hasher.clone().finalize()))
+    }
+}
+
+impl FatiaInteiro<'_> {
+    pub fn atualiza(&mut self, dados: &[u8]) {
+        if dados.is_empty() {
+            return;
+        }
+        self.inteiro.atualiza(self.offset, dados);
+        self.offset += dados.len() as u64;
+    }
+}
+
+// Bloco que terminou antes do fim (EOF, erro, pânico): os que esperam não podem ficar presos
+impl Drop for FatiaInteiro<'_> {
+    fn drop(&mut self) {
+        if self.offset < self.fim {
+            self.inteiro.marca_incompleto();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn dados() -> Vec<u8> {
+        (0..100_000u32).map(|i| (i * 31 % 253) as u8).collect()
+    }
+
+    #[test]
+    fn blocos_fora_de_ordem() {
+        let dados = Arc::new(dados());
+        let inteiro = Arc::new(Sha256Inteiro::novo());
+        // 7 blocos em threads, de trás para frente, em pedaços de 1000 bytes
+        let fronteiras: Vec<u64> = (0..=7).map(|i| (i * dados.len() / 7) as u64).collect();
+        let threads: Vec<_> = fronteiras
+            .windows(2)
+            .rev()
+            .map(|limites| {
+                let (inicio, fim) = (limites[0], limites[1]);
+                let (dados, inteiro) = (dados.clone(), inteiro.clone());
+                thread::spawn(move || {
+                    let mut fatia = inteiro.fatia(inicio, fim);
+                    for pedaco in dados[inicio as usize..fim as usize].chunks(1000) {
+                        fatia.atualiza(pedaco);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(
+            inteiro.finaliza(),
+            Some(format!("{:x}", Sha256::digest(&dados[..])))
+        );
+    }
+
+    #[test]
+    fn bloco_curto_deixa_incompleto() {
+        let dados = dados();
+        let inteiro = Sha256Inteiro::novo();
+        {
+            let mut fatia = inteiro.fatia(50_000, 100_000);
+            fatia.atualiza(&dados[50_000..]);
+        }
+        {
+            // O arquivo diminuiu: o primeiro bloco acaba antes
+            let mut fatia = inteiro.fatia(0, 50_000);
+            fatia.atualiza(&dados[..40_000]);
+        }
+        assert_eq!(inteiro.finaliza(), None);
+    }
+}
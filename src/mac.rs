@@ -0,0 +1,417 @@
+// Manifest com chave (--mac): HMAC-SHA256 ou BLAKE3 com chave, para quem pode mudar os dados
+// não poder gerar de novo um manifest que passe no check.
+//
+// O gen escreve, antes das entradas, um cabeçalho "#hush-mac alg=<alg> scope=<escopo>".
+// Escopo "entry": depois de cada entrada, uma linha "#mac <hex>" com o MAC da linha da entrada.
+// Sobrevive a reordenar as linhas, mas não acusa entrada removida.
+// Escopo "manifest": no fim, uma linha "#hush-mac-manifest <hex>" com o MAC de todas as linhas
+// anteriores (cabeçalho incluído), na ordem, cada uma terminada com "\n".
+// Linhas começando com '#' não são entradas: check, diff, merge e as listas de conhecidos pulam.
+// A chave vem de um arquivo (--mac_key_file) ou de uma variável de ambiente (--mac_key_env).
+// Para BLAKE3, a chave de 32 bytes é derivada da chave informada (derive_key).
+
+use hmac::{Hmac, Mac};
+use once_cell::sync::OnceCell;
+use sha2::Sha256;
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+
+use crate::auditoria;
+
+const CABECALHO: &str = "#hush-mac";
+const PREFIXO_MAC_ENTRADA: &str = "#mac ";
+const PREFIXO_MAC_MANIFEST: &str = "#hush-mac-manifest ";
+const CONTEXTO_BLAKE3: &str = "hush manifest mac v1";
+const CONTEXTO_ENTRADA: &[u8] = b"hush-mac-entry\n";
+const TAMANHO_MINIMO_CHAVE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlgMac {
+    HmacSha256,
+    Blake3,
+}
+
+impl AlgMac {
+    pub fn parse(valor: &str) -> Result<AlgMac, Box<dyn Error>> {
+        match valor.to_lowercase().as_str() {
+            "hmac-sha256" => Ok(AlgMac::HmacSha256),
+            "blake3" => Ok(AlgMac::Blake3),
+            _ => Err(format!("unknown MAC: {} (use hmac-sha256 or blake3)", valor).into()),
+        }
+    }
+
+    fn nome(&self) -> &'static str {
+        match self {
+            AlgMac::HmacSha256 => "hmac-sha256",
+            AlgMac::Blake3 => "blake3",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscopoMac {
+    Entrada,
+    Manifest,
+}
+
+impl EscopoMac {
+    pub fn parse(valor: &str) -> Result<EscopoMac, Box<dyn Error>> {
+        match valor.to_lowercase().as_str() {
+            "entry" => Ok(EscopoMac::Entrada),
+            "manifest" => Ok(EscopoMac::Manifest),
+            _ => Err(format!("unknown MAC scope: {} (use entry or manifest)", valor).into()),
+        }
+    }
+
+    fn nome(&self) -> &'static str {
+        match self {
+            EscopoMac::Entrada => "entry",
+            EscopoMac::Manifest => "manifest",
+        }
+    }
+}
+
+// A chave lida, sem algoritmo: o gen usa o de --mac, o check o do cabeçalho
+pub struct ChaveMac(Vec<u8>);
+
+impl ChaveMac {
+    pub fn de_arquivo(caminho: &str) -> Result<ChaveMac, Box<dyn Error>> {
+        ChaveMac::nova(fs::read(caminho).map_err(|e| format!("{}: {}", caminho, e))?)
+    }
+
+    pub fn de_env(variavel: &str) -> Result<ChaveMac, Box<dyn Error>> {
+        let valor =
+            env::var(variavel).map_err(|_| format!("environment variable {} not set", variavel))?;
+        ChaveMac::nova(valor.into_bytes())
+    }
+
+    pub fn nova(chave: Vec<u8>) -> Result<ChaveMac, Box<dyn Error>> {
+        if chave.len() < TAMANHO_MINIMO_CHAVE {
+            return Err(
+                format!("MAC key must have at least {} bytes", TAMANHO_MINIMO_CHAVE).into(),
+            );
+        }
+        Ok(ChaveMac(chave))
+    }
+}
+
+// MAC em andamento, de um dos dois algoritmos
+enum Calculo {
+    Hmac(Hmac<Sha256>),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Calculo {
+    fn novo(alg: AlgMac, chave: &ChaveMac) -> Calculo {
+        match alg {
+            AlgMac::HmacSha256 => Calculo::hmac(&chave.0),
+            AlgMac::Blake3 => Calculo::blake3(&blake3::derive_key(CONTEXTO_BLAKE3, &chave.0)),
+        }
+    }
+
+    fn hmac(chave: &[u8]) -> Calculo {
+        Calculo::Hmac(Hmac::<Sha256>::new_from_slice(chave).expect("HMAC takes any key size"))
+    }
+
+    fn blake3(chave: &[u8; 32]) -> Calculo {
+        Calculo::Blake3(Box::new(blake3::Hasher::new_keyed(chave)))
+    }
+
+    // Linha do manifest no MAC do escopo "manifest": a linha e "\n"
+    fn update_linha(&mut self, linha: &str) {
+        self.update(linha.as_bytes());
+        self.update(b"\n");
+    }
+
+    fn update(&mut self, dados: &[u8]) {
+        match self {
+            Calculo::Hmac(mac) => mac.update(dados),
+            Calculo::Blake3(hasher) => {
+                hasher.update(dados);
+            }
+        }
+    }
+
+    fn finaliza(self) -> String {
+        match self {
+            Calculo::Hmac(mac) => hex::encode(mac.finalize().into_bytes()),
+            Calculo::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+
+    // Comparação em tempo constante
+    fn confere(self, mac_hex: &str) -> bool {
+        let Ok(mac) = hex::decode(mac_hex.trim()) else {
+            return false;
+        };
+        match self {
+            Calculo::Hmac(calculado) => calculado.verify_slice(&mac).is_ok(),
+            Calculo::Blake3(hasher) => <[u8; 32]>::try_from(mac.as_slice())
+                .is_ok_and(|mac| hasher.finalize() == blake3::Hash::from(mac)),
+        }
+    }
+}
+
+fn mac_entrada(alg: AlgMac, chave: &ChaveMac, linha: &str) -> Calculo {
+    let mut calculo = Calculo::novo(alg, chave);
+    calculo.update(CONTEXTO_ENTRADA);
+    calculo.update(linha.as_bytes());
+    calculo
+}
+
+struct ConfigMac {
+    alg: AlgMac,
+    escopo: EscopoMac,
+    chave: ChaveMac,
+}
+
+static CONFIG_GEN: OnceCell<ConfigMac> = OnceCell::new();
+static CHAVE_CHECK: OnceCell<ChaveMac> = OnceCell::new();
+
+// gen com --mac: as linhas do manifest passam a levar MAC
+pub fn configura_gen(alg: AlgMac, escopo: EscopoMac, chave: ChaveMac) {
+    let _ = CONFIG_GEN.set(ConfigMac { alg, escopo, chave });
+}
+
+// check com a chave: o manifest tem que ter MAC, e ele tem que bater
+pub fn configura_check(chave: ChaveMac) {
+    let _ = CHAVE_CHECK.set(chave);
+}
+
+// Escrita das linhas do manifest, com o cabeçalho e as linhas de MAC quando há chave.
+// O gen escreve no stdout (saida_gen); o autoteste usa a mesma escrita num Vec.
+pub struct EscritaManifest<'a, W: Write> {
+    escrita: W,
+    mac: Option<(AlgMac, EscopoMac, &'a ChaveMac)>,
+    // Escopo "manifest": o MAC de todas as linhas escritas
+    calculo: Option<Calculo>,
+    // gen: o log de auditoria guarda o sha256 do que saiu
+    auditoria: bool,
+}
+
+// A escrita do gen, no stdout, com o --mac configurado (ou sem MAC)
+pub fn saida_gen() -> io::Result<EscritaManifest<'static, io::Stdout>> {
+    let mac = CONFIG_GEN
+        .get()
+        .map(|config| (config.alg, config.escopo, &config.chave));
+    let mut saida = EscritaManifest::nova(io::stdout(), mac)?;
+    saida.auditoria = true;
+    Ok(saida)
+}
+
+impl<'a, W: Write> EscritaManifest<'a, W> {
+    // Com MAC, já escreve o cabeçalho, antes da primeira entrada
+    pub fn nova(
+        escrita: W,
+        mac: Option<(AlgMac, EscopoMac, &'a ChaveMac)>,
+    ) -> io::Result<EscritaManifest<'a, W>> {
+        let mut saida = EscritaManifest {
+            escrita,
+            mac,
+            calculo: None,
+            auditoria: false,
+        };
+        if let Some((alg, escopo, chave)) = mac {
+            if escopo == EscopoMac::Manifest {
+                saida.calculo = Some(Calculo::novo(alg, chave));
+            }
+            saida.escreve(&cabecalho(alg, escopo))?;
+        }
+        Ok(saida)
+    }
+
+    // Uma entrada do manifest, com o MAC dela no escopo "entry"
+    pub fn linha(&mut self, linha: &str) -> io::Result<()> {
+        self.escreve(linha)?;
+        if let Some((alg, EscopoMac::Entrada, chave)) = self.mac {
+            let mac = mac_entrada(alg, chave, linha).finaliza();
+            self.escreve(&format!("{}{}", PREFIXO_MAC_ENTRADA, mac))?;
+        }
+        Ok(())
+    }
+
+    // Linha final com o MAC do manifest todo, no escopo "manifest"
+    pub fn termina(mut self) -> io::Result<W> {
+        if let Some(calculo) = self.calculo.take() {
+            let linha = format!("{}{}", PREFIXO_MAC_MANIFEST, calculo.finaliza());
+            self.escreve(&linha)?;
+        }
+        self.escrita.flush()?;
+        Ok(self.escrita)
+    }
+
+    fn escreve(&mut self, linha: &str) -> io::Result<()> {
+        if let Some(calculo) = self.calculo.as_mut() {
+            calculo.update_linha(linha);
+        }
+        writeln!(self.escrita, "{}", linha)?;
+        if self.auditoria {
+            auditoria::linha_manifest(linha);
+        }
+        Ok(())
+    }
+}
+
+fn cabecalho(alg: AlgMac, escopo: EscopoMac) -> String {
+    format!("{} alg={} scope={}", CABECALHO, alg.nome(), escopo.nome())
+}
+
+// Linhas com "#" que o hush escreve: o cabeçalho e as de MAC. Outra linha com "#" não é do hush.
+pub fn linha_do_hush(linha: &str) -> bool {
+    let marca = linha.split_whitespace().next().unwrap_or("");
+    marca == CABECALHO
+        || marca == PREFIXO_MAC_ENTRADA.trim_end()
+        || marca == PREFIXO_MAC_MANIFEST.trim_end()
+}
+
+// Confere o MAC do manifest antes do check ler os dados. Retorna os problemas achados.
+// Sem chave no check, só avisa se o manifest tem MAC.
+pub fn confere_manifest(file_path: &str) -> io::Result<Vec<String>> {
+    confere_manifest_com(file_path, CHAVE_CHECK.get())
+}
+
+fn confere_manifest_com(file_path: &str, chave: Option<&ChaveMac>) -> io::Result<Vec<String>> {
+    let reader = BufReader::new(File::open(file_path)?);
+    let linhas: Vec<String> = reader.lines().collect::<io::Result<_>>()?;
+    let cabecalho = linhas.iter().position(|linha| linha.starts_with(CABECALHO));
+
+    let Some(chave) = chave else {
+        if cabecalho.is_some() {
+            eprintln!(
+                "Warning: {} has a MAC, not verified. Use --mac_key_file or --mac_key_env",
+                file_path
+            );
+        }
+        return Ok(Vec::new());
+    };
+    let Some(n_cabecalho) = cabecalho else {
+        return Ok(vec![format!(
+            "{}: no MAC header ({}), manifest not keyed",
+            file_path, CABECALHO
+        )]);
+    };
+    let (alg, escopo) = match le_cabecalho(&linhas[n_cabecalho]) {
+        Ok(cabecalho) => cabecalho,
+        Err(e) => {
+            return Ok(vec![format!(
+                "{}: line {}: {}",
+                file_path,
+                n_cabecalho + 1,
+                e
+            )])
+        }
+    };
+    if linhas[..n_cabecalho]
+        .iter()
+        .any(|linha| !linha.starts_with('#') && !linha.trim().is_empty())
+    {
+        return Ok(vec![format!(
+            "{}: entries before the MAC header",
+            file_path
+        )]);
+    }
+
+    let mut problemas = Vec::new();
+    match escopo {
+        EscopoMac::Manifest => {
+            let mut calculo = Calculo::novo(alg, chave);
+            let mut mac_final = None;
+            for (n_linha, linha) in linhas.iter().enumerate().skip(n_cabecalho) {
+                if let Some(mac) = linha.strip_prefix(PREFIXO_MAC_MANIFEST) {
+                    if n_linha + 1 != linhas.len() {
+                        problemas.push(format!("{}: lines after the manifest MAC", file_path));
+                    }
+                    mac_final = Some(mac);
+                    break;
+                }
+                calculo.update_linha(linha);
+            }
+            match mac_final {
+                None => problemas.push(format!("{}: manifest MAC missing", file_path)),
+                Some(mac) if !calculo.confere(mac) => problemas.push(format!(
+                    "{}: manifest MAC does not verify (changed, or wrong key)",
+                    file_path
+                )),
+                Some(_) => {}
+            }
+        }
+        EscopoMac::Entrada => {
+            let mut n_linha = n_cabecalho + 1;
+            while n_linha < linhas.len() {
+                let linha = &linhas[n_linha];
+                if linha.starts_with('#') || linha.trim().is_empty() {
+                    if linha.starts_with(PREFIXO_MAC_ENTRADA) {
+                        problemas.push(format!(
+                            "{}: line {}: MAC without entry",
+                            file_path,
+                            n_linha + 1
+                        ));
+                    }
+                    n_linha += 1;
+                    continue;
+                }
+                let mac = linhas
+                    .get(n_linha + 1)
+                    .and_then(|proxima| proxima.strip_prefix(PREFIXO_MAC_ENTRADA));
+                match mac {
+                    None => problemas.push(format!(
+                        "{}: line {}: entry without MAC: {}",
+                        file_path,
+                        n_linha + 1,
+                        linha
+                    )),
+                    Some(mac) if !mac_entrada(alg, chave, linha).confere(mac) => {
+                        problemas.push(format!(
+                            "{}: line {}: entry MAC does not verify: {}",
+                            file_path,
+                            n_linha + 1,
+                            linha
+                        ))
+                    }
+                    Some(_) => {}
+                }
+                n_linha += if mac.is_some() { 2 } else { 1 };
+            }
+        }
+    }
+    Ok(problemas)
+}
+
+// "#hush-mac alg=<alg> scope=<escopo>"
+fn le_cabecalho(linha: &str) -> Result<(AlgMac, EscopoMac), Box<dyn Error>> {
+    let mut alg = None;
+    let mut escopo = None;
+    for campo in linha[CABECALHO.len()..].split_whitespace() {
+        match campo.split_once('=') {
+            Some(("alg", valor)) => alg = Some(AlgMac::parse(valor)?),
+            Some(("scope", valor)) => escopo = Some(EscopoMac::parse(valor)?),
+            _ => {}
+        }
+    }
+    match (alg, escopo) {
+        (Some(alg), Some(escopo)) => Ok((alg, escopo)),
+        _ => Err(format!("invalid MAC header: {}", linha).into()),
+    }
+}
+
+// Autoteste: MAC com a chave como veio, sem o tamanho mínimo e sem a derivação do BLAKE3 (chave
+// de 32 bytes), para os vetores da RFC 4231 e do BLAKE3 passarem pelo mesmo cálculo do gen.
+pub fn mac_bruto(alg: AlgMac, chave: &[u8], dados: &[u8]) -> Result<String, Box<dyn Error>> {
+    let mut calculo = match alg {
+        AlgMac::HmacSha256 => Calculo::hmac(chave),
+        AlgMac::Blake3 => Calculo::blake3(
+            chave
+                .try_into()
+                .map_err(|_| "BLAKE3 key must have 32 bytes")?,
+        ),
+    };
+    calculo.update(dados);
+    Ok(calculo.finaliza())
+}
+
+// Autoteste: o check do MAC de um manifest, com a chave dada
+pub fn confere_com_chave(file_path: &str, chave: &ChaveMac) -> io::Result<Vec<String>> {
+    confere_manifest_com(file_path, Some(chave))
+}
@@ -17,14 +17,39 @@ use std::thread;
 use std::time::Instant;
 use std::time::SystemTime;
 
+mod armazena;
+mod assinatura;
+mod auditoria;
+mod autoteste;
+mod bench;
+mod cache;
+mod carimbo;
+mod caso;
+mod construcao;
+mod controle;
+mod disco;
+mod dupes;
+mod eventos;
 mod functions;
+mod hashset;
+mod inteiro;
+mod mac;
+mod manifest;
+mod paridade;
+mod pool;
+mod progresso;
+mod relatorio;
+mod split;
+#[cfg(test)]
+mod testes;
+mod uring;
 
 pub static LOG_ENABLED: OnceCell<bool> = OnceCell::new();
 
 fn print_usage(main_args: functions::Argumentos) {
     eprintln!("{} - hash tool for rush situations", main_args.name);
     eprintln!("\nusage: {} <command> <path> <options>?", main_args.name);
-    eprintln!("\nCommands:\n    'gen': generate hashes from path. If a folder, its recursive. Glob match pattern can be used.\n    'check': check hashes from a file using a work_dir as base\n");
+    eprintln!("\nCommands:\n    'gen': generate hashes from path. If a folder, its recursive. Glob match pattern can be used.\n    'check': check hashes from a file using a work_dir as base\n    'diff': compare two hash files, without reading the data\n    'merge': join hash files of subtrees in one hash file\n    'extract': make a hash file with entries of a subdir\n    'dupes': show duplicate files, from a hash file or from a path\n    'bench': timed trials over a sample of the data with different settings, and the recommended settings\n    'split': split a big file in parts of blocksize, with a split manifest\n    'rebuild': rebuild a file from its parts, checking each part and the result with the split manifest\n    'keygen': make an Ed25519 key pair (OpenSSH format) to sign hash files\n    'sign': sign a hash file with an Ed25519 private key (detached signature)\n    'verify-sig': verify the signature of a hash file with trusted public keys\n    'timestamp': get an RFC 3161 timestamp of a hash file from a TSA\n    'audit': verify the chain of an audit log (audit verify <audit_log>)\n    'report': examiner report (text or html) of a hash file, with case data, audit log and check results\n    'selftest': known-answer tests of the hash algorithms and of the hush blocks on this machine\n");
 
     eprintln!(
         "Simple use:\n   '{} gen <input_path> <options>'",
@@ -34,10 +59,46 @@ fn print_usage(main_args: functions::Argumentos) {
         "   '{} check <file_hashes_path> <work_dir_base> <options>'",
         main_args.name
     );
+    eprintln!(
+        "   '{} diff <file_hashes_a> <file_hashes_b>'",
+        main_args.name
+    );
+    eprintln!(
+        "   '{} merge <file_hashes_1> <sub_dir_1> [<file_hashes_2> <sub_dir_2> ...]'",
+        main_args.name
+    );
+    eprintln!("   '{} extract <file_hashes> <sub_dir>'", main_args.name);
+    eprintln!(
+        "   '{} dupes <file_hashes_path> <work_dir_base>?' or '{} dupes <input_path> <options>'",
+        main_args.name, main_args.name
+    );
+    eprintln!(
+        "   '{} bench <input_path> <options>?' (--rounds N, default 1; --sample Value, default {})",
+        main_args.name,
+        bench::AMOSTRA_BENCH
+    );
+    eprintln!(
+        "   '{} split <file_path> <dest_folder>' and '{} rebuild <file_path> <parts_folder> <--manifest file>?'",
+        main_args.name, main_args.name
+    );
+    eprintln!(
+        "   '{} keygen <private_key>', '{} sign <file_hashes> <private_key>' and '{} verify-sig <file_hashes> <trusted_keys>'",
+        main_args.name, main_args.name, main_args.name
+    );
+    eprintln!(
+        "   '{} timestamp <file_hashes> <--tsa_url URL>? <--tsa_cert File>?'",
+        main_args.name
+    );
+    eprintln!(
+        "   '{} report <file_hashes> <--audit_log File>? <--check_events File>? <--format text|html>?'",
+        main_args.name
+    );
+    eprintln!("   '{} selftest'", main_args.name);
     eprintln!("\nOptions");
 
     eprintln!("   '--log' to print lots of boring stuff");
-    eprintln!("   '--progress' to show that something is being done while you drink coffee.");
+    eprintln!("   '--events jsonl <path|fd>' (gen, check) JSON events, one per line: walk, file and block done, errors and summary. Versioned (field v).");
+    eprintln!("   '--progress' (gen, check, dupes) percent, MB/s, ETA, current big file and errors. A plain line every 10s when stderr is not a terminal.");
     eprintln!(
         "   '--stop' Stop everything if some error. By default, don´t stop. (Make it in your way!) "
     );
@@ -55,8 +116,49 @@ fn print_usage(main_args: functions::Argumentos) {
     );
     eprintln!("   '--n_max_concur Value' to change how maximum number of concurrent access to each file, in pool of slaves. Default {}",  main_args.n_max_concur
     );
+    eprintln!("   '--n_threads Value' threads of the hash pool shared by all files (the only ones that read and hash). Default: the bigger of n_workers and n_max_concur.");
+    eprintln!(
+        "   '--max_in_flight Value' limit of read buffer bytes in use at the same time, for all files. Default {}. Use KB, MB, GB.",
+        pool::MAX_IN_FLIGHT_PADRAO
+    );
+    eprintln!(
+        "   '--hash_alg Value' (gen, hsha256, split, dupes) hush of files: hsha256 (v1, default: sha256 up to the blocksize, hsha256 above; sha256 and hsha256v1 are the same) or hsha256v2 (all files, raw block digests with size and block count). Check verifies both."
+    );
+    eprintln!("   '--known_good File' (gen) hash set of known good files: NSRL RDS (SHA-256 column), hush hash file or one hash per line. Can be repeated.");
+    eprintln!("   '--known_bad File' (gen) hash set of known bad files, same formats. Can be repeated. Found: exit code 2.");
+    eprintln!("   '--omit_known_good' (gen) don´t write known good files in the output.");
+    eprintln!("   '--trusted_keys File' (check) Ed25519 public keys (OpenSSH). The hash file must have a good signature by one of them: if not, exit code 3 and no file is read.");
+    eprintln!(
+        "   '--sig File' (sign, verify-sig, check) signature file. Default <file_hashes>.sig."
+    );
+    eprintln!("   '--comment Text' (keygen) comment of the new key. Default hush.");
+    eprintln!("   '--tsa_url URL' (timestamp) RFC 3161 TSA, http or https. Default: environment variable HUSH_TSA_URL.");
+    eprintln!("   '--tsa_cert File' (timestamp, check) certificates (PEM) of trusted TSAs or of their issuers. In check, the hash file must have a good timestamp: if not, exit code 3 and no file is read.");
+    eprintln!("   '--tsr File' (timestamp, check) timestamp file. Default <file_hashes>.tsr.");
+    eprintln!("   '--audit_log File' (gen, check) append an entry of the run to this audit log: user, host, time, arguments, root, hash file digest, totals and result, chained by the hash of the entry before.");
+    eprintln!("   '--case_file File' (gen, check, report) case data, 'key: value' lines: case, examiner, evidence, agency, notes. Goes to the audit log and to the report.");
+    eprintln!("   '--case Text', '--examiner Text', '--evidence Text', '--agency Text', '--notes Text' (gen, check, report) case data, over the ones of --case_file.");
+    eprintln!("   '--check_events File' (report) events of a check (--events jsonl File): summary and failures.");
+    eprintln!("   '--format Value' (report) text or html. Default text.");
+    eprintln!("   '--mac Value' (gen) keyed manifest: hmac-sha256 or blake3. Needs --mac_key_file or --mac_key_env.");
+    eprintln!("   '--mac_scope Value' (gen) entry (a MAC after each line) or manifest (one MAC for the whole file). Default manifest.");
+    eprintln!("   '--mac_key_file File' / '--mac_key_env VAR' (gen, check) key of the MAC, at least 16 bytes. In check, the manifest must have a valid MAC: if not, exit code 3 and no file is read.");
+    eprintln!("   '--mmap' to hash blocks straight from the file mapped in memory. Falls back to normal read when the file can´t be mapped. Only for files nobody is writing to: a file that shrinks while it is hashed kills hush (SIGBUS).");
+    eprintln!("   '--io_uring' (Linux) to read blocks of all files through one shared io_uring ring. Falls back to normal read if io_uring is not available.");
+    eprintln!("   '--direct' (Linux) to read with O_DIRECT, without filling the page cache of the machine.");
+    eprintln!("   '--disk_sched Value' (gen) auto, ssd or hdd. Default auto: files on spinning disks (Linux sysfs) are read by one sequential reader per disk, others in parallel. ssd: all in parallel. hdd: one sequential reader per device.");
+    eprintln!("   '--fadvise' (Linux) to read normally, but drop each block from the page cache once it is hashed.");
+    eprintln!("   '--max_rate Value' limit of reading speed for all files together. Ex: 200MB/s. Default: no limit.");
+    eprintln!("   '--nice Value' (Linux) CPU niceness of hush, from -20 to 19.");
+    eprintln!(
+        "   '--ioprio Value' (Linux) I/O priority class: idle, be[:0-7] or rt[:0-7]. Ex: be:7"
+    );
+    eprintln!("   '--control File' file read every second while hush runs, to change max_rate, nice and ioprio. Ex: a line 'max_rate 50MB/s'.");
+    eprintln!("   '--auto' in gen, a short bench over a sample of the data before the real run, to choose buffersize, threads, read mode and disk scheduling. Blocksize is not changed.");
     eprintln!(
-        "   '--hash_alg Value' to change hash function to use. By default and supported: sha256 "
+        "   '--sample Value' data read in each round of bench and gen --auto. Default {} for bench, {} for --auto.",
+        bench::AMOSTRA_BENCH,
+        bench::AMOSTRA_AUTO
     );
     eprintln!(
         "\n**IMPORTANT**. In check, the blocksize information necessary to check data. !! SAVE ALL DATA GENERATED !!"
@@ -126,6 +228,177 @@ fn resolve_path(caminho_pai: &str) -> PathBuf {
     }
 }
 
+// Valores de uma opção que pode ser usada mais de uma vez. Ex: --known_bad a.txt --known_bad b.txt
+fn valores_opcao(args: &[String], opcao: &str) -> Result<Vec<String>, String> {
+    let mut valores = Vec::new();
+    for (index, arg) in args.iter().enumerate() {
+        if arg == opcao {
+            match args.get(index + 1) {
+                Some(valor) if !valor.starts_with("--") => valores.push(valor.to_string()),
+                _ => return Err(format!("{} provided without a value.", opcao)),
+            }
+        }
+    }
+    Ok(valores)
+}
+
+// --sample Value: bytes lidos em cada rodada do bench e do gen --auto. None (já avisado) se o valor for inválido.
+fn le_amostra(args: &[String], padrao: &str) -> Option<u64> {
+    let valor = match args.iter().position(|x| x == "--sample") {
+        Some(index) => match args.get(index + 1) {
+            Some(valor) => valor.as_str(),
+            None => {
+                eprintln!("--sample provided without a value. Ex: --sample 512MB");
+                return None;
+            }
+        },
+        None => padrao,
+    };
+    match functions::ParseSize(valor) {
+        Ok(tamanho) if tamanho > 0 => Some(tamanho),
+        _ => {
+            eprintln!("Invalid --sample value: {}. Ex: --sample 512MB", valor);
+            None
+        }
+    }
+}
+
+// Segredo das partes cifradas: --passphrase_file, --passphrase_env ou --key_file (só um)
+fn le_segredo(args: &[String]) -> Option<armazena::Segredo> {
+    let fontes = [
+        (
+            "--passphrase_file",
+            armazena::Segredo::de_frase_arquivo as fn(&str) -> _,
+        ),
+        ("--passphrase_env", armazena::Segredo::de_frase_env),
+        ("--key_file", armazena::Segredo::de_arquivo_chave),
+    ];
+    let mut segredo = None;
+    for (opcao, le) in fontes {
+        let Some(index) = args.iter().position(|x| x == opcao) else {
+            continue;
+        };
+        if segredo.is_some() {
+            eprintln!("Use only one of --passphrase_file, --passphrase_env and --key_file.");
+            process::exit(1);
+        }
+        let Some(valor) = args.get(index + 1) else {
+            eprintln!("{} provided without a value.", opcao);
+            process::exit(1);
+        };
+        match le(valor) {
+            Ok(lido) => segredo = Some(lido),
+            Err(e) => {
+                eprintln!("{}: {}", opcao, e);
+                process::exit(1);
+            }
+        }
+    }
+    segredo
+}
+
+// Chave do manifest com MAC: --mac_key_file ou --mac_key_env (só um)
+fn le_chave_mac(args: &[String]) -> Option<mac::ChaveMac> {
+    let fontes = [
+        ("--mac_key_file", mac::ChaveMac::de_arquivo as fn(&str) -> _),
+        ("--mac_key_env", mac::ChaveMac::de_env),
+    ];
+    let mut chave = None;
+    for (opcao, le) in fontes {
+        let Some(index) = args.iter().position(|x| x == opcao) else {
+            continue;
+        };
+        if chave.is_some() {
+            eprintln!("Use only one of --mac_key_file and --mac_key_env.");
+            process::exit(1);
+        }
+        let Some(valor) = args.get(index + 1) else {
+            eprintln!("{} provided without a value.", opcao);
+            process::exit(1);
+        };
+        match le(valor) {
+            Ok(lida) => chave = Some(lida),
+            Err(e) => {
+                eprintln!("{}: {}", opcao, e);
+                process::exit(1);
+            }
+        }
+    }
+    chave
+}
+
+// Frase da chave privada de keygen e sign: --passphrase_file ou --passphrase_env
+fn le_frase_chave(args: &[String]) -> Option<Vec<u8>> {
+    match le_segredo(args) {
+        Some(armazena::Segredo::Frase(frase)) => Some(frase),
+        Some(armazena::Segredo::ArquivoChave(_)) => {
+            eprintln!("--key_file is for split parts. For the private key, use --passphrase_file or --passphrase_env.");
+            process::exit(1);
+        }
+        None => None,
+    }
+}
+
+// --sig File, ou <file_hashes>.sig
+fn le_caminho_assinatura(args: &[String], manifest: &str) -> String {
+    match args.iter().position(|x| x == "--sig") {
+        Some(index) => match args.get(index + 1) {
+            Some(caminho) => caminho.to_string(),
+            None => {
+                eprintln!("--sig provided without a file.");
+                process::exit(1);
+            }
+        },
+        None => assinatura::caminho_assinatura(manifest),
+    }
+}
+
+// --tsr File, ou <file_hashes>.tsr
+fn le_caminho_carimbo(args: &[String], manifest: &str) -> String {
+    match args.iter().position(|x| x == "--tsr") {
+        Some(index) => match args.get(index + 1) {
+            Some(caminho) => caminho.to_string(),
+            None => {
+                eprintln!("--tsr provided without a file.");
+                process::exit(1);
+            }
+        },
+        None => carimbo::caminho_carimbo(manifest),
+    }
+}
+
+// --tsa_cert File: certificados confiáveis da TSA
+fn le_certificados_tsa(args: &[String]) -> Option<&str> {
+    let index = args.iter().position(|x| x == "--tsa_cert")?;
+    match args.get(index + 1) {
+        Some(caminho) => Some(caminho.as_str()),
+        None => {
+            eprintln!("--tsa_cert provided without a file.");
+            process::exit(1);
+        }
+    }
+}
+
+// --audit_log File (gen, check)
+fn abre_auditoria(
+    args: &[String],
+    main_args: &functions::Argumentos,
+    raiz: &str,
+    manifest: Option<&str>,
+) {
+    let Some(index) = args.iter().position(|x| x == "--audit_log") else {
+        return;
+    };
+    let Some(caminho) = args.get(index + 1) else {
+        eprintln!("--audit_log provided without a file.");
+        process::exit(1);
+    };
+    if let Err(e) = auditoria::abre(caminho, main_args, raiz, manifest) {
+        eprintln!("--audit_log: {}", e);
+        process::exit(1);
+    }
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Coletando os argumentos da linha de comando
     let start = Instant::now();
@@ -147,6 +420,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         in_file_filter: String::new(),
         recursive_enabled: false,
         out_file_path: PathBuf::new(),
+        known_good_files: Vec::new(),
+        known_bad_files: Vec::new(),
+        flag_omit_known_good: false,
+        modo_leitura: functions::ModoLeitura::Read,
+        agendamento_disco: disco::AgendamentoDisco::Auto,
+        n_threads: 0,
+        max_in_flight: 0,
+        construcao: construcao::Construcao::V1,
+        caso: caso::Caso::default(),
     };
 
     // Pega
@@ -159,8 +441,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     main_args.n_workers = 15;
     main_args.n_max_concur = 15;
+    main_args.max_in_flight = ParseSize(pool::MAX_IN_FLIGHT_PADRAO)?;
 
-    if args.len() < 3 {
+    // selftest é o único comando sem <path>
+    if args.len() < 3 && args.get(1).map(String::as_str) != Some("selftest") {
         print_usage(main_args);
         process::exit(0);
     }
@@ -219,10 +503,64 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    main_args.known_good_files = valores_opcao(&args, "--known_good")?;
+    main_args.known_bad_files = valores_opcao(&args, "--known_bad")?;
+    main_args.flag_omit_known_good = args.contains(&"--omit_known_good".to_string());
+
     main_args.flag_stop_on_first_error = args.contains(&"--stop".to_string());
 
+    if let Some(index) = args.iter().position(|x| x == "--hash_alg") {
+        // "sha256", o valor da ajuda antiga, é o padrão: a v1
+        match args.get(index + 1).map(|valor| match valor.as_str() {
+            "sha256" => Ok(construcao::Construcao::V1),
+            _ => construcao::Construcao::parse(valor),
+        }) {
+            Some(Ok(construcao)) => main_args.construcao = construcao,
+            Some(Err(e)) => {
+                eprintln!("--hash_alg: {}", e);
+                process::exit(1);
+            }
+            None => {
+                eprintln!("--hash_alg provided without a value. Ex: --hash_alg hsha256v2");
+                process::exit(1);
+            }
+        }
+    }
+
+    main_args.caso = match caso::Caso::de_args(&args) {
+        Ok(caso) => caso,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
     main_args.flag_show_progress = args.contains(&"--progress".to_string());
 
+    if args.contains(&"--mmap".to_string()) {
+        main_args.modo_leitura = functions::ModoLeitura::Mmap;
+    }
+    if args.contains(&"--io_uring".to_string()) {
+        main_args.modo_leitura = functions::ModoLeitura::IoUring;
+    }
+    if let Some(index) = args.iter().position(|x| x == "--disk_sched") {
+        match args
+            .get(index + 1)
+            .and_then(|valor| disco::AgendamentoDisco::from_arg(valor))
+        {
+            Some(agendamento) => main_args.agendamento_disco = agendamento,
+            None => {
+                eprintln!("--disk_sched needs a value: auto, ssd or hdd. Ex: --disk_sched hdd");
+                process::exit(1);
+            }
+        }
+    }
+    if args.contains(&"--direct".to_string()) {
+        cache::define_politica(cache::PoliticaCache::Direct);
+    } else if args.contains(&"--fadvise".to_string()) {
+        cache::define_politica(cache::PoliticaCache::Fadvise);
+    }
+
     let enable_logging = args.contains(&"--log".to_string());
     if enable_logging {
         main_args.log_enabled = true;
@@ -235,65 +573,275 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         info!("Logging is enabled in the main thread.");
     }
 
+    // Limite de leitura e prioridades, para não atrapalhar a máquina (podem mudar com --control)
+    if let Some(index) = args
+        .iter()
+        .position(|x| x == "--max_rate" || x == "--max-rate")
+    {
+        match args.get(index + 1).map(|valor| controle::parse_taxa(valor)) {
+            Some(Ok(taxa)) => controle::define_taxa(taxa),
+            _ => {
+                eprintln!(
+                    "--max_rate needs a value. Use KB, MB, GB per second. Ex: --max_rate 200MB/s"
+                );
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(index) = args.iter().position(|x| x == "--nice") {
+        match args.get(index + 1).map(|valor| valor.parse::<i32>()) {
+            Some(Ok(nice)) => {
+                if let Err(e) = controle::define_nice(nice) {
+                    eprintln!("--nice {}: {}", nice, e);
+                    process::exit(1);
+                }
+            }
+            _ => {
+                eprintln!("--nice needs a number, from -20 to 19. Ex: --nice 10");
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(index) = args.iter().position(|x| x == "--ioprio") {
+        match args
+            .get(index + 1)
+            .and_then(|valor| controle::ClasseIo::from_arg(valor))
+        {
+            Some(classe) => {
+                if let Err(e) = controle::define_ioprio(classe) {
+                    eprintln!("--ioprio {:?}: {}", classe, e);
+                    process::exit(1);
+                }
+            }
+            None => {
+                eprintln!("--ioprio needs a class: idle, be[:0-7] or rt[:0-7]. Ex: --ioprio be:7");
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(index) = args.iter().position(|x| x == "--control") {
+        match args.get(index + 1) {
+            Some(caminho) => controle::vigia_controle(caminho),
+            None => {
+                eprintln!("--control provided without a file. Ex: --control /tmp/hush.control");
+                process::exit(1);
+            }
+        }
+    }
+
+    // Pool único de hash e limite de bytes em voo, para qualquer n_workers x n_max_concur
+    main_args.n_threads = main_args.n_workers.max(main_args.n_max_concur);
+    if let Some(index) = args.iter().position(|x| x == "--n_threads") {
+        match args.get(index + 1).map(|valor| valor.parse::<u32>()) {
+            Some(Ok(parsed_value)) if parsed_value > 0 => main_args.n_threads = parsed_value,
+            _ => {
+                eprintln!("--n_threads needs a number greater than 0. Ex: --n_threads 16");
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(index) = args.iter().position(|x| x == "--max_in_flight") {
+        if let Some(size_str) = args.get(index + 1) {
+            main_args.max_in_flight = ParseSize(size_str)?;
+        } else {
+            eprintln!("--max_in_flight provided without a value. Use KB, MB, GB. Ex: --max_in_flight 512MB");
+            process::exit(1);
+        }
+    }
+    pool::define_limites(main_args.n_threads, main_args.max_in_flight);
+
+    if args[1] == "selftest" {
+        match autoteste::executa(main_args.n_max_concur) {
+            Ok(true) => process::exit(0),
+            Ok(false) => process::exit(1),
+            Err(e) => {
+                eprintln!("Selftest error: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
     // Extraindo os argumentos
     let comando = &args[1];
     let file_path = &args[2];
 
     let in_file_resovido = resolve_path(file_path);
 
-    main_args.in_file_path = in_file_resovido.canonicalize()?; // Obtém o caminho absoluto
+    // No rebuild e no keygen, o caminho é o do arquivo a criar: ainda não existe. No audit, é a ação.
+    // No check e no gen, o manifest ou o path que não abre é erro deles, que fica no log de auditoria.
+    main_args.in_file_path = if comando == "rebuild" || comando == "keygen" || comando == "audit" {
+        in_file_resovido
+    } else if comando == "check" || comando == "gen" {
+        in_file_resovido.canonicalize().unwrap_or(in_file_resovido)
+    } else {
+        in_file_resovido.canonicalize()? // Obtém o caminho absoluto
+    };
     main_args.sub_comando = comando.to_string();
     info!("Comando {}", comando);
+
+    // Eventos em JSON, um por linha, para GUIs e agendadores
+    if let Some(index) = args.iter().position(|x| x == "--events") {
+        match (args.get(index + 1), args.get(index + 2)) {
+            (Some(formato), Some(destino)) => {
+                if let Err(e) = eventos::abre(formato, destino, comando) {
+                    eprintln!("--events {} {}: {}", formato, destino, e);
+                    process::exit(1);
+                }
+            }
+            _ => {
+                eprintln!("--events needs a format and a path or fd. Ex: --events jsonl /tmp/hush.jsonl or --events jsonl 3");
+                process::exit(1);
+            }
+        }
+    }
     match comando.as_str() {
         "split" => {
-            if args.len() != 4 {
-                eprintln!("Use: {} split <file_path> <dest_folder_path>?", args[0]);
+            if args.len() < 4 || args[3].starts_with("--") {
+                eprintln!("Use: {} split <file_path> <dest_folder_path>", args[0]);
                 eprintln!("<file_path>: file to split.");
-                eprintln!("<file_path>: folder to receive splitted files.");
+                eprintln!(
+                    "<dest_folder_path>: folder to receive the parts and the split manifest."
+                );
+                eprintln!("--parity N: N parity parts per group of parts, to rebuild with up to N missing or corrupt parts.");
+                eprintln!("--parts N: split in N parts, instead of parts of --blocksize bytes.");
+                eprintln!("--prefix P: parts named P00, P01... Default: <file name>_");
+                eprintln!("--force: overwrite parts and manifest already in <dest_folder_path>.");
+                eprintln!("--compress zstd[:level]: compress the parts.");
+                eprintln!("--encrypt: encrypt the parts (AES-256-GCM) with --passphrase_file F, --passphrase_env VAR or --key_file F.");
                 process::exit(1);
             }
 
             let dir_destino = &args[3];
+            let mut opcoes = split::OpcoesSplit {
+                forca: args.iter().any(|x| x == "--force"),
+                construcao: main_args.construcao,
+                ..Default::default()
+            };
+            if let Some(index) = args.iter().position(|x| x == "--parity") {
+                match args.get(index + 1).map(|valor| valor.parse::<usize>()) {
+                    Some(Ok(valor)) => opcoes.n_paridade = valor,
+                    _ => {
+                        eprintln!("--parity provided without a valid value. Ex: --parity 2");
+                        process::exit(1);
+                    }
+                }
+            }
+            if let Some(index) = args.iter().position(|x| x == "--parts") {
+                match args.get(index + 1).map(|valor| valor.parse::<u64>()) {
+                    Some(Ok(valor)) if valor > 0 => opcoes.n_partes = Some(valor),
+                    _ => {
+                        eprintln!("--parts provided without a valid value. Ex: --parts 10");
+                        process::exit(1);
+                    }
+                }
+            }
+            if let Some(index) = args.iter().position(|x| x == "--prefix") {
+                match args.get(index + 1) {
+                    Some(prefixo) => opcoes.prefixo = Some(prefixo.clone()),
+                    None => {
+                        eprintln!("--prefix provided without a value. Ex: --prefix disk.E01.part");
+                        process::exit(1);
+                    }
+                }
+            }
+            if let Some(index) = args.iter().position(|x| x == "--compress") {
+                match args
+                    .get(index + 1)
+                    .map(|valor| armazena::parse_compressao(valor))
+                {
+                    Some(Ok(nivel)) => opcoes.nivel_zstd = Some(nivel),
+                    Some(Err(e)) => {
+                        eprintln!("--compress: {}", e);
+                        process::exit(1);
+                    }
+                    None => {
+                        eprintln!("--compress provided without a value. Ex: --compress zstd");
+                        process::exit(1);
+                    }
+                }
+            }
+            opcoes.segredo = le_segredo(&args);
+            match (args.iter().any(|x| x == "--encrypt"), &opcoes.segredo) {
+                (true, None) => {
+                    eprintln!("--encrypt needs --passphrase_file, --passphrase_env or --key_file.");
+                    process::exit(1);
+                }
+                (false, Some(_)) => {
+                    eprintln!("Passphrase or key given without --encrypt.");
+                    process::exit(1);
+                }
+                _ => {}
+            }
 
-            if let Err(e) = functions::split(
+            if let Err(e) = split::split(
                 file_path,
                 dir_destino,
                 main_args.buffer_size as usize,
                 main_args.block_size as usize,
+                &main_args.block_size_str,
+                main_args.n_max_concur,
+                &opcoes,
             ) {
                 eprintln!("Split Error: {}", e);
                 process::exit(1);
             }
         }
         "rebuild" => {
-            if args.len() != 4 {
-                eprintln!("Use: {} rebuild <file_path> <dest_folder_path>?", args[0]);
+            if args.len() < 4 || args[3].starts_with("--") {
+                eprintln!(
+                    "Use: {} rebuild <file_path> <parts_folder_path> <--manifest file>?",
+                    args[0]
+                );
                 eprintln!("<file_path>: file to rebuild.");
-                eprintln!("<file_path>: folder to read splitted files.");
+                eprintln!("<parts_folder_path>: folder with the parts and the split manifest.");
+                eprintln!("Encrypted parts: --passphrase_file F, --passphrase_env VAR or --key_file F, as in the split.");
                 process::exit(1);
             }
-            let dir_destino = &args[3];
+            let dir_partes = &args[3];
+            let manifest = match args.iter().position(|x| x == "--manifest") {
+                Some(index) => match args.get(index + 1) {
+                    Some(manifest) => Some(manifest.as_str()),
+                    None => {
+                        eprintln!("--manifest provided without a file.");
+                        process::exit(1);
+                    }
+                },
+                None => None,
+            };
 
-            if let Err(e) = functions::rebuild(
-                dir_destino,
+            if let Err(e) = split::rebuild(
                 file_path,
+                dir_partes,
+                manifest,
+                le_segredo(&args).as_ref(),
                 main_args.buffer_size as usize,
-                main_args.block_size as usize,
+                main_args.n_max_concur,
+                main_args.modo_leitura,
             ) {
                 eprintln!("Rebuild error: {}", e);
                 process::exit(1);
             }
         }
         "hsha256" => {
-            match functions::hash_hsha256(
+            if main_args.flag_show_progress {
+                let files = [file_path.to_string()];
+                progresso::inicia(1, progresso::soma_tamanhos(&files));
+            }
+            let resultado = functions::hash_hsha256(
                 file_path,
                 main_args.buffer_size as usize,
                 main_args.block_size as usize,
                 main_args.n_max_concur,
                 main_args.flag_show_progress,
-            ) {
+                main_args.modo_leitura,
+                main_args.construcao,
+                None,
+            );
+            progresso::arquivo_pronto(file_path);
+            progresso::termina();
+            match resultado {
                 Ok(hash_final) => {
-                    let hash_alg = "hsha256";
+                    let hash_alg = main_args.construcao.nome();
 
                     let safe_hash_final = String::from_utf8_lossy(hash_final.as_bytes());
                     let safe_hash_alg = String::from_utf8_lossy(hash_alg.as_bytes());
@@ -317,8 +865,92 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 eprintln!("<file_path>: file_path to gen (file_path can be aglob pattern!).");
                 process::exit(1);
             }
+            let chave_mac = le_chave_mac(&args);
+            match args.iter().position(|x| x == "--mac") {
+                Some(index) => {
+                    let alg = match args.get(index + 1).map(|valor| mac::AlgMac::parse(valor)) {
+                        Some(Ok(alg)) => alg,
+                        Some(Err(e)) => {
+                            eprintln!("--mac: {}", e);
+                            process::exit(1);
+                        }
+                        None => {
+                            eprintln!("--mac provided without a value. Ex: --mac hmac-sha256");
+                            process::exit(1);
+                        }
+                    };
+                    let escopo = match args.iter().position(|x| x == "--mac_scope") {
+                        Some(index) => {
+                            match args
+                                .get(index + 1)
+                                .map(|valor| mac::EscopoMac::parse(valor))
+                            {
+                                Some(Ok(escopo)) => escopo,
+                                Some(Err(e)) => {
+                                    eprintln!("--mac_scope: {}", e);
+                                    process::exit(1);
+                                }
+                                None => {
+                                    eprintln!("--mac_scope provided without a value. Ex: --mac_scope entry");
+                                    process::exit(1);
+                                }
+                            }
+                        }
+                        None => mac::EscopoMac::Manifest,
+                    };
+                    let Some(chave) = chave_mac else {
+                        eprintln!("--mac needs a key: --mac_key_file or --mac_key_env.");
+                        process::exit(1);
+                    };
+                    mac::configura_gen(alg, escopo, chave);
+                }
+                None if chave_mac.is_some() || args.iter().any(|x| x == "--mac_scope") => {
+                    eprintln!("--mac_key_file, --mac_key_env and --mac_scope are used with --mac.");
+                    process::exit(1);
+                }
+                None => {}
+            }
             debug!("search_files: {} ", file_path);
-            let results = search_files(file_path).unwrap();
+            eventos::walk_start(file_path);
+            let results = match search_files(file_path) {
+                Ok(results) => results,
+                Err(e) => {
+                    // Sem hash nenhum, a execução ainda fica no log de auditoria
+                    eprintln!("gen error: {}: {}", file_path, e);
+                    abre_auditoria(
+                        &args,
+                        &main_args,
+                        &main_args.in_file_path.display().to_string(),
+                        None,
+                    );
+                    eventos::error(file_path, None, &e.to_string());
+                    eventos::summary("error");
+                    process::exit(1);
+                }
+            };
+            let bytes = if eventos::ativo() {
+                progresso::soma_tamanhos(&results)
+            } else {
+                0
+            };
+            eventos::walk_finish(file_path, results.len() as u64, bytes);
+            let mut main_args = main_args;
+            if args.iter().any(|x| x == "--auto") {
+                let tamanho_amostra = match le_amostra(&args, bench::AMOSTRA_AUTO) {
+                    Some(tamanho) => tamanho,
+                    None => process::exit(1),
+                };
+                if let Err(e) = bench::auto_ajuste(&mut main_args, &results, tamanho_amostra) {
+                    eprintln!("Auto: probe failed, using the given settings: {}", e);
+                }
+            }
+            // Com os Argumentos finais (depois do --auto)
+            abre_auditoria(
+                &args,
+                &main_args,
+                &main_args.in_file_path.display().to_string(),
+                None,
+            );
             process_files(main_args, results);
         }
 
@@ -337,9 +969,388 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             if work_dir.starts_with("--") {
                 eprintln!("<dir_work> not informed.  set work dir where files are.");
             }
-
+            let raiz = resolve_path(work_dir);
+            let raiz = raiz.canonicalize().unwrap_or(raiz).display().to_string();
+            let manifest = main_args.in_file_path.display().to_string();
+            abre_auditoria(&args, &main_args, &raiz, Some(&manifest));
+            if let Some(chave) = le_chave_mac(&args) {
+                mac::configura_check(chave);
+            }
+            // Assinatura, antes do MAC e dos dados
+            if let Some(index) = args.iter().position(|x| x == "--trusted_keys") {
+                let Some(chaves) = args.get(index + 1) else {
+                    eprintln!("--trusted_keys provided without a file.");
+                    process::exit(1);
+                };
+                let caminho_sig = le_caminho_assinatura(&args, file_path);
+                match assinatura::confere(file_path, &caminho_sig, chaves) {
+                    Ok(chave) => eprintln!("Good signature by {}", chave),
+                    Err(e) => {
+                        let error_msg = format!("Signature Error! {}", e);
+                        eprintln!("{}", error_msg);
+                        eprintln!("Hash file signature does not verify. No file was checked.");
+                        eventos::error(file_path, None, &error_msg);
+                        eventos::summary("signature_failed");
+                        process::exit(3);
+                    }
+                }
+            }
+            // Carimbo de tempo, antes do MAC e dos dados
+            let caminho_tsr = le_caminho_carimbo(&args, file_path);
+            match le_certificados_tsa(&args) {
+                Some(certificados) => {
+                    match carimbo::confere(file_path, &caminho_tsr, Some(certificados)) {
+                        Ok(carimbo) => eprintln!(
+                            "Good timestamp: {} by {}, serial {}",
+                            carimbo.hora, carimbo.tsa, carimbo.serial
+                        ),
+                        Err(e) => {
+                            let error_msg = format!("Timestamp Error! {}", e);
+                            eprintln!("{}", error_msg);
+                            eprintln!("Hash file timestamp does not verify. No file was checked.");
+                            eventos::error(file_path, None, &error_msg);
+                            eventos::summary("timestamp_failed");
+                            process::exit(3);
+                        }
+                    }
+                }
+                None if carimbo::existe(&caminho_tsr) => eprintln!(
+                    "Warning: {} has a timestamp ({}), not verified. Use --tsa_cert",
+                    file_path, caminho_tsr
+                ),
+                None => {}
+            }
             if let Err(e) = functions::check_hash(main_args, file_path, work_dir) {
-                eprintln!("check error: {}", e);
+                eprintln!("check error: {}: {}", file_path, e);
+                eventos::error(file_path, None, &e.to_string());
+                eventos::summary("error");
+                process::exit(1);
+            }
+        }
+
+        "diff" => {
+            if args.len() < 4 || args[3].starts_with("--") {
+                eprintln!("Use: {} diff <file_hashes_a> <file_hashes_b>", args[0]);
+                eprintln!("<file_hashes_a>: manifest used as reference (ex: from acquisition).");
+                eprintln!("<file_hashes_b>: manifest to compare (ex: from lab intake).");
+                process::exit(1);
+            }
+            match manifest::diff_manifests(file_path, &args[3]) {
+                Ok(false) => {}
+                Ok(true) => process::exit(1),
+                Err(e) => {
+                    eprintln!("diff error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        "merge" => {
+            let posicionais: Vec<&String> = args[2..]
+                .iter()
+                .take_while(|arg| !arg.starts_with("--"))
+                .collect();
+            if posicionais.is_empty() || !posicionais.len().is_multiple_of(2) {
+                eprintln!(
+                    "Use: {} merge <file_hashes_1> <sub_dir_1> [<file_hashes_2> <sub_dir_2> ...]",
+                    args[0]
+                );
+                eprintln!("<file_hashes_N>: hash file generated in a subtree.");
+                eprintln!("<sub_dir_N>: path of that subtree, relative to the common parent.");
+                process::exit(1);
+            }
+            let manifests: Vec<(String, String)> = posicionais
+                .chunks(2)
+                .map(|par| (par[0].to_string(), par[1].to_string()))
+                .collect();
+
+            if let Err(e) = manifest::merge_manifests(&manifests) {
+                eprintln!("merge error: {}", e);
+                process::exit(1);
+            }
+        }
+
+        "extract" => {
+            if args.len() < 4 || args[3].starts_with("--") {
+                eprintln!("Use: {} extract <file_hashes> <sub_dir>", args[0]);
+                eprintln!("<file_hashes>: hash file to read.");
+                eprintln!("<sub_dir>: subdir to extract. Paths in new hash file start at it.");
+                process::exit(1);
+            }
+            if let Err(e) = manifest::extract_manifest(file_path, &args[3]) {
+                eprintln!("extract error: {}", e);
+                process::exit(1);
+            }
+        }
+
+        "keygen" => {
+            if args.len() < 3 || file_path.starts_with("--") {
+                eprintln!("Use: {} keygen <private_key> <--comment Text>?", args[0]);
+                eprintln!("<private_key>: file of the new private key. The public key goes to <private_key>.pub.");
+                eprintln!(
+                    "To encrypt the private key: --passphrase_file F or --passphrase_env VAR."
+                );
+                process::exit(1);
+            }
+            let comentario = match args.iter().position(|x| x == "--comment") {
+                Some(index) => match args.get(index + 1) {
+                    Some(comentario) => comentario.to_string(),
+                    None => {
+                        eprintln!("--comment provided without a value.");
+                        process::exit(1);
+                    }
+                },
+                None => String::from("hush"),
+            };
+            match assinatura::gera_chaves(file_path, le_frase_chave(&args).as_deref(), &comentario)
+            {
+                Ok(digital) => {
+                    eprintln!("Private key: {}", file_path);
+                    eprintln!("Public key: {}.pub", file_path);
+                    println!("{}", digital);
+                }
+                Err(e) => {
+                    eprintln!("Keygen error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        "sign" => {
+            if args.len() < 4 || args[3].starts_with("--") {
+                eprintln!(
+                    "Use: {} sign <file_hashes> <private_key> <--sig File>?",
+                    args[0]
+                );
+                eprintln!("<file_hashes>: hash file to sign.");
+                eprintln!("<private_key>: Ed25519 private key, OpenSSH format. Encrypted: --passphrase_file F or --passphrase_env VAR.");
+                eprintln!("The signature goes to <file_hashes>.sig, or to --sig File.");
+                process::exit(1);
+            }
+            let caminho_sig = le_caminho_assinatura(&args, file_path);
+            match assinatura::assina(
+                file_path,
+                &args[3],
+                le_frase_chave(&args).as_deref(),
+                &caminho_sig,
+            ) {
+                Ok(digital) => eprintln!("Signed {} with {}: {}", file_path, digital, caminho_sig),
+                Err(e) => {
+                    eprintln!("Sign error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        "verify-sig" => {
+            if args.len() < 4 || args[3].starts_with("--") {
+                eprintln!(
+                    "Use: {} verify-sig <file_hashes> <trusted_keys> <--sig File>?",
+                    args[0]
+                );
+                eprintln!("<file_hashes>: signed hash file.");
+                eprintln!("<trusted_keys>: Ed25519 public keys, one per line (.pub, authorized_keys or allowed_signers).");
+                process::exit(1);
+            }
+            let caminho_sig = le_caminho_assinatura(&args, file_path);
+            match assinatura::confere(file_path, &caminho_sig, &args[3]) {
+                Ok(chave) => println!("Good signature by {}", chave),
+                Err(e) => {
+                    eprintln!("Signature Error! {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        "timestamp" => {
+            if args.len() < 3 || file_path.starts_with("--") {
+                eprintln!(
+                    "Use: {} timestamp <file_hashes> <--tsa_url URL>? <--tsa_cert File>? <--tsr File>?",
+                    args[0]
+                );
+                eprintln!("<file_hashes>: hash file to timestamp.");
+                eprintln!(
+                    "--tsa_url: RFC 3161 TSA (http or https). Default: environment variable {}.",
+                    carimbo::VARIAVEL_URL
+                );
+                eprintln!("--tsa_cert: certificates (PEM) the TSA must be, or be issued by.");
+                eprintln!("The timestamp goes to <file_hashes>.tsr, or to --tsr File.");
+                process::exit(1);
+            }
+            let url = match args.iter().position(|x| x == "--tsa_url") {
+                Some(index) => match args.get(index + 1) {
+                    Some(url) => url.to_string(),
+                    None => {
+                        eprintln!("--tsa_url provided without a value.");
+                        process::exit(1);
+                    }
+                },
+                None => match env::var(carimbo::VARIAVEL_URL) {
+                    Ok(url) => url,
+                    Err(_) => {
+                        eprintln!(
+                            "No TSA: use --tsa_url URL or the environment variable {}.",
+                            carimbo::VARIAVEL_URL
+                        );
+                        process::exit(1);
+                    }
+                },
+            };
+            let caminho_tsr = le_caminho_carimbo(&args, file_path);
+            if carimbo::existe(&caminho_tsr) && !args.iter().any(|x| x == "--force") {
+                eprintln!("{} already exists. Use --force to replace it.", caminho_tsr);
+                process::exit(1);
+            }
+            match carimbo::pede(file_path, &url, &caminho_tsr, le_certificados_tsa(&args)) {
+                Ok(carimbo) => {
+                    println!(
+                        "Timestamp: {} by {}, serial {}",
+                        carimbo.hora, carimbo.tsa, carimbo.serial
+                    );
+                    eprintln!("Saved in {}", caminho_tsr);
+                    if !carimbo.confiavel {
+                        eprintln!("Warning: TSA certificate not verified. Use --tsa_cert");
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Timestamp error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        "audit" => {
+            if file_path != "verify" || args.len() < 4 || args[3].starts_with("--") {
+                eprintln!("Use: {} audit verify <audit_log> <--head Hash>?", args[0]);
+                eprintln!("<audit_log>: log of --audit_log.");
+                eprintln!("--head: sha256 of the last entry, saved elsewhere. Finds entries removed from the end.");
+                process::exit(1);
+            }
+            let head = match args.iter().position(|x| x == "--head") {
+                Some(index) => match args.get(index + 1) {
+                    Some(head) => Some(head.to_lowercase()),
+                    None => {
+                        eprintln!("--head provided without a value.");
+                        process::exit(1);
+                    }
+                },
+                None => None,
+            };
+            let verificacao = match auditoria::verifica(&args[3], head.as_deref()) {
+                Ok(verificacao) => verificacao,
+                Err(e) => {
+                    eprintln!("Audit error: {}", e);
+                    process::exit(1);
+                }
+            };
+            let problemas = verificacao.problemas;
+            for problema in &problemas {
+                eprintln!("Audit Error! {}: {}", args[3], problema);
+            }
+            println!(
+                "Entries:{} Head:{}",
+                verificacao.n_entradas, verificacao.head
+            );
+            if !problemas.is_empty() {
+                println!("Audit log does not verify: {} problems.", problemas.len());
+                process::exit(1);
+            }
+            println!("Audit log verifies: chain unbroken.");
+        }
+
+        "report" => {
+            if args.len() < 3 || file_path.starts_with("--") {
+                eprintln!(
+                    "Use: {} report <file_hashes> <--audit_log File>? <--check_events File>? <--format text|html>?",
+                    args[0]
+                );
+                eprintln!("<file_hashes>: hash file of the report.");
+                eprintln!("--audit_log: gen and check runs of this hash file, from the audit log.");
+                eprintln!("--check_events: events of a check (--events jsonl File).");
+                eprintln!("Case data: --case_file F, or --case, --examiner, --evidence, --agency, --notes.");
+                eprintln!("To verify: --trusted_keys F, --tsa_cert F, --mac_key_file F or --mac_key_env VAR.");
+                process::exit(1);
+            }
+            let valor = |opcao: &str| -> Option<String> {
+                let index = args.iter().position(|x| x == opcao)?;
+                match args.get(index + 1) {
+                    Some(valor) => Some(valor.to_string()),
+                    None => {
+                        eprintln!("{} provided without a value.", opcao);
+                        process::exit(1);
+                    }
+                }
+            };
+            let formato = match valor("--format").map(|valor| relatorio::Formato::parse(&valor)) {
+                Some(Ok(formato)) => formato,
+                Some(Err(e)) => {
+                    eprintln!("--format: {}", e);
+                    process::exit(1);
+                }
+                None => relatorio::Formato::Texto,
+            };
+            let com_chave_mac = match le_chave_mac(&args) {
+                Some(chave) => {
+                    mac::configura_check(chave);
+                    true
+                }
+                None => false,
+            };
+            let opcoes = relatorio::OpcoesRelatorio {
+                formato,
+                caso: main_args.caso.clone(),
+                audit_log: valor("--audit_log"),
+                eventos_check: valor("--check_events"),
+                chaves_confiaveis: valor("--trusted_keys"),
+                caminho_sig: le_caminho_assinatura(&args, file_path),
+                certificados_tsa: le_certificados_tsa(&args).map(str::to_string),
+                caminho_tsr: le_caminho_carimbo(&args, file_path),
+                com_chave_mac,
+            };
+            match relatorio::gera(file_path, &opcoes) {
+                Ok(relatorio) => print!("{}", relatorio),
+                Err(e) => {
+                    eprintln!("Report error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        "dupes" => {
+            // Arquivo: é um manifest. Diretório ou glob: lê a árvore.
+            let resultado = if Path::new(file_path).is_file() {
+                let mut work_dir = ".";
+                if args.len() > 3 && !args[3].starts_with("--") {
+                    work_dir = &args[3];
+                }
+                dupes::dupes_manifest(file_path, work_dir)
+            } else {
+                let results = search_files(file_path).unwrap();
+                dupes::dupes_tree(main_args, results)
+            };
+            if let Err(e) = resultado {
+                eprintln!("dupes error: {}", e);
+                process::exit(1);
+            }
+        }
+
+        "bench" => {
+            let mut n_rodadas = 1;
+            if let Some(rounds_index) = args.iter().position(|x| x == "--rounds") {
+                match args.get(rounds_index + 1).map(|valor| valor.parse::<u32>()) {
+                    Some(Ok(valor)) if valor > 0 => n_rodadas = valor,
+                    _ => {
+                        eprintln!("--rounds provided without a valid value. Ex: --rounds 5");
+                        process::exit(1);
+                    }
+                }
+            }
+            let tamanho_amostra = match le_amostra(&args, bench::AMOSTRA_BENCH) {
+                Some(tamanho) => tamanho,
+                None => process::exit(1),
+            };
+            if let Err(e) = bench::bench(&main_args, file_path, tamanho_amostra, n_rodadas) {
+                eprintln!("bench error: {}", e);
                 process::exit(1);
             }
         }
@@ -352,7 +1363,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         _ => {
             eprintln!("Use: {} <command> <file_path> <dest_folder_path>?", args[0]);
-            eprintln!("Commands: 'hsha256','split','rebuild','sha256' ");
+            eprintln!("Commands: 'gen','check','diff','merge','extract','dupes','bench','hsha256','split','rebuild','sha256','keygen','sign','verify-sig','timestamp','audit','report','selftest' ");
             process::exit(1);
         }
     }
@@ -0,0 +1,554 @@
+use log::{info, warn};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io;
+use std::process;
+
+use crate::functions::{ler_manifest, normaliza_alg, TFileHash};
+
+// (algoritmo, blocksize em bytes) + hash: identifica o conteúdo de um arquivo
+type ChaveHash<'a> = ((String, Option<u64>), &'a str);
+
+// Lê o manifest e indexa as entradas pelo caminho relativo.
+// Caminhos repetidos ficam com a última entrada lida (com aviso).
+fn indexa_manifest(file_path: &str) -> io::Result<BTreeMap<String, TFileHash>> {
+    let (entradas, erros) = ler_manifest(file_path)?;
+    for erro in erros {
+        eprintln!("Error! {}", erro);
+    }
+
+    let mut por_caminho = BTreeMap::new();
+    for entrada in entradas {
+        let caminho = match caminho_da_entrada(&entrada, file_path) {
+            Ok(caminho) => format!("./{}", caminho),
+            Err(erro) => {
+                eprintln!("Error! {}", erro);
+                continue;
+            }
+        };
+        if let Some(anterior) = por_caminho.insert(caminho.clone(), entrada) {
+            warn!(
+                "{}: path {} repeated (line {}), using last one",
+                file_path, caminho, anterior.n_arquivo
+            );
+        }
+    }
+    Ok(por_caminho)
+}
+
+// Duas entradas são comparáveis se usam o mesmo algoritmo com o mesmo blocksize.
+fn comparaveis(a: &TFileHash, b: &TFileHash) -> bool {
+    normaliza_alg(&a.alg_hash) == normaliza_alg(&b.alg_hash)
+}
+
+// Resultado da comparação de dois manifests indexados pelo caminho
+#[derive(Default)]
+struct DiffManifests<'a> {
+    n_iguais: usize,
+    // M: mesmo caminho, mesmo algoritmo, outro hash
+    alterados: Vec<(&'a String, &'a TFileHash, &'a TFileHash)>,
+    // R: (caminho em A, caminho em B)
+    renomeados: Vec<(&'a String, &'a String)>,
+    // -: só em A
+    removidos: Vec<(&'a String, &'a TFileHash)>,
+    // +: só em B
+    adicionados: Vec<&'a String>,
+    // !: mesmo caminho com algoritmo ou blocksize diferente
+    nao_comparaveis: Vec<(&'a String, &'a TFileHash, &'a TFileHash)>,
+}
+
+fn compara<'a>(
+    entradas_a: &'a BTreeMap<String, TFileHash>,
+    entradas_b: &'a BTreeMap<String, TFileHash>,
+) -> DiffManifests<'a> {
+    let mut diff = DiffManifests::default();
+    let DiffManifests {
+        n_iguais,
+        alterados,
+        renomeados,
+        removidos,
+        adicionados,
+        nao_comparaveis,
+    } = &mut diff;
+
+    for (caminho, entrada_a) in entradas_a {
+        match entradas_b.get(caminho) {
+            Some(entrada_b) => {
+                if !comparaveis(entrada_a, entrada_b) {
+                    nao_comparaveis.push((caminho, entrada_a, entrada_b));
+                } else if entrada_a.valor_hash == entrada_b.valor_hash {
+                    *n_iguais += 1;
+                } else {
+                    alterados.push((caminho, entrada_a, entrada_b));
+                }
+            }
+            None => removidos.push((caminho, entrada_a)),
+        }
+    }
+    for caminho in entradas_b.keys() {
+        if !entradas_a.contains_key(caminho) {
+            adicionados.push(caminho);
+        }
+    }
+
+    // Renomeados: some de A e aparece em B com o mesmo algoritmo e hash
+    let mut adicionados_por_hash: HashMap<ChaveHash, Vec<&String>> = HashMap::new();
+    for caminho in adicionados.iter().rev() {
+        let entrada_b = &entradas_b[*caminho];
+        adicionados_por_hash
+            .entry((
+                normaliza_alg(&entrada_b.alg_hash),
+                entrada_b.valor_hash.as_str(),
+            ))
+            .or_default()
+            .push(caminho);
+    }
+    removidos.retain(|(caminho, entrada_a)| {
+        let chave = (
+            normaliza_alg(&entrada_a.alg_hash),
+            entrada_a.valor_hash.as_str(),
+        );
+        match adicionados_por_hash
+            .get_mut(&chave)
+            .and_then(|caminhos| caminhos.pop())
+        {
+            Some(caminho_novo) => {
+                renomeados.push((*caminho, caminho_novo));
+                false
+            }
+            None => true,
+        }
+    });
+    let renomeados_para: HashSet<&String> = renomeados.iter().map(|(_, novo)| *novo).collect();
+    adicionados.retain(|caminho| !renomeados_para.contains(caminho));
+    diff
+}
+
+// Compara dois manifests sem ler os dados.
+// Retorna true se existe alguma diferença entre eles.
+pub fn diff_manifests(manifest_a: &str, manifest_b: &str) -> io::Result<bool> {
+    info!("diff: {} x {}", manifest_a, manifest_b);
+    let entradas_a = indexa_manifest(manifest_a)?;
+    let entradas_b = indexa_manifest(manifest_b)?;
+    let DiffManifests {
+        n_iguais,
+        alterados,
+        renomeados,
+        removidos,
+        adicionados,
+        nao_comparaveis,
+    } = compara(&entradas_a, &entradas_b);
+
+    for (caminho, entrada_a, entrada_b) in &alterados {
+        println!(
+            "M {} ?{} {} -> {}",
+            caminho, entrada_a.alg_hash, entrada_a.valor_hash, entrada_b.valor_hash
+        );
+    }
+    for (caminho_antigo, caminho_novo) in &renomeados {
+        println!("R {} -> {}", caminho_antigo, caminho_novo);
+    }
+    for (caminho, _) in &removidos {
+        println!("- {}", caminho);
+    }
+    for caminho in &adicionados {
+        println!("+ {}", caminho);
+    }
+    for (caminho, entrada_a, entrada_b) in &nao_comparaveis {
+        println!(
+            "! {} ?{} x ?{} not comparable",
+            caminho, entrada_a.alg_hash, entrada_b.alg_hash
+        );
+    }
+
+    eprintln!(
+        "Entries: {}:{} / {}:{}",
+        manifest_a,
+        entradas_a.len(),
+        manifest_b,
+        entradas_b.len()
+    );
+    eprintln!(
+        "Unchanged:{} Changed:{} Renamed:{} Removed:{} Added:{} Not comparable:{}",
+        n_iguais,
+        alterados.len(),
+        renomeados.len(),
+        removidos.len(),
+        adicionados.len(),
+        nao_comparaveis.len()
+    );
+    if nao_comparaveis.is_empty() {
+        eprintln!("Manifests are comparable: same algorithm and blocksize in all common entries.");
+    } else {
+        eprintln!(
+            "Manifests are NOT fully comparable: {} common entries use different algorithm or blocksize.",
+            nao_comparaveis.len()
+        );
+    }
+
+    Ok(!(alterados.is_empty()
+        && renomeados.is_empty()
+        && removidos.is_empty()
+        && adicionados.is_empty()
+        && nao_comparaveis.is_empty()))
+}
+
+// Normaliza um caminho relativo de manifest: "./a//b/" => "a/b". A raiz ("." ou "./") vira "".
+// Os nomes ficam como estão, byte a byte: espaço e '\' são válidos num nome no Linux.
+// ".." é erro: o caminho sairia da raiz do manifest.
+fn normaliza_caminho(caminho: &str) -> Result<String, String> {
+    let mut partes = Vec::new();
+    for parte in caminho.split('/') {
+        match parte {
+            "" | "." => {}
+            ".." => return Err(format!("path {} has '..'", caminho)),
+            _ => partes.push(parte),
+        }
+    }
+    Ok(partes.join("/"))
+}
+
+fn caminho_da_entrada(entrada: &TFileHash, origem: &str) -> Result<String, String> {
+    normaliza_caminho(&entrada.path)
+        .map_err(|e| format!("Line:{} File:{} {}", entrada.n_arquivo, origem, e))
+}
+
+fn subdir_normalizado(subdir: &str) -> io::Result<String> {
+    normaliza_caminho(subdir).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+// Manifest de saída do merge e do extract
+#[derive(Default)]
+struct ManifestJunto {
+    entradas: BTreeMap<String, TFileHash>,
+    em_conflito: BTreeSet<String>,
+    // Linhas inválidas e caminhos com ".."
+    n_erros: usize,
+}
+
+// Junta uma entrada no manifest de saída. Entradas idênticas repetidas entram uma vez só;
+// o mesmo caminho com outro algoritmo ou hash é conflito.
+fn junta_entrada(destino: &mut ManifestJunto, caminho: String, entrada: TFileHash, origem: &str) {
+    match destino.entradas.get(&caminho) {
+        Some(existente) => {
+            if normaliza_alg(&existente.alg_hash) == normaliza_alg(&entrada.alg_hash)
+                && existente.valor_hash == entrada.valor_hash
+            {
+                info!("{}: ./{} repeated with same hash, ignored", origem, caminho);
+            } else {
+                eprintln!(
+                    "Conflict! ./{}: {} ?{} x {} ?{} (line {} of {})",
+                    caminho,
+                    existente.valor_hash,
+                    existente.alg_hash,
+                    entrada.valor_hash,
+                    entrada.alg_hash,
+                    entrada.n_arquivo,
+                    origem
+                );
+                destino.em_conflito.insert(caminho);
+            }
+        }
+        None => {
+            destino.entradas.insert(caminho, entrada);
+        }
+    }
+}
+
+// Imprime as entradas que sobraram, sem os caminhos em conflito (nenhuma versão é confiável).
+fn imprime_manifest(junto: &ManifestJunto) -> usize {
+    let mut n_impressas = 0;
+    for (caminho, entrada) in &junto.entradas {
+        if junto.em_conflito.contains(caminho) {
+            continue;
+        }
+        println!("{} ?{}*./{}", entrada.valor_hash, entrada.alg_hash, caminho);
+        n_impressas += 1;
+    }
+    n_impressas
+}
+
+// Lê um manifest, mostra as linhas inválidas e conta no destino
+fn le_entradas(manifest: &str, destino: &mut ManifestJunto) -> io::Result<Vec<TFileHash>> {
+    let (entradas, erros) = ler_manifest(manifest)?;
+    for erro in &erros {
+        eprintln!("Error! {}", erro);
+    }
+    destino.n_erros += erros.len();
+    Ok(entradas)
+}
+
+fn junta_manifests(manifests: &[(String, String)]) -> io::Result<ManifestJunto> {
+    let mut saida = ManifestJunto::default();
+    for (manifest, subdir) in manifests {
+        let prefixo = subdir_normalizado(subdir)?;
+        info!("merge: {} => ./{}", manifest, prefixo);
+        for entrada in le_entradas(manifest, &mut saida)? {
+            let relativo = match caminho_da_entrada(&entrada, manifest) {
+                Ok(relativo) => relativo,
+                Err(erro) => {
+                    eprintln!("Error! {}", erro);
+                    saida.n_erros += 1;
+                    continue;
+                }
+            };
+            let caminho = if prefixo.is_empty() {
+                relativo
+            } else {
+                format!("{}/{}", prefixo, relativo)
+            };
+            junta_entrada(&mut saida, caminho, entrada, manifest);
+        }
+    }
+    Ok(saida)
+}
+
+// Junta manifests gerados em subárvores num único manifest, com raiz no pai comum.
+// Cada item é (manifest, subdir do manifest em relação ao pai comum).
+pub fn merge_manifests(manifests: &[(String, String)]) -> io::Result<()> {
+    let saida = junta_manifests(manifests)?;
+    let n_impressas = imprime_manifest(&saida);
+
+    eprintln!(
+        "Manifests merged:{} Entries:{} Conflicts:{} Invalid lines:{}",
+        manifests.len(),
+        n_impressas,
+        saida.em_conflito.len(),
+        saida.n_erros
+    );
+    if !saida.em_conflito.is_empty() || saida.n_erros > 0 {
+        eprintln!("Error. Conflicting or invalid entries were left out!");
+        process::exit(1);
+    }
+    Ok(())
+}
+
+// As entradas de ./<prefixo>, com o caminho a partir dele
+fn extrai(manifest: &str, prefixo: &str) -> io::Result<ManifestJunto> {
+    let mut saida = ManifestJunto::default();
+    for entrada in le_entradas(manifest, &mut saida)? {
+        let caminho = match caminho_da_entrada(&entrada, manifest) {
+            Ok(caminho) => caminho,
+            Err(erro) => {
+                eprintln!("Error! {}", erro);
+                saida.n_erros += 1;
+                continue;
+            }
+        };
+        let relativo = if prefixo.is_empty() {
+            Some(caminho.as_str())
+        } else {
+            caminho
+                .strip_prefix(prefixo)
+                .and_then(|resto| resto.strip_prefix('/'))
+        };
+        if let Some(relativo) = relativo {
+            junta_entrada(&mut saida, relativo.to_string(), entrada, manifest);
+        }
+    }
+    Ok(saida)
+}
+
+// Extrai as entradas de um subdir para um novo manifest, com raiz nesse subdir.
+pub fn extract_manifest(manifest: &str, subdir: &str) -> io::Result<()> {
+    let prefixo = subdir_normalizado(subdir)?;
+    info!("extract: ./{} from {}", prefixo, manifest);
+    let saida = extrai(manifest, &prefixo)?;
+    let n_impressas = imprime_manifest(&saida);
+
+    eprintln!(
+        "Entries extracted from ./{}:{} Conflicts:{} Invalid lines:{}",
+        prefixo,
+        n_impressas,
+        saida.em_conflito.len(),
+        saida.n_erros
+    );
+    if !saida.em_conflito.is_empty() || saida.n_erros > 0 {
+        eprintln!("Error. Conflicting or invalid entries were left out!");
+        process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testes::PastaTeste;
+    use std::fs;
+
+    fn hash(n: u8) -> String {
+        format!("{:02x}", n).repeat(32)
+    }
+
+    fn escreve_manifest(dir: &PastaTeste, nome: &str, linhas: &[String]) -> String {
+        let caminho = dir.join(nome);
+        fs::write(&caminho, linhas.join("\n") + "\n").unwrap();
+        caminho.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn diff_classifica() {
+        let dir = PastaTeste::nova("manifest_diff");
+        let a = escreve_manifest(
+            &dir,
+            "a.txt",
+            &[
+                format!("{} ?sha256*./igual", hash(1)),
+                format!("{} ?sha256*./mudou", hash(2)),
+                format!("{} ?sha256*./nome_velho", hash(3)),
+                format!("{} ?sha256*./sumiu", hash(4)),
+                format!("{} ?hsha256-50MB*./outro_alg", hash(5)),
+            ],
+        );
+        let b = escreve_manifest(
+            &dir,
+            "b.txt",
+            &[
+                format!("{} ?sha256*./igual", hash(1)),
+                format!("{} ?sha256*./mudou", hash(12)),
+                format!("{} ?sha256*./nome_novo", hash(3)),
+                format!("{} ?sha256*./novo", hash(6)),
+                format!("{} ?hsha256-10MB*./outro_alg", hash(5)),
+            ],
+        );
+        let entradas_a = indexa_manifest(&a).unwrap();
+        let entradas_b = indexa_manifest(&b).unwrap();
+        let diff = compara(&entradas_a, &entradas_b);
+
+        assert_eq!(diff.n_iguais, 1);
+        let alterados: Vec<_> = diff.alterados.iter().map(|(c, _, _)| c.as_str()).collect();
+        assert_eq!(alterados, ["./mudou"]);
+        let renomeados: Vec<_> = diff
+            .renomeados
+            .iter()
+            .map(|(velho, novo)| (velho.as_str(), novo.as_str()))
+            .collect();
+        assert_eq!(renomeados, [("./nome_velho", "./nome_novo")]);
+        let removidos: Vec<_> = diff.removidos.iter().map(|(c, _)| c.as_str()).collect();
+        assert_eq!(removidos, ["./sumiu"]);
+        let adicionados: Vec<_> = diff.adicionados.iter().map(|c| c.as_str()).collect();
+        assert_eq!(adicionados, ["./novo"]);
+        let nao_comparaveis: Vec<_> = diff
+            .nao_comparaveis
+            .iter()
+            .map(|(c, _, _)| c.as_str())
+            .collect();
+        assert_eq!(nao_comparaveis, ["./outro_alg"]);
+
+        assert!(diff_manifests(&a, &b).unwrap());
+        assert!(!diff_manifests(&a, &a).unwrap());
+    }
+
+    #[test]
+    fn normaliza_caminho_mantem_os_nomes() {
+        assert_eq!(normaliza_caminho("./a//b/").unwrap(), "a/b");
+        assert_eq!(normaliza_caminho("./").unwrap(), "");
+        assert_eq!(normaliza_caminho(".").unwrap(), "");
+        // Espaço nas pontas e '\' são parte do nome
+        assert_eq!(normaliza_caminho("./ a /b ").unwrap(), " a /b ");
+        assert_eq!(normaliza_caminho("./a\\b").unwrap(), "a\\b");
+        assert!(normaliza_caminho("./a/../b").is_err());
+        assert!(normaliza_caminho("..").is_err());
+        // ".." dentro do nome não é o diretório pai
+        assert_eq!(normaliza_caminho("./a..b/..c").unwrap(), "a..b/..c");
+    }
+
+    #[test]
+    fn diff_nome_com_espaco_e_outro_arquivo() {
+        let dir = PastaTeste::nova("manifest_diff_espaco");
+        let a = escreve_manifest(&dir, "a.txt", &[format!("{} ?sha256*./x", hash(1))]);
+        let b = escreve_manifest(&dir, "b.txt", &[format!("{} ?sha256*./x ", hash(2))]);
+        let entradas_a = indexa_manifest(&a).unwrap();
+        let entradas_b = indexa_manifest(&b).unwrap();
+        let diff = compara(&entradas_a, &entradas_b);
+        assert!(diff.alterados.is_empty());
+        assert_eq!(diff.removidos.len(), 1);
+        assert_eq!(diff.adicionados, [&String::from("./x ")]);
+    }
+
+    #[test]
+    fn merge_junta_e_acha_conflitos() {
+        let dir = PastaTeste::nova("manifest_merge");
+        let m1 = escreve_manifest(
+            &dir,
+            "m1.txt",
+            &[
+                format!("{} ?sha256*./a", hash(1)),
+                format!("{} ?sha256*./dup", hash(2)),
+                format!("{} ?sha256*./conflito", hash(3)),
+            ],
+        );
+        let m2 = escreve_manifest(
+            &dir,
+            "m2.txt",
+            &[
+                format!("{} ?sha256*./b", hash(4)),
+                format!("{} ?sha256*./sub/dup", hash(2)),
+                format!("{} ?sha256*./sub/conflito", hash(5)),
+                format!("{} ?sha256*./../fora", hash(6)),
+            ],
+        );
+        // m2 foi gerado dentro de ./x, o m1 no pai
+        let junto = junta_manifests(&[
+            (m1.clone(), String::from(".")),
+            (m2.clone(), String::from("./x/")),
+        ])
+        .unwrap();
+        let caminhos: Vec<_> = junto.entradas.keys().map(|c| c.as_str()).collect();
+        assert_eq!(
+            caminhos,
+            ["a", "conflito", "dup", "x/b", "x/sub/conflito", "x/sub/dup"]
+        );
+        assert!(junto.em_conflito.is_empty());
+        assert_eq!(junto.n_erros, 1);
+
+        // O mesmo caminho duas vezes: igual entra uma vez, diferente é conflito
+        let m3 = escreve_manifest(
+            &dir,
+            "m3.txt",
+            &[
+                format!("{} ?sha256*./sub/dup", hash(2)),
+                format!("{} ?sha256*./sub/conflito", hash(7)),
+                format!("{} ?hsha256-50MB*./b", hash(4)),
+            ],
+        );
+        let junto =
+            junta_manifests(&[(m2.clone(), String::from(".")), (m3, String::from("."))]).unwrap();
+        let em_conflito: Vec<_> = junto.em_conflito.iter().map(|c| c.as_str()).collect();
+        assert_eq!(em_conflito, ["b", "sub/conflito"]);
+
+        assert!(junta_manifests(&[(m2, String::from("../x"))]).is_err());
+    }
+
+    #[test]
+    fn extract_pega_so_o_subdir() {
+        let dir = PastaTeste::nova("manifest_extract");
+        let m = escreve_manifest(
+            &dir,
+            "m.txt",
+            &[
+                format!("{} ?sha256*./sub/a", hash(1)),
+                format!("{} ?sha256*./sub/c/d", hash(2)),
+                format!("{} ?sha256*./subdir/b", hash(3)),
+                format!("{} ?sha256*./outro/e", hash(4)),
+            ],
+        );
+        let extraido = extrai(&m, "sub").unwrap();
+        let caminhos: Vec<_> = extraido.entradas.keys().map(|c| c.as_str()).collect();
+        assert_eq!(caminhos, ["a", "c/d"]);
+        assert_eq!(extraido.entradas["c/d"].valor_hash, hash(2));
+        assert_eq!(extraido.n_erros, 0);
+        assert_eq!(extrai(&m, "").unwrap().entradas.len(), 4);
+    }
+
+    #[test]
+    fn diff_blocksize_em_outra_unidade_e_comparavel() {
+        let dir = PastaTeste::nova("manifest_diff_unidade");
+        let a = escreve_manifest(&dir, "a.txt", &[format!("{} ?hsha256-50MB*./x", hash(1))]);
+        let b = escreve_manifest(
+            &dir,
+            "b.txt",
+            &[format!("{} ?hsha256-51200KB*./x", hash(1))],
+        );
+        assert!(!diff_manifests(&a, &b).unwrap());
+    }
+}
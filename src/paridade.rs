@@ -0,0 +1,551 @@
+// Partes de paridade Reed-Solomon para o split (--parity N).
+//
+// As partes de dados são separadas em grupos de até 256 - N partes (limite do GF(2^8)), e cada
+// grupo ganha N partes de paridade do tamanho de uma parte. No rebuild, em cada grupo, até N
+// partes faltando ou corrompidas (pelo sha256 do manifest) são reconstruídas a partir das
+// partes boas do grupo e das paridades boas.
+// O cálculo é feito em pedaços (PEDACO_PARIDADE) na mesma posição de todas as partes do grupo:
+// Reed-Solomon trabalha byte a byte, então não precisa da parte inteira na memória.
+// A última parte, mais curta, conta como completada com zeros.
+// Com --compress ou --encrypt, a paridade é do que foi gravado (tamanhos diferentes por parte,
+// o grupo usa o maior): a reconstrução devolve a parte gravada, que depois é decifrada.
+
+use log::info;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::split::{ManifestSplit, ParteSplit};
+
+const MAX_PARTES_GRUPO: usize = 256;
+const PEDACO_PARIDADE: u64 = 256 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GrupoParidade {
+    // Partes de dados first_part .. first_part + n_parts
+    pub first_part: u64,
+    pub n_parts: u64,
+    // Tamanho das partes de paridade (a maior parte gravada do grupo). 0: part_size
+    #[serde(default)]
+    pub shard_size: u64,
+    pub parts: Vec<ParteSplit>,
+}
+
+impl GrupoParidade {
+    fn tamanho_shard(&self, manifest: &ManifestSplit) -> u64 {
+        if self.shard_size > 0 {
+            self.shard_size
+        } else {
+            manifest.part_size
+        }
+    }
+}
+
+// Quantas partes de dados por grupo com n_paridade partes de paridade
+fn partes_por_grupo(n_paridade: usize) -> usize {
+    MAX_PARTES_GRUPO - n_paridade
+}
+
+pub fn valida_n_paridade(n_paridade: usize) -> Result<(), Box<dyn Error>> {
+    if n_paridade == 0 || n_paridade > MAX_PARTES_GRUPO / 2 {
+        return Err(format!("--parity must be from 1 to {}", MAX_PARTES_GRUPO / 2).into());
+    }
+    Ok(())
+}
+
+// Grupos do manifest: em sequência, cobrindo todas as partes de dados, e cada um com até 256
+// partes (dados e paridade). Um manifest mexido não pode levar o rebuild para fora das partes.
+pub fn valida_grupos(manifest: &ManifestSplit) -> Result<(), String> {
+    if manifest.parity.is_empty() {
+        return Ok(());
+    }
+    let mut inicio = 0;
+    for grupo in &manifest.parity {
+        let fim_grupo = grupo.first_part.checked_add(grupo.n_parts);
+        if grupo.first_part != inicio || grupo.n_parts == 0 {
+            return Err(format!(
+                "parity group of parts {}..+{} out of sequence (expected from part {})",
+                grupo.first_part, grupo.n_parts, inicio
+            ));
+        }
+        let fim_grupo = match fim_grupo {
+            Some(fim) if fim <= manifest.parts.len() as u64 => fim,
+            _ => {
+                return Err(format!(
+                    "parity group of parts {}..+{} beyond the {} parts",
+                    grupo.first_part,
+                    grupo.n_parts,
+                    manifest.parts.len()
+                ))
+            }
+        };
+        if grupo.parts.is_empty() || grupo.n_parts as usize + grupo.parts.len() > MAX_PARTES_GRUPO {
+            return Err(format!(
+                "parity group of parts {}..{}: {} parity parts (must be 1 to {} with the data parts)",
+                grupo.first_part,
+                fim_grupo - 1,
+                grupo.parts.len(),
+                MAX_PARTES_GRUPO
+            ));
+        }
+        for (index, parte) in grupo.parts.iter().enumerate() {
+            if parte.index != index as u64 {
+                return Err(format!("parity part {} out of sequence", parte.name));
+            }
+        }
+        inicio = fim_grupo;
+    }
+    if inicio != manifest.parts.len() as u64 {
+        return Err(format!(
+            "parity groups cover {} of {} parts",
+            inicio,
+            manifest.parts.len()
+        ));
+    }
+    Ok(())
+}
+
+// <prefixo>parity<grupo>_<índice>, como os nomes das partes de dados
+fn nome_paridade(prefixo: &str, grupo: usize, index: usize) -> String {
+    format!("{}parity{}_{}", prefixo, grupo, index)
+}
+
+// Nomes das partes de paridade de um split de n_partes partes
+pub fn nomes_paridade(prefixo: &str, n_partes: u64, n_paridade: usize) -> Vec<String> {
+    let n_grupos = (n_partes as usize).div_ceil(partes_por_grupo(n_paridade));
+    (0..n_grupos)
+        .flat_map(|grupo| (0..n_paridade).map(move |index| nome_paridade(prefixo, grupo, index)))
+        .collect()
+}
+
+// Lê até buffer.len() bytes a partir da posição atual; o que faltar fica com zero
+fn le_pedaco(file: &mut File, buffer: &mut [u8]) -> io::Result<()> {
+    let mut lido = 0;
+    while lido < buffer.len() {
+        match file.read(&mut buffer[lido..]) {
+            Ok(0) => break,
+            Ok(n) => lido += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    buffer[lido..].fill(0);
+    Ok(())
+}
+
+// Escreve as partes de paridade de todos os grupos, lendo as partes de dados já escritas
+pub fn gera_paridade(
+    dir_destino: &str,
+    manifest: &ManifestSplit,
+    n_paridade: usize,
+) -> Result<Vec<GrupoParidade>, Box<dyn Error>> {
+    use std::io::Write;
+
+    let mut grupos = Vec::new();
+    for (n_grupo, partes_dados) in manifest
+        .parts
+        .chunks(partes_por_grupo(n_paridade))
+        .enumerate()
+    {
+        let tamanho_parte = partes_dados
+            .iter()
+            .map(|parte| parte.tamanho_guardado())
+            .max()
+            .unwrap_or(0);
+        let rs = ReedSolomon::new(partes_dados.len(), n_paridade)?;
+        let mut arquivos_dados = partes_dados
+            .iter()
+            .map(|parte| File::open(Path::new(dir_destino).join(&parte.name)))
+            .collect::<io::Result<Vec<File>>>()?;
+        let nomes: Vec<String> = (0..n_paridade)
+            .map(|index| nome_paridade(&manifest.part_prefix, n_grupo, index))
+            .collect();
+        let mut arquivos_paridade = nomes
+            .iter()
+            .map(|nome| File::create(Path::new(dir_destino).join(nome)))
+            .collect::<io::Result<Vec<File>>>()?;
+        let mut hashers: Vec<Sha256> = (0..n_paridade).map(|_| Sha256::new()).collect();
+
+        let mut offset = 0;
+        while offset < tamanho_parte {
+            let tamanho = PEDACO_PARIDADE.min(tamanho_parte - offset) as usize;
+            let mut dados = vec![vec![0u8; tamanho]; partes_dados.len()];
+            for (arquivo, pedaco) in arquivos_dados.iter_mut().zip(dados.iter_mut()) {
+                le_pedaco(arquivo, pedaco)?;
+            }
+            let mut paridade = vec![vec![0u8; tamanho]; n_paridade];
+            rs.encode_sep(&dados, &mut paridade)?;
+            for ((arquivo, hasher), pedaco) in arquivos_paridade
+                .iter_mut()
+                .zip(hashers.iter_mut())
+                .zip(paridade.iter())
+            {
+                arquivo.write_all(pedaco)?;
+                hasher.update(pedaco);
+            }
+            offset += tamanho as u64;
+        }
+
+        let mut partes_paridade = Vec::new();
+        for (index, ((nome, arquivo), hasher)) in nomes
+            .into_iter()
+            .zip(arquivos_paridade)
+            .zip(hashers)
+            .enumerate()
+        {
+            arquivo.sync_all()?;
+            partes_paridade.push(ParteSplit {
+                index: index as u64,
+                name: nome,
+                offset: 0,
+                size: tamanho_parte,
+                sha256: format!("{:x}", hasher.finalize()),
+                stored_size: None,
+                stored_sha256: None,
+            });
+        }
+        info!(
+            "parity group {}: parts {}..{}, {} parity parts",
+            n_grupo,
+            partes_dados[0].index,
+            partes_dados[0].index + partes_dados.len() as u64 - 1,
+            n_paridade
+        );
+        grupos.push(GrupoParidade {
+            first_part: partes_dados[0].index,
+            n_parts: partes_dados.len() as u64,
+            shard_size: tamanho_parte,
+            parts: partes_paridade,
+        });
+    }
+    Ok(grupos)
+}
+
+fn sha256_arquivo(caminho: &Path) -> io::Result<(u64, String)> {
+    let mut file = File::open(caminho)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; PEDACO_PARIDADE as usize];
+    let mut total = 0;
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        total += n as u64;
+    }
+    Ok((total, format!("{:x}", hasher.finalize())))
+}
+
+// Destino dos bytes reconstruídos: (parte, posição na parte, bytes)
+pub type EntregaParte<'a> = dyn FnMut(&ParteSplit, u64, &[u8]) -> io::Result<()> + 'a;
+
+// Reconstrói as partes de dados ruins (índices em `ruins`): os bytes gravados de cada uma vão
+// para `entrega(parte, posição na parte, bytes)`. Retorna os problemas (grupo sem partes boas
+// suficientes, parte reconstruída que não bate) e as partes reconstruídas.
+pub fn reconstroi(
+    dir_partes: &str,
+    manifest: &ManifestSplit,
+    ruins: &[u64],
+    entrega: &mut EntregaParte,
+) -> Result<(Vec<String>, Vec<u64>), Box<dyn Error>> {
+    let mut problemas = Vec::new();
+    let mut reconstruidas = Vec::new();
+
+    for grupo in &manifest.parity {
+        let tamanho_parte = grupo.tamanho_shard(manifest);
+        let fim_grupo = grupo.first_part + grupo.n_parts;
+        let ruins_grupo: Vec<u64> = ruins
+            .iter()
+            .copied()
+            .filter(|index| (grupo.first_part..fim_grupo).contains(index))
+            .collect();
+        if ruins_grupo.is_empty() {
+            continue;
+        }
+        let partes_dados = &manifest.parts[grupo.first_part as usize..fim_grupo as usize];
+
+        // Só entram as paridades que batem com o manifest
+        let mut paridades_boas = Vec::new();
+        for parte in &grupo.parts {
+            let caminho = Path::new(dir_partes).join(&parte.name);
+            match sha256_arquivo(&caminho) {
+                Ok((tamanho, sha256)) if tamanho == parte.size && sha256 == parte.sha256 => {
+                    paridades_boas.push(parte.index as usize)
+                }
+                Ok(_) => eprintln!("Parity part {} corrupt, not used", parte.name),
+                Err(e) => eprintln!("Parity part {} not used: {}", parte.name, e),
+            }
+        }
+        if ruins_grupo.len() > paridades_boas.len() {
+            problemas.push(format!(
+                "parts {}..{}: {} damaged, only {} good parity parts. Not reconstructed",
+                grupo.first_part,
+                fim_grupo - 1,
+                ruins_grupo.len(),
+                paridades_boas.len()
+            ));
+            continue;
+        }
+
+        let n_dados = grupo.n_parts as usize;
+        let rs = ReedSolomon::new(n_dados, grupo.parts.len())?;
+        // Arquivos abertos das partes boas (dados e paridade), na ordem dos shards
+        let mut arquivos: Vec<Option<File>> = Vec::new();
+        for parte in partes_dados {
+            arquivos.push(if ruins_grupo.contains(&parte.index) {
+                None
+            } else {
+                Some(File::open(Path::new(dir_partes).join(&parte.name))?)
+            });
+        }
+        for (index, parte) in grupo.parts.iter().enumerate() {
+            arquivos.push(if paridades_boas.contains(&index) {
+                Some(File::open(Path::new(dir_partes).join(&parte.name))?)
+            } else {
+                None
+            });
+        }
+
+        let mut hashers: Vec<Sha256> = ruins_grupo.iter().map(|_| Sha256::new()).collect();
+        let mut offset = 0;
+        while offset < tamanho_parte {
+            let tamanho = PEDACO_PARIDADE.min(tamanho_parte - offset) as usize;
+            let mut shards: Vec<Option<Vec<u8>>> = Vec::with_capacity(arquivos.len());
+            for arquivo in arquivos.iter_mut() {
+                shards.push(match arquivo {
+                    Some(arquivo) => {
+                        let mut pedaco = vec![0u8; tamanho];
+                        le_pedaco(arquivo, &mut pedaco)?;
+                        Some(pedaco)
+                    }
+                    None => None,
+                });
+            }
+            rs.reconstruct_data(&mut shards)?;
+            for (index, hasher) in ruins_grupo.iter().zip(hashers.iter_mut()) {
+                let parte = &manifest.parts[*index as usize];
+                if offset >= parte.tamanho_guardado() {
+                    continue;
+                }
+                let pedaco = shards[(*index - grupo.first_part) as usize]
+                    .as_ref()
+                    .expect("shard reconstructed");
+                let ate = (parte.tamanho_guardado() - offset).min(tamanho as u64) as usize;
+                entrega(parte, offset, &pedaco[..ate])?;
+                hasher.update(&pedaco[..ate]);
+            }
+            offset += tamanho as u64;
+        }
+
+        for (index, hasher) in ruins_grupo.iter().zip(hashers) {
+            let parte = &manifest.parts[*index as usize];
+            let sha256 = format!("{:x}", hasher.finalize());
+            if sha256 == parte.sha256_guardado() {
+                eprintln!(
+                    "Part {} ({}) reconstructed from parity",
+                    parte.index, parte.name
+                );
+                reconstruidas.push(parte.index);
+            } else {
+                problemas.push(format!(
+                    "part {} ({}): reconstructed with wrong sha256 {}",
+                    parte.index, parte.name, sha256
+                ));
+            }
+        }
+    }
+    Ok((problemas, reconstruidas))
+}
+
+#[cfg(unix)]
+pub fn escreve_em(file: &File, dados: &[u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.write_all_at(dados, offset)
+}
+
+#[cfg(windows)]
+pub fn escreve_em(file: &File, mut dados: &[u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !dados.is_empty() {
+        let n = file.seek_write(dados, offset)?;
+        dados = &dados[n..];
+        offset += n as u64;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::ModoLeitura;
+    use crate::split::{self, OpcoesSplit};
+    use crate::testes::PastaTeste;
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    const TAMANHO_PARTE: u64 = 1000;
+    const N_PARIDADE: usize = 2;
+
+    // Arquivo de 9500 bytes em 10 partes (a última com 500) e 2 partes de paridade
+    fn prepara(nome: &str) -> (PastaTeste, ManifestSplit, Vec<u8>) {
+        let dir = PastaTeste::nova(nome);
+        let dados: Vec<u8> = (0..9500u32).map(|i| (i * 7 % 251) as u8).collect();
+        let original = dir.join("evidence.bin");
+        fs::write(&original, &dados).unwrap();
+        let opcoes = OpcoesSplit {
+            n_paridade: N_PARIDADE,
+            ..Default::default()
+        };
+        let manifest = split::split(
+            original.to_str().unwrap(),
+            dir.join("parts").to_str().unwrap(),
+            256,
+            TAMANHO_PARTE as usize,
+            "1000B",
+            4,
+            &opcoes,
+        )
+        .unwrap();
+        (dir, manifest, dados)
+    }
+
+    // Apaga as partes e reconstrói: (problemas, reconstruídas, bytes entregues por parte)
+    fn reconstroi_sem(
+        dir: &PastaTeste,
+        manifest: &ManifestSplit,
+        faltando: &[u64],
+    ) -> (Vec<String>, Vec<u64>, BTreeMap<u64, Vec<u8>>) {
+        let dir_partes = dir.join("parts");
+        for index in faltando {
+            let _ = fs::remove_file(dir_partes.join(&manifest.parts[*index as usize].name));
+        }
+        let mut entregues: BTreeMap<u64, Vec<u8>> = BTreeMap::new();
+        let mut entrega = |parte: &ParteSplit, offset: u64, bytes: &[u8]| -> io::Result<()> {
+            let destino = entregues.entry(parte.index).or_default();
+            let fim = offset as usize + bytes.len();
+            if destino.len() < fim {
+                destino.resize(fim, 0);
+            }
+            destino[offset as usize..fim].copy_from_slice(bytes);
+            Ok(())
+        };
+        let (problemas, reconstruidas) = reconstroi(
+            dir_partes.to_str().unwrap(),
+            manifest,
+            faltando,
+            &mut entrega,
+        )
+        .unwrap();
+        (problemas, reconstruidas, entregues)
+    }
+
+    #[test]
+    fn reconstroi_ate_n_partes_faltando() {
+        let (dir, manifest, dados) = prepara("paridade_n");
+        assert_eq!(manifest.parity.len(), 1);
+        assert_eq!(manifest.parity[0].parts.len(), N_PARIDADE);
+
+        // A parte 9 é a última, menor que as outras
+        let (problemas, reconstruidas, entregues) = reconstroi_sem(&dir, &manifest, &[3, 9]);
+        assert!(problemas.is_empty(), "{:?}", problemas);
+        assert_eq!(reconstruidas, vec![3, 9]);
+        assert_eq!(entregues[&3], dados[3000..4000]);
+        assert_eq!(entregues[&9], dados[9000..]);
+    }
+
+    #[test]
+    fn nao_reconstroi_com_n_mais_1_faltando() {
+        let (dir, manifest, _) = prepara("paridade_n_mais_1");
+        let (problemas, reconstruidas, entregues) = reconstroi_sem(&dir, &manifest, &[0, 4, 9]);
+        assert_eq!(problemas.len(), 1);
+        assert!(problemas[0].contains("3 damaged, only 2 good parity parts"));
+        assert!(reconstruidas.is_empty());
+        assert!(entregues.is_empty());
+    }
+
+    #[test]
+    fn paridade_corrompida_nao_conta() {
+        let (dir, manifest, dados) = prepara("paridade_paridade_corrompida");
+        let paridade = dir.join("parts").join(&manifest.parity[0].parts[0].name);
+        let mut bytes = fs::read(&paridade).unwrap();
+        bytes[10] ^= 0xff;
+        fs::write(&paridade, bytes).unwrap();
+
+        // Com uma paridade boa, uma parte volta; duas não
+        let (problemas, reconstruidas, entregues) = reconstroi_sem(&dir, &manifest, &[5]);
+        assert!(problemas.is_empty(), "{:?}", problemas);
+        assert_eq!(reconstruidas, vec![5]);
+        assert_eq!(entregues[&5], dados[5000..6000]);
+        let (problemas, reconstruidas, _) = reconstroi_sem(&dir, &manifest, &[1, 5]);
+        assert_eq!(problemas.len(), 1);
+        assert!(problemas[0].contains("2 damaged, only 1 good parity parts"));
+        assert!(reconstruidas.is_empty());
+    }
+
+    #[test]
+    fn rebuild_conta_so_as_partes_que_nao_voltaram() {
+        // 2600 bytes em 260 partes: mais de um grupo de paridade, com 1 parte de paridade cada
+        let dir = PastaTeste::nova("paridade_conta_perdidas");
+        let original = dir.join("evidence.bin");
+        fs::write(&original, vec![7u8; 2600]).unwrap();
+        let opcoes = OpcoesSplit {
+            n_paridade: 1,
+            ..Default::default()
+        };
+        let dir_partes = dir.join("parts");
+        let manifest = split::split(
+            original.to_str().unwrap(),
+            dir_partes.to_str().unwrap(),
+            256,
+            10,
+            "1000B",
+            4,
+            &opcoes,
+        )
+        .unwrap();
+        assert!(manifest.parity.len() > 1);
+        // Uma parte no primeiro grupo (volta) e duas no último (não voltam)
+        let ultimo = manifest.parity.last().unwrap().first_part;
+        for index in [0, ultimo, ultimo + 1] {
+            fs::remove_file(dir_partes.join(&manifest.parts[index as usize].name)).unwrap();
+        }
+        let erro = split::rebuild(
+            dir.join("rebuilt.bin").to_str().unwrap(),
+            dir_partes.to_str().unwrap(),
+            None,
+            None,
+            4096,
+            4,
+            ModoLeitura::Read,
+        )
+        .unwrap_err();
+        assert!(
+            erro.to_string()
+                .starts_with("2 of 260 parts missing or corrupt"),
+            "{}",
+            erro
+        );
+    }
+
+    #[test]
+    fn grupos_fora_das_partes() {
+        let (_dir, manifest, _) = prepara("paridade_grupos");
+        assert!(valida_grupos(&manifest).is_ok());
+        let mexe = |muda: &dyn Fn(&mut ManifestSplit)| {
+            let mut manifest: ManifestSplit =
+                serde_json::from_value(serde_json::to_value(&manifest).unwrap()).unwrap();
+            muda(&mut manifest);
+            valida_grupos(&manifest)
+        };
+        assert!(mexe(&|m| m.parity[0].n_parts = 11).is_err());
+        assert!(mexe(&|m| m.parity[0].first_part = 1).is_err());
+        assert!(mexe(&|m| m.parity[0].n_parts = 9).is_err());
+        assert!(mexe(&|m| m.parity[0].parts.clear()).is_err());
+        assert!(mexe(&|m| m.parity[0].n_parts = u64::MAX).is_err());
+    }
+}
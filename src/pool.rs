@@ -0,0 +1,172 @@
+// Pool único de threads de hash e limite global de bytes em voo.
+//
+// Os workers (--n_workers) só distribuem os blocos dos seus arquivos; quem lê e hasheia são
+// as threads deste pool, as mesmas para todos os arquivos. Assim o número de threads não
+// multiplica com n_workers x n_max_concur.
+// Antes de mandar um bloco para o pool, quem manda reserva os bytes que a leitura vai usar
+// (--buffersize). Se o limite (--max_in_flight) estiver cheio, espera. As tarefas do pool nunca
+// esperam por reserva, então quem está rodando sempre termina e devolve os bytes.
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use log::info;
+use once_cell::sync::OnceCell;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+pub const N_THREADS_PADRAO: u32 = 15;
+pub const MAX_IN_FLIGHT_PADRAO: &str = "1GB";
+
+// Retorna false para a thread que a executou sair do pool
+type Tarefa = Box<dyn FnOnce() -> bool + Send + 'static>;
+
+pub struct PoolHash {
+    tarefas: Sender<Tarefa>,
+    receiver_tarefas: Receiver<Tarefa>,
+    n_threads: Mutex<u32>,
+}
+
+// Bytes reservados pelas leituras em andamento
+pub struct Orcamento {
+    limite: u64,
+    estado: Mutex<EstadoOrcamento>,
+    liberado: Condvar,
+}
+
+#[derive(Default)]
+struct EstadoOrcamento {
+    em_uso: u64,
+    // Reservado para sempre (buffers do io_uring), fora do alcance das leituras
+    fixo: u64,
+}
+
+// Bytes reservados. Voltam para o orçamento no drop.
+pub struct Reserva {
+    bytes: u64,
+}
+
+static POOL_HASH: OnceCell<PoolHash> = OnceCell::new();
+static ORCAMENTO: OnceCell<Orcamento> = OnceCell::new();
+
+// Chamado uma vez pelo main, antes de qualquer hash
+pub fn define_limites(n_threads: u32, max_in_flight: u64) {
+    let n_threads = n_threads.max(1);
+    let max_in_flight = max_in_flight.max(1);
+    info!(
+        "Hash pool: {} threads, max in flight: {} bytes",
+        n_threads, max_in_flight
+    );
+    if POOL_HASH.set(inicia_pool(n_threads)).is_err() {
+        panic!("POOL_HASH can only be set once");
+    }
+    if ORCAMENTO.set(Orcamento::new(max_in_flight)).is_err() {
+        panic!("ORCAMENTO can only be set once");
+    }
+}
+
+pub fn pool() -> &'static PoolHash {
+    POOL_HASH.get_or_init(|| inicia_pool(N_THREADS_PADRAO))
+}
+
+pub fn orcamento() -> &'static Orcamento {
+    ORCAMENTO.get_or_init(|| {
+        Orcamento::new(crate::functions::ParseSize(MAX_IN_FLIGHT_PADRAO).unwrap_or(1 << 30))
+    })
+}
+
+// Reserva no orçamento global os bytes de uma leitura (ver Orcamento::reserva)
+pub fn reserva(bytes: u64) -> Reserva {
+    orcamento().reserva(bytes)
+}
+
+fn inicia_pool(n_threads: u32) -> PoolHash {
+    let (tarefas, receiver_tarefas) = unbounded::<Tarefa>();
+    let pool = PoolHash {
+        tarefas,
+        receiver_tarefas,
+        n_threads: Mutex::new(0),
+    };
+    pool.ajusta_threads(n_threads);
+    pool
+}
+
+impl PoolHash {
+    pub fn executa<F>(&self, tarefa: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.tarefas
+            .send(Box::new(move || {
+                tarefa();
+                true
+            }))
+            .expect("Hash pool stopped");
+    }
+
+    // Muda o número de threads (bench e --auto testam vários). Para diminuir, manda tarefas
+    // que fazem a thread sair: elas entram na fila depois do que já foi mandado.
+    pub fn ajusta_threads(&self, n_threads: u32) {
+        let n_threads = n_threads.max(1);
+        let mut n_atual = self.n_threads.lock().unwrap();
+        while *n_atual < n_threads {
+            let receiver_tarefas = self.receiver_tarefas.clone();
+            thread::Builder::new()
+                .name(format!("hush-hash-{}", *n_atual))
+                .spawn(move || {
+                    while let Ok(tarefa) = receiver_tarefas.recv() {
+                        if !tarefa() {
+                            break;
+                        }
+                    }
+                })
+                .expect("Failed to spawn hash pool thread");
+            *n_atual += 1;
+        }
+        while *n_atual > n_threads {
+            self.tarefas
+                .send(Box::new(|| false))
+                .expect("Hash pool stopped");
+            *n_atual -= 1;
+        }
+        info!("Hash pool: {} threads", n_threads);
+    }
+}
+
+impl Orcamento {
+    fn new(limite: u64) -> Orcamento {
+        Orcamento {
+            limite,
+            estado: Mutex::new(EstadoOrcamento::default()),
+            liberado: Condvar::new(),
+        }
+    }
+
+    // Espera até ter `bytes` livres. Um pedido maior que o limite espera o orçamento todo.
+    fn reserva(&self, bytes: u64) -> Reserva {
+        let mut estado = self.estado.lock().unwrap();
+        let disponivel = self.limite - estado.fixo;
+        let bytes = bytes.min(disponivel);
+        while estado.em_uso + bytes > disponivel {
+            estado = self.liberado.wait(estado).unwrap();
+        }
+        estado.em_uso += bytes;
+        Reserva { bytes }
+    }
+
+    // Tira do orçamento, para sempre, memória alocada uma vez (no máximo metade do limite).
+    // Retorna quanto pode ser usado.
+    pub fn reserva_fixa(&self, bytes: u64) -> u64 {
+        let mut estado = self.estado.lock().unwrap();
+        let bytes = bytes.min(self.limite / 2 - estado.fixo.min(self.limite / 2));
+        estado.fixo += bytes;
+        bytes
+    }
+}
+
+impl Drop for Reserva {
+    fn drop(&mut self) {
+        let orcamento = orcamento();
+        let mut estado = orcamento.estado.lock().unwrap();
+        estado.em_uso -= self.bytes;
+        orcamento.liberado.notify_all();
+    }
+}
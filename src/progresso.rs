@@ -0,0 +1,281 @@
+// Progresso do gen, check e dupes (--progress).
+//
+// O total de arquivos e de bytes vem da busca; os bytes hasheados são somados a cada bloco
+// pronto (arquivo pequeno, sha256, soma tudo quando termina).
+// Com o stderr num terminal, uma linha redesenhada: percentual, MB/s, ETA, arquivo grande em
+// andamento e erros. Fora de terminal (stderr num arquivo de log), uma linha comum a cada
+// INTERVALO_LOG.
+
+use once_cell::sync::OnceCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const INTERVALO_TERMINAL: Duration = Duration::from_millis(250);
+const INTERVALO_LOG: Duration = Duration::from_secs(10);
+// MB/s e ETA pela média dos últimos segundos: acompanha mudança de --max_rate, disco mais lento...
+const JANELA_TAXA: Duration = Duration::from_secs(10);
+
+struct Progresso {
+    total_arquivos: u64,
+    total_bytes: u64,
+    arquivos_prontos: AtomicU64,
+    bytes_prontos: AtomicU64,
+    erros: AtomicU64,
+    // Arquivos grandes (hsha256) em andamento: caminho -> (tamanho, bytes hasheados)
+    grandes: Mutex<BTreeMap<String, (u64, u64)>>,
+    terminal: bool,
+    parar: AtomicBool,
+    // Para a linha de progresso e as mensagens de erro não se misturarem
+    saida: Mutex<()>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+static PROGRESSO: OnceCell<Progresso> = OnceCell::new();
+
+// Começa a mostrar o progresso. Chamado uma vez, depois da busca dos arquivos.
+pub fn inicia(total_arquivos: u64, total_bytes: u64) {
+    let progresso = Progresso {
+        total_arquivos,
+        total_bytes,
+        arquivos_prontos: AtomicU64::new(0),
+        bytes_prontos: AtomicU64::new(0),
+        erros: AtomicU64::new(0),
+        grandes: Mutex::new(BTreeMap::new()),
+        terminal: io::stderr().is_terminal(),
+        parar: AtomicBool::new(false),
+        saida: Mutex::new(()),
+        thread: Mutex::new(None),
+    };
+    if PROGRESSO.set(progresso).is_err() {
+        return;
+    }
+    let handle = thread::Builder::new()
+        .name("hush-progress".to_string())
+        .spawn(mostra_progresso)
+        .expect("Failed to spawn progress thread");
+    if let Some(progresso) = PROGRESSO.get() {
+        *progresso.thread.lock().unwrap() = Some(handle);
+    }
+}
+
+// Total de bytes de uma lista de arquivos, para o inicia
+pub fn soma_tamanhos(files: &[String]) -> u64 {
+    files
+        .iter()
+        .filter_map(|file| fs::metadata(file).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+// Bloco de um arquivo grande hasheado
+pub fn bloco_pronto(file_path: &str, bytes: u64) {
+    let Some(progresso) = PROGRESSO.get() else {
+        return;
+    };
+    progresso.bytes_prontos.fetch_add(bytes, Ordering::Relaxed);
+    let mut grandes = progresso.grandes.lock().unwrap();
+    grandes
+        .entry(file_path.to_string())
+        .or_insert_with(|| (tamanho(file_path), 0))
+        .1 += bytes;
+}
+
+// Arquivo terminado (com hash ou com erro). Os bytes que faltaram contam como feitos.
+pub fn arquivo_pronto(file_path: &str) {
+    let Some(progresso) = PROGRESSO.get() else {
+        return;
+    };
+    let restante = match progresso.grandes.lock().unwrap().remove(file_path) {
+        Some((tamanho, feito)) => tamanho.saturating_sub(feito),
+        None => tamanho(file_path),
+    };
+    progresso
+        .bytes_prontos
+        .fetch_add(restante, Ordering::Relaxed);
+    progresso.arquivos_prontos.fetch_add(1, Ordering::Relaxed);
+}
+
+// Mensagem de erro no stderr, contada no progresso, sem quebrar a linha de progresso
+pub fn erro(mensagem: &str) {
+    match PROGRESSO.get() {
+        Some(progresso) => {
+            progresso.erros.fetch_add(1, Ordering::Relaxed);
+            let _saida = progresso.saida.lock().unwrap();
+            if progresso.terminal {
+                eprint!("\r\x1b[K");
+            }
+            eprintln!("{}", mensagem);
+        }
+        None => eprintln!("{}", mensagem),
+    }
+}
+
+// Para a thread do progresso e mostra a última linha
+pub fn termina() {
+    let Some(progresso) = PROGRESSO.get() else {
+        return;
+    };
+    progresso.parar.store(true, Ordering::Relaxed);
+    if let Some(handle) = progresso.thread.lock().unwrap().take() {
+        let _ = handle.join();
+    }
+}
+
+fn tamanho(file_path: &str) -> u64 {
+    fs::metadata(file_path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0)
+}
+
+fn mostra_progresso() {
+    let progresso = PROGRESSO.get().expect("progress not started");
+    let inicio = Instant::now();
+    let intervalo = if progresso.terminal {
+        INTERVALO_TERMINAL
+    } else {
+        INTERVALO_LOG
+    };
+    let mut amostras: VecDeque<(Instant, u64)> = VecDeque::new();
+    amostras.push_back((inicio, 0));
+    let mut ultima_linha = inicio;
+
+    loop {
+        thread::sleep(INTERVALO_TERMINAL);
+        let parar = progresso.parar.load(Ordering::Relaxed);
+        let agora = Instant::now();
+        if !parar && agora.duration_since(ultima_linha) < intervalo {
+            continue;
+        }
+        ultima_linha = agora;
+
+        let bytes = progresso.bytes_prontos.load(Ordering::Relaxed);
+        amostras.push_back((agora, bytes));
+        while amostras.len() > 2 && agora.duration_since(amostras[1].0) >= JANELA_TAXA {
+            amostras.pop_front();
+        }
+        // Na última linha, a média do trabalho todo
+        let (tempo_antigo, bytes_antigo) = if parar { (inicio, 0) } else { amostras[0] };
+        let segundos = agora.duration_since(tempo_antigo).as_secs_f64();
+        let taxa = if segundos > 0.0 {
+            bytes.saturating_sub(bytes_antigo) as f64 / segundos
+        } else {
+            0.0
+        };
+        let linha = linha_progresso(progresso, bytes, taxa, agora.duration_since(inicio), parar);
+
+        let _saida = progresso.saida.lock().unwrap();
+        if progresso.terminal {
+            eprint!("\r\x1b[K{}", linha);
+            if parar {
+                eprintln!();
+            }
+            let _ = io::stderr().flush();
+        } else {
+            eprintln!("Progress: {}", linha);
+        }
+        if parar {
+            break;
+        }
+    }
+}
+
+fn linha_progresso(
+    progresso: &Progresso,
+    bytes: u64,
+    taxa: f64,
+    decorrido: Duration,
+    terminou: bool,
+) -> String {
+    let percentual = if progresso.total_bytes > 0 {
+        bytes as f64 * 100.0 / progresso.total_bytes as f64
+    } else if progresso.total_arquivos > 0 {
+        progresso.arquivos_prontos.load(Ordering::Relaxed) as f64 * 100.0
+            / progresso.total_arquivos as f64
+    } else {
+        100.0
+    };
+    let mut linha = format!(
+        "{:5.1}% {}/{} {}/{} files {}/s",
+        percentual.min(100.0),
+        formata_bytes(bytes),
+        formata_bytes(progresso.total_bytes),
+        progresso.arquivos_prontos.load(Ordering::Relaxed),
+        progresso.total_arquivos,
+        formata_bytes(taxa as u64)
+    );
+    if terminou {
+        linha.push_str(&format!(" elapsed {}", formata_tempo(decorrido)));
+    } else if taxa > 0.0 {
+        let restante = progresso.total_bytes.saturating_sub(bytes) as f64 / taxa;
+        linha.push_str(&format!(
+            " ETA {}",
+            formata_tempo(Duration::from_secs_f64(restante))
+        ));
+    } else {
+        linha.push_str(" ETA --:--:--");
+    }
+    linha.push_str(&format!(
+        " errors {}",
+        progresso.erros.load(Ordering::Relaxed)
+    ));
+
+    // O arquivo grande mais longe de terminar
+    let grandes = progresso.grandes.lock().unwrap();
+    if let Some((caminho, (tamanho, feito))) = grandes
+        .iter()
+        .max_by_key(|(_, (tamanho, feito))| tamanho.saturating_sub(*feito))
+    {
+        let nome = Path::new(caminho)
+            .file_name()
+            .map(|nome| nome.to_string_lossy().to_string())
+            .unwrap_or_else(|| caminho.clone());
+        // Nome longo: fica o fim, que costuma ter a extensão e o número da parte
+        let n_chars = nome.chars().count();
+        let nome = if n_chars > 40 {
+            format!("...{}", nome.chars().skip(n_chars - 37).collect::<String>())
+        } else {
+            nome
+        };
+        linha.push_str(&format!(
+            " | {} {:.0}%",
+            nome,
+            *feito as f64 * 100.0 / (*tamanho).max(1) as f64
+        ));
+        if grandes.len() > 1 {
+            linha.push_str(&format!(" (+{} big)", grandes.len() - 1));
+        }
+    }
+    linha
+}
+
+// Em KB, MB, GB e TB de 1024, como o --blocksize
+pub fn formata_bytes(bytes: u64) -> String {
+    let unidades = ["B", "KB", "MB", "GB", "TB"];
+    let mut valor = bytes as f64;
+    let mut unidade = 0;
+    while valor >= 1024.0 && unidade < unidades.len() - 1 {
+        valor /= 1024.0;
+        unidade += 1;
+    }
+    if unidade == 0 {
+        format!("{}B", bytes)
+    } else {
+        format!("{:.1}{}", valor, unidades[unidade])
+    }
+}
+
+pub fn formata_tempo(duracao: Duration) -> String {
+    let segundos = duracao.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}",
+        segundos / 3600,
+        (segundos / 60) % 60,
+        segundos % 60
+    )
+}
@@ -0,0 +1,468 @@
+// Relatório do perito (hush report): texto ou HTML, para anexar ao laudo.
+//
+// Junta, de um manifest:
+//   - os dados do caso (--case_file e --case ..., ou os do log de auditoria);
+//   - o manifest: sha256 (da forma canônica e do arquivo), entradas, algoritmos e blocksize,
+//     MAC, assinatura e carimbo de tempo (conferidos quando vêm as chaves e certificados);
+//   - do log de auditoria (--audit_log), as execuções de gen e check deste manifest (as com o
+//     mesmo manifest_sha256): quem, onde, quando, versão, totais, resultado e falhas;
+//   - dos eventos de um check (--check_events, o arquivo do --events jsonl), o resumo e as falhas.
+// O relatório é montado em seções (campos e uma lista) e depois escrito no formato pedido.
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::assinatura;
+use crate::auditoria;
+use crate::carimbo;
+use crate::caso::Caso;
+use crate::functions::parse_line;
+use crate::mac;
+use crate::progresso::{formata_bytes, formata_tempo};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Formato {
+    Texto,
+    Html,
+}
+
+impl Formato {
+    pub fn parse(valor: &str) -> Result<Formato, Box<dyn Error>> {
+        match valor.to_lowercase().as_str() {
+            "text" | "txt" => Ok(Formato::Texto),
+            "html" => Ok(Formato::Html),
+            _ => Err(format!("unknown report format: {} (use text or html)", valor).into()),
+        }
+    }
+}
+
+pub struct OpcoesRelatorio {
+    pub formato: Formato,
+    pub caso: Caso,
+    pub audit_log: Option<String>,
+    pub eventos_check: Option<String>,
+    pub chaves_confiaveis: Option<String>,
+    pub caminho_sig: String,
+    pub certificados_tsa: Option<String>,
+    pub caminho_tsr: String,
+    // Com a chave, o MAC é conferido (mac::configura_check já chamado)
+    pub com_chave_mac: bool,
+}
+
+struct Secao {
+    titulo: String,
+    campos: Vec<(String, String)>,
+    titulo_lista: String,
+    lista: Vec<String>,
+}
+
+impl Secao {
+    fn nova(titulo: &str) -> Secao {
+        Secao {
+            titulo: titulo.to_string(),
+            campos: Vec::new(),
+            titulo_lista: String::new(),
+            lista: Vec::new(),
+        }
+    }
+
+    fn campo(&mut self, nome: &str, valor: impl ToString) {
+        self.campos.push((nome.to_string(), valor.to_string()));
+    }
+}
+
+pub fn gera(manifest: &str, opcoes: &OpcoesRelatorio) -> Result<String, Box<dyn Error>> {
+    let mut caso = opcoes.caso.clone();
+    let mut secoes = Vec::new();
+
+    let (secao_manifest, resumo) = secao_manifest(manifest, opcoes)?;
+    secoes.push(secao_manifest);
+    if let Some(audit_log) = &opcoes.audit_log {
+        let (execucoes, caso_log) = secoes_auditoria(audit_log, &resumo)?;
+        if let Some(caso_log) = caso_log {
+            caso.completa(&caso_log);
+        }
+        secoes.extend(execucoes);
+    }
+    if let Some(eventos_check) = &opcoes.eventos_check {
+        secoes.push(secao_eventos(eventos_check)?);
+    }
+
+    let mut secao_caso = Secao::nova("Case");
+    for (nome, valor) in caso.campos() {
+        if let Some(valor) = valor {
+            secao_caso.campo(nome, valor);
+        }
+    }
+    if caso.vazio() {
+        secao_caso.campo(
+            "Case",
+            "not informed (use --case_file or --case, --examiner, --evidence)",
+        );
+    }
+    let agora = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    secao_caso.campo("Report date", auditoria::hora_utc(agora.as_millis() as u64));
+    secao_caso.campo("Tool", format!("hush {}", env!("CARGO_PKG_VERSION")));
+    secoes.insert(0, secao_caso);
+
+    Ok(match opcoes.formato {
+        Formato::Texto => escreve_texto(&secoes),
+        Formato::Html => escreve_html(&secoes),
+    })
+}
+
+// O manifest, com o sha256 da forma canônica (o que liga o log de auditoria ao manifest)
+fn secao_manifest(
+    manifest: &str,
+    opcoes: &OpcoesRelatorio,
+) -> Result<(Secao, String), Box<dyn Error>> {
+    let bytes = fs::read(manifest).map_err(|e| format!("{}: {}", manifest, e))?;
+    let texto = String::from_utf8_lossy(&bytes);
+    let resumo = hex::encode(Sha256::digest(assinatura::canonico(manifest)?));
+
+    let mut n_entradas = 0;
+    let mut invalidas = Vec::new();
+    let mut cabecalho_mac = None;
+    // "hsha256, blocksize 50MB" -> arquivos
+    let mut algoritmos: BTreeMap<String, u64> = BTreeMap::new();
+    for (n_linha, linha) in texto.lines().enumerate() {
+        if linha.trim().is_empty() {
+            continue;
+        }
+        if linha.starts_with('#') {
+            if let Some(cabecalho) = linha.strip_prefix("#hush-mac ") {
+                cabecalho_mac = Some(cabecalho.to_string());
+            } else if !mac::linha_do_hush(linha) {
+                invalidas.push(format!(
+                    "line {}: not a hush line (starts with '#')",
+                    n_linha + 1
+                ));
+            }
+            continue;
+        }
+        match parse_line(linha) {
+            Ok(entrada) => {
+                n_entradas += 1;
+                let nome = match entrada.alg_hash.split_once('-') {
+                    Some((alg, blocksize)) => {
+                        format!("{}, blocksize {}", alg.to_lowercase(), blocksize)
+                    }
+                    None => entrada.alg_hash.to_lowercase(),
+                };
+                *algoritmos.entry(nome).or_default() += 1;
+            }
+            Err(e) => invalidas.push(format!("line {}: {}", n_linha + 1, e)),
+        }
+    }
+
+    let mut secao = Secao::nova("Hash file");
+    secao.campo("File", caminho_absoluto(manifest));
+    secao.campo("Size", format!("{} bytes", bytes.len()));
+    secao.campo("SHA-256 (canonical)", &resumo);
+    secao.campo("SHA-256 (file)", hex::encode(Sha256::digest(&bytes)));
+    secao.campo("Entries", n_entradas);
+    let algoritmos: Vec<String> = algoritmos
+        .iter()
+        .map(|(nome, n)| format!("{}: {} files", nome, n))
+        .collect();
+    secao.campo("Algorithms", algoritmos.join("; "));
+
+    let mac = match (cabecalho_mac, opcoes.com_chave_mac) {
+        (None, _) => "none".to_string(),
+        (Some(cabecalho), false) => format!(
+            "{}, not verified (use --mac_key_file or --mac_key_env)",
+            cabecalho
+        ),
+        (Some(cabecalho), true) => {
+            let problemas = mac::confere_manifest(manifest)?;
+            if problemas.is_empty() {
+                format!("{}, good", cabecalho)
+            } else {
+                format!("{}, FAILED: {}", cabecalho, problemas.join("; "))
+            }
+        }
+    };
+    secao.campo("MAC", mac);
+
+    let assinatura = match &opcoes.chaves_confiaveis {
+        Some(chaves) => match assinatura::confere(manifest, &opcoes.caminho_sig, chaves) {
+            Ok(chave) => format!("good signature by {}", chave),
+            Err(e) => format!("FAILED: {}", e),
+        },
+        None if Path::new(&opcoes.caminho_sig).exists() => {
+            format!("{}, not verified (use --trusted_keys)", opcoes.caminho_sig)
+        }
+        None => "none".to_string(),
+    };
+    secao.campo("Signature", assinatura);
+
+    let carimbo = if carimbo::existe(&opcoes.caminho_tsr) {
+        match carimbo::confere(
+            manifest,
+            &opcoes.caminho_tsr,
+            opcoes.certificados_tsa.as_deref(),
+        ) {
+            Ok(carimbo) if carimbo.confiavel => format!(
+                "{} by {}, serial {}, good",
+                carimbo.hora, carimbo.tsa, carimbo.serial
+            ),
+            Ok(carimbo) => format!(
+                "{} by {}, serial {}, TSA not verified (use --tsa_cert)",
+                carimbo.hora, carimbo.tsa, carimbo.serial
+            ),
+            Err(e) => format!("FAILED: {}", e),
+        }
+    } else if opcoes.certificados_tsa.is_some() {
+        format!("FAILED: no timestamp ({})", opcoes.caminho_tsr)
+    } else {
+        "none".to_string()
+    };
+    secao.campo("Timestamp", carimbo);
+
+    if !invalidas.is_empty() {
+        secao.titulo_lista = "Invalid lines".to_string();
+        secao.lista = invalidas;
+    }
+    Ok((secao, resumo))
+}
+
+// Uma seção para o log e uma para cada execução deste manifest. Volta também o caso: os dados
+// das execuções, os da mais nova primeiro.
+fn secoes_auditoria(
+    audit_log: &str,
+    resumo: &str,
+) -> Result<(Vec<Secao>, Option<Caso>), Box<dyn Error>> {
+    let verificacao = auditoria::verifica(audit_log, None)?;
+    let texto = fs::read_to_string(audit_log)?;
+    let entradas: Vec<Value> = texto
+        .lines()
+        .filter_map(|linha| serde_json::from_str::<Value>(linha).ok())
+        .filter(|entrada| entrada["manifest_sha256"].as_str() == Some(resumo))
+        .collect();
+
+    let mut secao_log = Secao::nova("Audit log");
+    secao_log.campo("File", caminho_absoluto(audit_log));
+    secao_log.campo("Entries", verificacao.n_entradas);
+    secao_log.campo("Entries of this hash file", entradas.len());
+    secao_log.campo("Head", &verificacao.head);
+    if verificacao.problemas.is_empty() {
+        secao_log.campo("Chain", "unbroken");
+    } else {
+        secao_log.campo(
+            "Chain",
+            format!("BROKEN: {} problems", verificacao.problemas.len()),
+        );
+        secao_log.titulo_lista = "Problems".to_string();
+        secao_log.lista = verificacao.problemas;
+    }
+
+    let mut secoes = vec![secao_log];
+    let mut caso = Caso::default();
+    for entrada in &entradas {
+        let comando = entrada["command"].as_str().unwrap_or("?");
+        let titulo = match comando {
+            "gen" => "Generation".to_string(),
+            "check" => "Check".to_string(),
+            outro => outro.to_string(),
+        };
+        let mut secao = Secao::nova(&format!("{} (audit entry {})", titulo, entrada["seq"]));
+        secao.campo("Time", texto_de(&entrada["time"]));
+        secao.campo(
+            "User",
+            format!(
+                "{}@{}",
+                texto_de(&entrada["user"]),
+                texto_de(&entrada["host"])
+            ),
+        );
+        secao.campo("Tool", format!("hush {}", texto_de(&entrada["hush"])));
+        secao.campo("Path", texto_de(&entrada["root"]));
+        if let Some(manifest) = entrada["manifest"].as_str() {
+            secao.campo("Hash file", manifest);
+        }
+        if let Some(blocksize) = entrada["args"]["block_size_str"].as_str() {
+            if comando == "gen" {
+                secao.campo("Blocksize", blocksize);
+            }
+        }
+        campos_totais(&mut secao, &entrada["totals"]);
+        secao.campo("Result", texto_de(&entrada["result"]));
+        if let Some(argv) = entrada["argv"].as_array() {
+            let argv: Vec<String> = argv.iter().map(texto_de).collect();
+            secao.campo("Command line", argv.join(" "));
+        }
+        if let Some(falhas) = entrada["failures"].as_array() {
+            secao.titulo_lista = "Failures".to_string();
+            secao.lista = falhas
+                .iter()
+                .map(|falha| texto_de(&falha["message"]))
+                .collect();
+        }
+        if let Ok(mut caso_entrada) = serde_json::from_value::<Caso>(entrada["case"].clone()) {
+            caso_entrada.completa(&caso);
+            caso = caso_entrada;
+        }
+        secoes.push(secao);
+    }
+    Ok((secoes, (!caso.vazio()).then_some(caso)))
+}
+
+// O check com --events jsonl File: resumo e erros
+fn secao_eventos(caminho: &str) -> Result<Secao, Box<dyn Error>> {
+    let texto = fs::read_to_string(caminho).map_err(|e| format!("{}: {}", caminho, e))?;
+    let mut secao = Secao::nova("Check (events)");
+    secao.campo("File", caminho_absoluto(caminho));
+    let mut resumo = None;
+    let mut falhas = Vec::new();
+    for (n_linha, linha) in texto.lines().enumerate() {
+        if linha.trim().is_empty() {
+            continue;
+        }
+        let evento: Value = serde_json::from_str(linha)
+            .map_err(|e| format!("{}: line {}: {}", caminho, n_linha + 1, e))?;
+        match evento["event"].as_str() {
+            Some("run_start") => {
+                if evento["command"].as_str() != Some("check") {
+                    return Err(format!(
+                        "{}: events of {}, not of a check",
+                        caminho, evento["command"]
+                    )
+                    .into());
+                }
+                secao.campo("Tool", format!("hush {}", texto_de(&evento["hush"])));
+                secao.campo(
+                    "Time",
+                    auditoria::hora_utc(evento["time"].as_u64().unwrap_or(0)),
+                );
+            }
+            Some("walk_start") => secao.campo("Path", texto_de(&evento["root"])),
+            Some("error") => falhas.push(texto_de(&evento["message"])),
+            Some("summary") => resumo = Some(evento),
+            _ => {}
+        }
+    }
+    match resumo {
+        Some(resumo) => {
+            campos_totais(&mut secao, &resumo);
+            secao.campo("Result", texto_de(&resumo["result"]));
+        }
+        None => secao.campo("Result", "no summary: check did not finish"),
+    }
+    secao.titulo_lista = "Failures".to_string();
+    secao.lista = falhas;
+    Ok(secao)
+}
+
+fn campos_totais(secao: &mut Secao, totais: &Value) {
+    if let Some(files) = totais["files"].as_u64() {
+        secao.campo("Files", files);
+    }
+    if let Some(files_done) = totais["files_done"].as_u64() {
+        secao.campo("Files hashed", files_done);
+    }
+    if let Some(bytes) = totais["bytes"].as_u64() {
+        secao.campo("Bytes", format!("{} ({})", bytes, formata_bytes(bytes)));
+    }
+    if let Some(errors) = totais["errors"].as_u64() {
+        secao.campo("Errors", errors);
+    }
+    if let Some(elapsed_ms) = totais["elapsed_ms"].as_u64() {
+        secao.campo("Elapsed", formata_tempo(Duration::from_millis(elapsed_ms)));
+    }
+}
+
+fn texto_de(valor: &Value) -> String {
+    match valor {
+        Value::String(texto) => texto.clone(),
+        Value::Null => String::new(),
+        outro => outro.to_string(),
+    }
+}
+
+fn caminho_absoluto(caminho: &str) -> String {
+    fs::canonicalize(caminho)
+        .map(|caminho| caminho.display().to_string())
+        .unwrap_or_else(|_| caminho.to_string())
+}
+
+fn escreve_texto(secoes: &[Secao]) -> String {
+    let titulo = "Hash report";
+    let mut saida = format!("{}\n{}\n", titulo, "=".repeat(titulo.len()));
+    for secao in secoes {
+        saida.push_str(&format!(
+            "\n{}\n{}\n",
+            secao.titulo,
+            "-".repeat(secao.titulo.chars().count())
+        ));
+        let largura = secao
+            .campos
+            .iter()
+            .map(|(nome, _)| nome.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (nome, valor) in &secao.campos {
+            saida.push_str(&format!("{:<largura$} : {}\n", nome, valor));
+        }
+        if !secao.titulo_lista.is_empty() {
+            saida.push_str(&format!("{}: {}\n", secao.titulo_lista, secao.lista.len()));
+            for item in &secao.lista {
+                saida.push_str(&format!("  - {}\n", item));
+            }
+        }
+    }
+    saida
+}
+
+fn escreve_html(secoes: &[Secao]) -> String {
+    let mut saida = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Hash report</title>\n\
+         <style>\nbody { font-family: sans-serif; margin: 2em; }\n\
+         table { border-collapse: collapse; margin-bottom: 1em; }\n\
+         th, td { border: 1px solid #999; padding: 4px 8px; text-align: left; vertical-align: top; }\n\
+         th { background: #eee; white-space: nowrap; }\n\
+         td { font-family: monospace; word-break: break-all; }\n\
+         </style>\n</head>\n<body>\n<h1>Hash report</h1>\n",
+    );
+    for secao in secoes {
+        saida.push_str(&format!("<h2>{}</h2>\n<table>\n", escapa(&secao.titulo)));
+        for (nome, valor) in &secao.campos {
+            saida.push_str(&format!(
+                "<tr><th>{}</th><td>{}</td></tr>\n",
+                escapa(nome),
+                escapa(valor)
+            ));
+        }
+        saida.push_str("</table>\n");
+        if !secao.titulo_lista.is_empty() {
+            saida.push_str(&format!(
+                "<h3>{}: {}</h3>\n",
+                escapa(&secao.titulo_lista),
+                secao.lista.len()
+            ));
+            if !secao.lista.is_empty() {
+                saida.push_str("<ul>\n");
+                for item in &secao.lista {
+                    saida.push_str(&format!("<li>{}</li>\n", escapa(item)));
+                }
+                saida.push_str("</ul>\n");
+            }
+        }
+    }
+    saida.push_str("</body>\n</html>\n");
+    saida
+}
+
+fn escapa(texto: &str) -> String {
+    texto
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
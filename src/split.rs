@@ -0,0 +1,647 @@
+// Split de um arquivo grande em partes e rebuild verificado.
+//
+// O split escreve, junto das partes, um manifest (<nome>.hush-split.json) com o nome e o
+// tamanho do original, as partes (nome, offset, tamanho, sha256) e o hush do arquivo todo.
+// As partes são os blocos do hsha256 (calcular_blocos), escritas e hasheadas em paralelo na
+// mesma passada: o sha256 de cada parte é o hash do bloco e o hush sai das partes, sem ler o
+// arquivo de novo.
+// O rebuild usa só o manifest: junta as partes na ordem, confere cada uma e o arquivo final,
+// e diz exatamente qual parte falta ou está corrompida.
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::armazena::{Armazenamento, Embalagem, EscritaParte, Segredo};
+use crate::construcao::Construcao;
+use crate::functions::{divide_hsha256, hash_hush, ModoLeitura};
+use crate::paridade::{self, escreve_em, GrupoParidade};
+
+pub const SUFIXO_MANIFEST: &str = ".hush-split.json";
+const FORMATO_MANIFEST: &str = "hush-split";
+const VERSAO_MANIFEST: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestSplit {
+    pub format: String,
+    pub version: u32,
+    // Nome do arquivo original, sem o diretório
+    pub name: String,
+    pub size: u64,
+    pub part_size: u64,
+    // Nomes das partes: <part_prefix><índice com index_digits dígitos>
+    #[serde(default)]
+    pub part_prefix: String,
+    #[serde(default)]
+    pub index_digits: usize,
+    // Hush do arquivo todo, como no gen: sha256 até o blocksize, hsha256-<blocksize> acima (v1);
+    // hsha256v2-<blocksize> sempre (v2)
+    pub hush_alg: String,
+    pub hush: String,
+    pub parts: Vec<ParteSplit>,
+    // Partes de paridade (--parity), por grupo de partes de dados
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parity: Vec<GrupoParidade>,
+    // Compressão e cifragem das partes (--compress, --encrypt)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage: Option<Armazenamento>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ParteSplit {
+    pub index: u64,
+    pub name: String,
+    pub offset: u64,
+    pub size: u64,
+    pub sha256: String,
+    // Com --compress ou --encrypt: tamanho e sha256 do arquivo gravado (size e sha256 são do texto)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stored_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stored_sha256: Option<String>,
+}
+
+impl ParteSplit {
+    pub fn tamanho_guardado(&self) -> u64 {
+        self.stored_size.unwrap_or(self.size)
+    }
+
+    pub fn sha256_guardado(&self) -> &str {
+        self.stored_sha256.as_deref().unwrap_or(&self.sha256)
+    }
+}
+
+// Opções do split além do tamanho da parte
+#[derive(Debug, Default)]
+pub struct OpcoesSplit {
+    // --parts N: tamanho da parte = tamanho do arquivo / N, arredondado para cima
+    pub n_partes: Option<u64>,
+    // --prefix: início do nome das partes. Padrão: "<nome do arquivo>_"
+    pub prefixo: Option<String>,
+    // --force: escreve por cima de partes e manifest que já existem
+    pub forca: bool,
+    // --parity N
+    pub n_paridade: usize,
+    // --compress zstd[:nível]
+    pub nivel_zstd: Option<i32>,
+    // --encrypt, com a frase secreta ou o arquivo de chave
+    pub segredo: Option<Segredo>,
+    // --hash_alg: construção do hush do arquivo todo, como no gen
+    pub construcao: Construcao,
+}
+
+// Dígitos do maior índice: com zeros à esquerda, o ls lista as partes na ordem
+fn digitos_indice(n_partes: u64) -> usize {
+    n_partes.saturating_sub(1).to_string().len()
+}
+
+pub fn nome_parte(prefixo: &str, digitos: usize, index: u64) -> String {
+    format!("{}{:0digitos$}", prefixo, index, digitos = digitos)
+}
+
+// O nome das partes fica no diretório de destino: nada de caminho no prefixo
+fn valida_prefixo(prefixo: &str) -> Result<(), Box<dyn Error>> {
+    if prefixo.is_empty()
+        || prefixo == "."
+        || prefixo == ".."
+        || prefixo.contains('/')
+        || prefixo.contains('\\')
+    {
+        return Err(format!("invalid --prefix: {}", prefixo).into());
+    }
+    Ok(())
+}
+
+// Nome de parte do manifest: só um nome de arquivo, dentro do diretório das partes
+fn nome_simples(nome: &str) -> bool {
+    !nome.is_empty()
+        && nome != "."
+        && nome != ".."
+        && Path::new(nome).file_name().and_then(|n| n.to_str()) == Some(nome)
+}
+
+fn caminho_manifest(dir_destino: &str, nome: &str) -> PathBuf {
+    Path::new(dir_destino).join(format!("{}{}", nome, SUFIXO_MANIFEST))
+}
+
+// Divide o arquivo em partes de `chunk_size` bytes, escritas em paralelo pelo pool de hash nos
+// limites dos blocos do hsha256, e escreve o manifest. Retorna o manifest.
+pub fn split(
+    file: &str,
+    dir_destino: &str,
+    buffer_size: usize,
+    chunk_size: usize,
+    block_size_str: &str,
+    n_max_concur: u32,
+    opcoes: &OpcoesSplit,
+) -> Result<ManifestSplit, Box<dyn Error>> {
+    let n_paridade = opcoes.n_paridade;
+    if n_paridade > 0 {
+        paridade::valida_n_paridade(n_paridade)?;
+    }
+    let nome = Path::new(file)
+        .file_name()
+        .and_then(|nome| nome.to_str())
+        .ok_or_else(|| format!("invalid file name: {}", file))?
+        .to_string();
+    let tamanho = fs::metadata(file)?.len();
+    // Com --parts, a parte (e o bloco do hush) sai do número de partes
+    let (chunk_size, block_size_str) = match opcoes.n_partes {
+        Some(0) => return Err("--parts must be at least 1".into()),
+        Some(n_partes) => {
+            let chunk_size = tamanho.div_ceil(n_partes).max(1);
+            (chunk_size, format!("{}B", chunk_size))
+        }
+        None => (chunk_size.max(1) as u64, block_size_str.to_string()),
+    };
+    let n_partes = tamanho.div_ceil(chunk_size).max(1);
+    let prefixo = opcoes
+        .prefixo
+        .clone()
+        .unwrap_or_else(|| format!("{}_", nome));
+    valida_prefixo(&prefixo)?;
+    let digitos = digitos_indice(n_partes);
+
+    let path_dir = Path::new(dir_destino);
+    if !path_dir.exists() {
+        // Cria o diretório, incluindo diretórios intermediários, se necessário
+        fs::create_dir_all(path_dir)?;
+        info!("Dir created: {}", dir_destino);
+    }
+
+    // Não escreve por cima de partes de outro split, a não ser com --force
+    let mut nomes: Vec<String> = (0..n_partes)
+        .map(|index| nome_parte(&prefixo, digitos, index))
+        .collect();
+    if n_paridade > 0 {
+        nomes.extend(paridade::nomes_paridade(&prefixo, n_partes, n_paridade));
+    }
+    nomes.push(format!("{}{}", nome, SUFIXO_MANIFEST));
+    let existentes: Vec<&String> = nomes
+        .iter()
+        .filter(|nome| path_dir.join(nome).exists())
+        .collect();
+    if !existentes.is_empty() && !opcoes.forca {
+        let mut lista: Vec<String> = existentes
+            .iter()
+            .take(5)
+            .map(|nome| nome.to_string())
+            .collect();
+        if existentes.len() > 5 {
+            lista.push(format!("and {} more", existentes.len() - 5));
+        }
+        return Err(format!(
+            "{} files already exist in {}: {}. Use --force to overwrite",
+            existentes.len(),
+            dir_destino,
+            lista.join(", ")
+        )
+        .into());
+    }
+
+    let (embalagem, armazenamento) = Embalagem::nova(opcoes.nivel_zstd, opcoes.segredo.as_ref())?;
+    let abre_parte = |index: u64| -> io::Result<Box<dyn EscritaParte>> {
+        let file = File::create(path_dir.join(nome_parte(&prefixo, digitos, index)))?;
+        if embalagem.ativa() {
+            embalagem.escrita(file, index)
+        } else {
+            Ok(Box::new(file))
+        }
+    };
+    let (blocos, hush) = divide_hsha256(
+        file,
+        &abre_parte,
+        buffer_size,
+        chunk_size as usize,
+        n_max_concur,
+        opcoes.construcao,
+    )?;
+    let mut partes: Vec<ParteSplit> = blocos
+        .into_iter()
+        .map(|(bloco, guardado)| ParteSplit {
+            index: bloco.n_bloco,
+            name: nome_parte(&prefixo, digitos, bloco.n_bloco),
+            offset: bloco.inicio_bloco,
+            size: bloco.fim_bloco - bloco.inicio_bloco,
+            sha256: bloco.hash_bloco,
+            stored_size: guardado.as_ref().map(|(tamanho, _)| *tamanho),
+            stored_sha256: guardado.map(|(_, sha256)| sha256),
+        })
+        .collect();
+    // Arquivo vazio também tem uma parte (vazia), para o rebuild recriar o arquivo
+    if partes.is_empty() {
+        let guardado = abre_parte(0)?.termina()?;
+        partes.push(ParteSplit {
+            index: 0,
+            name: nome_parte(&prefixo, digitos, 0),
+            offset: 0,
+            size: 0,
+            sha256: format!("{:x}", Sha256::digest(b"")),
+            stored_size: guardado.as_ref().map(|(tamanho, _)| *tamanho),
+            stored_sha256: guardado.map(|(_, sha256)| sha256),
+        });
+    }
+    let tamanho_partes: u64 = partes.iter().map(|parte| parte.size).sum();
+    if tamanho_partes != tamanho {
+        return Err(format!(
+            "{}: size changed while splitting ({} -> {} bytes)",
+            file, tamanho, tamanho_partes
+        )
+        .into());
+    }
+
+    // Partes do tamanho do bloco: o hush é o hsha256 sobre os hashes das partes
+    let hush_alg = if opcoes.construcao.usa_sha256(tamanho, chunk_size) {
+        String::from("sha256")
+    } else {
+        format!("{}-{}", opcoes.construcao.nome(), block_size_str)
+    };
+
+    let mut manifest = ManifestSplit {
+        format: FORMATO_MANIFEST.to_string(),
+        version: VERSAO_MANIFEST,
+        name: nome.clone(),
+        size: tamanho,
+        part_size: chunk_size,
+        part_prefix: prefixo,
+        index_digits: digitos,
+        hush_alg,
+        hush,
+        parts: partes,
+        parity: Vec::new(),
+        storage: embalagem.ativa().then_some(armazenamento),
+    };
+    if n_paridade > 0 {
+        manifest.parity = paridade::gera_paridade(dir_destino, &manifest, n_paridade)?;
+    }
+    let caminho = caminho_manifest(dir_destino, &nome);
+    fs::write(&caminho, serde_json::to_string_pretty(&manifest)? + "\n")?;
+    eprintln!(
+        "Split: {} parts and {} parity parts of {} in {}, manifest {}",
+        manifest.parts.len(),
+        manifest
+            .parity
+            .iter()
+            .map(|grupo| grupo.parts.len())
+            .sum::<usize>(),
+        nome,
+        dir_destino,
+        caminho.display()
+    );
+    println!(
+        "{} ?{}*./{}",
+        manifest.hush, manifest.hush_alg, manifest.name
+    );
+    Ok(manifest)
+}
+
+// O manifest informado, ou o único *.hush-split.json do diretório das partes
+fn acha_manifest(dir_partes: &str, manifest: Option<&str>) -> Result<PathBuf, Box<dyn Error>> {
+    if let Some(manifest) = manifest {
+        return Ok(PathBuf::from(manifest));
+    }
+    let mut achados: Vec<PathBuf> = fs::read_dir(dir_partes)?
+        .filter_map(|entrada| entrada.ok())
+        .map(|entrada| entrada.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|nome| nome.to_str())
+                .is_some_and(|nome| nome.ends_with(SUFIXO_MANIFEST))
+        })
+        .collect();
+    match achados.len() {
+        0 => Err(format!("no split manifest (*{}) in {}", SUFIXO_MANIFEST, dir_partes).into()),
+        1 => Ok(achados.remove(0)),
+        _ => {
+            achados.sort();
+            let nomes: Vec<String> = achados.iter().map(|p| p.display().to_string()).collect();
+            Err(format!(
+                "more than one split manifest in {}: {}. Choose one with --manifest",
+                dir_partes,
+                nomes.join(", ")
+            )
+            .into())
+        }
+    }
+}
+
+pub fn le_manifest(caminho: &Path) -> Result<ManifestSplit, Box<dyn Error>> {
+    let manifest: ManifestSplit = serde_json::from_str(&fs::read_to_string(caminho)?)
+        .map_err(|e| format!("{}: invalid split manifest: {}", caminho.display(), e))?;
+    if manifest.format != FORMATO_MANIFEST || manifest.version != VERSAO_MANIFEST {
+        return Err(format!(
+            "{}: unsupported split manifest {} version {}",
+            caminho.display(),
+            manifest.format,
+            manifest.version
+        )
+        .into());
+    }
+    // Partes e paridades só com nome de arquivo, e do esquema de nomes do split
+    let paridades = manifest.parity.iter().flat_map(|grupo| grupo.parts.iter());
+    for parte in manifest.parts.iter().chain(paridades) {
+        if !nome_simples(&parte.name) {
+            return Err(format!("{}: invalid part name {}", caminho.display(), parte.name).into());
+        }
+    }
+    if !manifest.part_prefix.is_empty() {
+        for parte in &manifest.parts {
+            let esperado = nome_parte(&manifest.part_prefix, manifest.index_digits, parte.index);
+            if parte.name != esperado {
+                return Err(format!(
+                    "{}: part {} named {}, expected {}",
+                    caminho.display(),
+                    parte.index,
+                    parte.name,
+                    esperado
+                )
+                .into());
+            }
+        }
+    }
+
+    // Partes em sequência, sem buraco nem sobreposição, até o tamanho do original
+    let mut offset = 0;
+    for (index, parte) in manifest.parts.iter().enumerate() {
+        if parte.index != index as u64 || parte.offset != offset {
+            return Err(format!(
+                "{}: part {} out of sequence (index {}, offset {})",
+                caminho.display(),
+                parte.name,
+                parte.index,
+                parte.offset
+            )
+            .into());
+        }
+        offset += parte.size;
+    }
+    if offset != manifest.size {
+        return Err(format!(
+            "{}: parts sum {} bytes, original has {}",
+            caminho.display(),
+            offset,
+            manifest.size
+        )
+        .into());
+    }
+    // Mesmo o arquivo vazio tem uma parte
+    if manifest.parts.is_empty() {
+        return Err(format!("{}: no parts", caminho.display()).into());
+    }
+    paridade::valida_grupos(&manifest).map_err(|e| format!("{}: {}", caminho.display(), e))?;
+    Ok(manifest)
+}
+
+// Copia a parte para a saída, na posição dela, com o sha256 do que foi lido.
+// Retorna (bytes, sha256). Parte maior que o esperado não passa do seu pedaço da saída.
+fn copia_parte(
+    parte: &ParteSplit,
+    caminho: &Path,
+    saida: &File,
+    buffer: &mut [u8],
+) -> io::Result<(u64, String)> {
+    let mut part_file = File::open(caminho)?;
+    let mut hasher = Sha256::new();
+    let mut total = 0;
+    loop {
+        let bytes_read = part_file.read(buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        let cabe = parte.size.saturating_sub(total).min(bytes_read as u64) as usize;
+        escreve_em(saida, &buffer[..cabe], parte.offset + total)?;
+        hasher.update(&buffer[..bytes_read]);
+        total += bytes_read as u64;
+    }
+    Ok((total, format!("{:x}", hasher.finalize())))
+}
+
+// Parte comprimida ou cifrada: o texto vai para a saída, na posição dela.
+// Retorna o problema da parte, se tiver: gravado diferente do manifest, erro ao decifrar ou
+// descomprimir, texto diferente do manifest.
+fn copia_parte_embalada(
+    parte: &ParteSplit,
+    caminho: &Path,
+    saida: &File,
+    embalagem: &Embalagem,
+) -> io::Result<Option<String>> {
+    let mut hasher = Sha256::new();
+    let mut total = 0;
+    let guardado = embalagem.le_parte(caminho, parte.index, &mut |dados| {
+        let cabe = parte.size.saturating_sub(total).min(dados.len() as u64) as usize;
+        escreve_em(saida, &dados[..cabe], parte.offset + total)?;
+        hasher.update(dados);
+        total += dados.len() as u64;
+        Ok(())
+    });
+    let (tamanho_guardado, sha256_guardado) = match guardado {
+        Ok(guardado) => guardado,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            return Ok(Some(format!(
+                "part {} ({}): corrupt, cannot decode: {}",
+                parte.index, parte.name, e
+            )))
+        }
+        Err(e) => return Err(e),
+    };
+    let sha256 = format!("{:x}", hasher.finalize());
+    if tamanho_guardado != parte.tamanho_guardado() || sha256_guardado != parte.sha256_guardado() {
+        return Ok(Some(format!(
+            "part {} ({}): corrupt. Expected stored {} bytes sha256 {}, found {} bytes sha256 {}",
+            parte.index,
+            parte.name,
+            parte.tamanho_guardado(),
+            parte.sha256_guardado(),
+            tamanho_guardado,
+            sha256_guardado
+        )));
+    }
+    if total != parte.size || sha256 != parte.sha256 {
+        return Ok(Some(format!(
+            "part {} ({}): decoded to {} bytes sha256 {}, expected {} bytes sha256 {}",
+            parte.index, parte.name, total, sha256, parte.size, parte.sha256
+        )));
+    }
+    Ok(None)
+}
+
+// Reconstrói o arquivo pelo manifest, conferindo cada parte e o resultado.
+// Com parte faltando ou corrompida, lista todas e não deixa arquivo de saída.
+// Partes comprimidas ou cifradas são desfeitas com o que diz o manifest (e o segredo).
+pub fn rebuild(
+    file: &str,
+    dir_partes: &str,
+    manifest: Option<&str>,
+    segredo: Option<&Segredo>,
+    buffer_size: usize,
+    n_max_concur: u32,
+    modo_leitura: ModoLeitura,
+) -> Result<(), Box<dyn Error>> {
+    let caminho_manifest = acha_manifest(dir_partes, manifest)?;
+    let manifest = le_manifest(&caminho_manifest)?;
+    info!(
+        "rebuild: {} ({} bytes, {} parts) from {}",
+        manifest.name,
+        manifest.size,
+        manifest.parts.len(),
+        caminho_manifest.display()
+    );
+    let embalagem = Embalagem::do_manifest(
+        manifest
+            .storage
+            .as_ref()
+            .unwrap_or(&Armazenamento::default()),
+        segredo,
+    )?;
+
+    // Escreve ao lado e só troca o nome no fim: saída com o nome final é sempre verificada
+    let parcial = format!("{}.partial", file);
+    let saida = File::create(&parcial)?;
+    let mut buffer = vec![0; buffer_size.max(1)];
+    let mut problemas = Vec::new();
+    let mut ruins = Vec::new();
+
+    for parte in &manifest.parts {
+        let caminho = Path::new(dir_partes).join(&parte.name);
+        if embalagem.ativa() {
+            let problema = match copia_parte_embalada(parte, &caminho, &saida, &embalagem) {
+                Ok(None) => {
+                    info!("part {} ({}) ok", parte.index, parte.name);
+                    continue;
+                }
+                Ok(Some(problema)) => problema,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => format!(
+                    "part {} ({}): missing in {}",
+                    parte.index, parte.name, dir_partes
+                ),
+                Err(e) => format!("part {} ({}): read error: {}", parte.index, parte.name, e),
+            };
+            problemas.push(problema);
+            ruins.push(parte.index);
+            continue;
+        }
+        let resultado = copia_parte(parte, &caminho, &saida, &mut buffer);
+        if !matches!(&resultado, Ok((tamanho, sha256)) if *tamanho == parte.size && *sha256 == parte.sha256)
+        {
+            ruins.push(parte.index);
+        }
+        match resultado {
+            Ok((tamanho, sha256)) if tamanho == parte.size && sha256 == parte.sha256 => {
+                info!("part {} ({}) ok", parte.index, parte.name);
+            }
+            Ok((tamanho, sha256)) => problemas.push(format!(
+                "part {} ({}): corrupt. Expected {} bytes sha256 {}, found {} bytes sha256 {}",
+                parte.index, parte.name, parte.size, parte.sha256, tamanho, sha256
+            )),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => problemas.push(format!(
+                "part {} ({}): missing in {}",
+                parte.index, parte.name, dir_partes
+            )),
+            Err(e) => problemas.push(format!(
+                "part {} ({}): read error: {}",
+                parte.index, parte.name, e
+            )),
+        }
+    }
+
+    // As ruins que não voltaram: sem paridade, todas
+    let mut perdidas = ruins.clone();
+
+    // Com paridade, as partes ruins são reconstruídas direto na saída. Partes comprimidas ou
+    // cifradas são reconstruídas como foram gravadas, ao lado da saída, e então desfeitas.
+    if !ruins.is_empty() && !manifest.parity.is_empty() {
+        for problema in &problemas {
+            eprintln!("{}", problema);
+        }
+        let caminho_reconstruida = |parte: &ParteSplit| format!("{}.{}", parcial, parte.name);
+        let mut entrega = |parte: &ParteSplit, offset: u64, dados: &[u8]| -> io::Result<()> {
+            if !embalagem.ativa() {
+                return escreve_em(&saida, dados, parte.offset + offset);
+            }
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(offset == 0)
+                .open(caminho_reconstruida(parte))?;
+            escreve_em(&file, dados, offset)
+        };
+        let (restantes, reconstruidas) =
+            paridade::reconstroi(dir_partes, &manifest, &ruins, &mut entrega)?;
+        problemas = restantes;
+        perdidas.retain(|index| !reconstruidas.contains(index));
+        if embalagem.ativa() {
+            for parte in &manifest.parts {
+                if !ruins.contains(&parte.index) {
+                    continue;
+                }
+                let caminho = caminho_reconstruida(parte);
+                if reconstruidas.contains(&parte.index) {
+                    match copia_parte_embalada(parte, Path::new(&caminho), &saida, &embalagem) {
+                        Ok(None) => {}
+                        Ok(Some(problema)) => {
+                            problemas.push(problema);
+                            perdidas.push(parte.index);
+                        }
+                        Err(e) => {
+                            problemas.push(format!(
+                                "part {} ({}): reconstructed, read error: {}",
+                                parte.index, parte.name, e
+                            ));
+                            perdidas.push(parte.index);
+                        }
+                    }
+                }
+                let _ = fs::remove_file(&caminho);
+            }
+        }
+    }
+
+    if !problemas.is_empty() {
+        drop(saida);
+        let _ = fs::remove_file(&parcial);
+        for problema in &problemas {
+            eprintln!("Error! {}", problema);
+        }
+        return Err(format!(
+            "{} of {} parts missing or corrupt. {} not rebuilt",
+            perdidas.len(),
+            manifest.parts.len(),
+            manifest.name
+        )
+        .into());
+    }
+    saida.set_len(manifest.size)?;
+    saida.sync_all()?;
+    drop(saida);
+
+    // Confere o arquivo escrito, lendo de novo, contra o hush do original
+    let hush = hash_hush(
+        &parcial,
+        &manifest.hush_alg,
+        buffer_size,
+        n_max_concur,
+        false,
+        modo_leitura,
+    )?;
+    if hush != manifest.hush {
+        let _ = fs::remove_file(&parcial);
+        return Err(format!(
+            "rebuilt file does not match: {} expected {}, found {}",
+            manifest.hush_alg, manifest.hush, hush
+        )
+        .into());
+    }
+    fs::rename(&parcial, file)?;
+    eprintln!(
+        "Rebuilt and verified: {} parts, {} bytes -> {}",
+        manifest.parts.len(),
+        manifest.size,
+        file
+    );
+    println!("{} ?{}*./{}", hush, manifest.hush_alg, manifest.name);
+    Ok(())
+}
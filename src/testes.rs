@@ -0,0 +1,36 @@
+// Ajudas dos testes de unidade
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CONTADOR: AtomicUsize = AtomicUsize::new(0);
+
+// Pasta temporária de um teste, apagada quando sai de escopo (também se o teste falha)
+pub struct PastaTeste {
+    caminho: PathBuf,
+}
+
+impl PastaTeste {
+    pub fn nova(nome: &str) -> PastaTeste {
+        let caminho = std::env::temp_dir().join(format!(
+            "hush-test-{}-{}-{}",
+            std::process::id(),
+            CONTADOR.fetch_add(1, Ordering::Relaxed),
+            nome
+        ));
+        let _ = fs::remove_dir_all(&caminho);
+        fs::create_dir_all(&caminho).unwrap();
+        PastaTeste { caminho }
+    }
+
+    pub fn join<P: AsRef<Path>>(&self, nome: P) -> PathBuf {
+        self.caminho.join(nome)
+    }
+}
+
+impl Drop for PastaTeste {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.caminho);
+    }
+}
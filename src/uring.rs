@@ -0,0 +1,397 @@
+// Leitura dos blocos com io_uring (só Linux).
+//
+// Um único anel, compartilhado por todos os arquivos e blocos, atende os pedidos de leitura
+// das threads de hash. Os buffers são registrados no kernel (READ_FIXED) e emprestados à
+// thread que pediu a leitura; quando ela termina de hashear, o buffer volta para o anel.
+// Cada thread pede alguns pedaços do bloco à frente (JANELA) e hasheia na ordem, então o
+// hash de cada ChunkBloco é o mesmo do caminho com read().
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use log::{info, warn};
+use once_cell::sync::OnceCell;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+use crate::inteiro::FatiaInteiro;
+
+// Pedaços pedidos à frente por thread de hash
+const JANELA: usize = 4;
+// Entradas do anel, e também número máximo de buffers registrados
+const PROFUNDIDADE: u32 = 128;
+
+// Resposta de um pedido: o buffer com os bytes lidos (pode ser menos que o pedido)
+pub type RespostaUring = io::Result<BufferUring>;
+
+struct Pedido {
+    file: Arc<File>,
+    offset: u64,
+    len: usize,
+    resposta: Sender<RespostaUring>,
+}
+
+// Memória dos buffers: um bloco só, alocado uma vez e nunca liberado (o serviço é global)
+#[derive(Clone, Copy)]
+struct MemoriaUring {
+    base: *mut u8,
+    tam_buffer: usize,
+}
+// Safety: cada buffer só é acessado por quem o tem emprestado (anel ou thread de hash)
+unsafe impl Send for MemoriaUring {}
+unsafe impl Sync for MemoriaUring {}
+
+pub struct ServicoUring {
+    pedidos: Sender<Pedido>,
+    livres: Sender<usize>,
+    memoria: MemoriaUring,
+}
+
+// Buffer emprestado a uma thread de hash. Volta para o anel quando é descartado.
+pub struct BufferUring {
+    memoria: MemoriaUring,
+    livres: Sender<usize>,
+    index: usize,
+    len: usize,
+}
+
+impl std::ops::Deref for BufferUring {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        // Safety: o buffer está emprestado a este BufferUring até o drop
+        unsafe {
+            std::slice::from_raw_parts(
+                self.memoria.base.add(self.index * self.memoria.tam_buffer),
+                self.len,
+            )
+        }
+    }
+}
+
+impl Drop for BufferUring {
+    fn drop(&mut self) {
+        let _ = self.livres.send(self.index);
+    }
+}
+
+static SERVICO_URING: OnceCell<Option<ServicoUring>> = OnceCell::new();
+
+// Serviço global. O tamanho dos buffers vem da primeira chamada (--buffersize).
+// Retorna None se o io_uring não está disponível (kernel antigo, seccomp, outro SO).
+pub fn servico(buffer_size: usize) -> Option<&'static ServicoUring> {
+    SERVICO_URING
+        .get_or_init(|| match inicia_servico(buffer_size.max(512)) {
+            Ok(servico) => Some(servico),
+            Err(e) => {
+                warn!("io_uring not available, using read: {}", e);
+                eprintln!("io_uring not available, using read: {}", e);
+                None
+            }
+        })
+        .as_ref()
+}
+
+impl ServicoUring {
+    fn pede(&self, file: &Arc<File>, offset: u64, len: usize) -> Receiver<RespostaUring> {
+        let (resposta, receiver) = unbounded();
+        let pedido = Pedido {
+            file: file.clone(),
+            offset,
+            len: len.min(self.memoria.tam_buffer),
+            resposta,
+        };
+        if let Err(erro) = self.pedidos.send(pedido) {
+            let _ = erro
+                .0
+                .resposta
+                .send(Err(io::Error::other("io_uring service stopped")));
+        }
+        receiver
+    }
+
+    pub fn tam_buffer(&self) -> usize {
+        self.memoria.tam_buffer
+    }
+
+    fn buffer(&self, index: usize, len: usize) -> BufferUring {
+        BufferUring {
+            memoria: self.memoria,
+            livres: self.livres.clone(),
+            index,
+            len,
+        }
+    }
+}
+
+// Lê o resto de um pedaço que voltou curto. Retorna false se chegou no fim do arquivo.
+// O descritor é só da thread que chamou, então o seek não atrapalha ninguém.
+// O --max_rate já contou o pedaço inteiro quando ele foi pedido ao anel.
+fn completa_leitura(
+    mut file: &File,
+    hasher: &mut Sha256,
+    inteiro: &mut Option<&mut FatiaInteiro>,
+    offset: u64,
+    mut restante: usize,
+) -> io::Result<bool> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buffer = vec![0; restante.min(1 << 20)];
+    while restante > 0 {
+        let a_ler = restante.min(buffer.len());
+        let n = file.read(&mut buffer[..a_ler])?;
+        if n == 0 {
+            return Ok(false);
+        }
+        hasher.update(&buffer[..n]);
+        if let Some(fatia) = inteiro {
+            fatia.atualiza(&buffer[..n]);
+        }
+        restante -= n;
+    }
+    Ok(true)
+}
+
+// Hash de um intervalo do arquivo lido pelo anel. Mesmo resultado do read(): os pedaços
+// são hasheados na ordem, e se o arquivo acabar antes do fim, hasheia o que foi lido.
+// O `file` deve ser um descritor só da thread que chama. Com `inteiro`, os mesmos pedaços
+// vão também para o sha256 do arquivo todo.
+pub fn hash_intervalo_uring(
+    servico: &ServicoUring,
+    file: &Arc<File>,
+    inicio: u64,
+    fim: u64,
+    mut inteiro: Option<&mut FatiaInteiro>,
+) -> io::Result<String> {
+    let tam_pedaco = servico.tam_buffer() as u64;
+    let mut hasher = Sha256::new();
+    let mut pendentes = std::collections::VecDeque::new();
+    let mut proximo = inicio;
+
+    loop {
+        while pendentes.len() < JANELA && proximo < fim {
+            let len = std::cmp::min(tam_pedaco, fim - proximo) as usize;
+            crate::controle::consome(len);
+            pendentes.push_back((proximo, len, servico.pede(file, proximo, len)));
+            proximo += len as u64;
+        }
+        let (offset, len, receiver) = match pendentes.pop_front() {
+            Some(pendente) => pendente,
+            None => break,
+        };
+        let buffer = receiver
+            .recv()
+            .map_err(|_| io::Error::other("io_uring service stopped"))??;
+        hasher.update(&buffer[..]);
+        if let Some(fatia) = &mut inteiro {
+            fatia.atualiza(&buffer[..]);
+        }
+        let lidos = buffer.len();
+        drop(buffer);
+        if lidos < len
+            && !completa_leitura(
+                file,
+                &mut hasher,
+                &mut inteiro,
+                offset + lidos as u64,
+                len - lidos,
+            )?
+        {
+            // EOF: o arquivo diminuiu. Os pedidos pendentes devolvem os buffers sozinhos.
+            break;
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(target_os = "linux")]
+fn inicia_servico(tam_buffer: usize) -> io::Result<ServicoUring> {
+    use io_uring::IoUring;
+
+    let ring = IoUring::new(PROFUNDIDADE)?;
+    // Os buffers ficam com no máximo metade do limite de bytes em voo, tirada do orçamento
+    // para sempre; a outra metade fica para as leituras que não passam pelo anel
+    let n_buffers = (crate::pool::orcamento().reserva_fixa(PROFUNDIDADE as u64 * tam_buffer as u64)
+        as usize
+        / tam_buffer)
+        .max(1);
+    let memoria_buffers: &'static mut [u8] =
+        Box::leak(vec![0u8; n_buffers * tam_buffer].into_boxed_slice());
+    let memoria = MemoriaUring {
+        base: memoria_buffers.as_mut_ptr(),
+        tam_buffer,
+    };
+
+    // Registrar os buffers evita mapear as páginas a cada leitura. Se o kernel recusar
+    // (ex: limite de memlock), usa READ normal nos mesmos buffers.
+    let iovecs: Vec<libc::iovec> = (0..n_buffers)
+        .map(|index| libc::iovec {
+            iov_base: unsafe { memoria.base.add(index * tam_buffer) } as *mut libc::c_void,
+            iov_len: tam_buffer,
+        })
+        .collect();
+    let registrados = match unsafe { ring.submitter().register_buffers(&iovecs) } {
+        Ok(()) => true,
+        Err(e) => {
+            warn!(
+                "io_uring: register_buffers failed, using plain reads: {}",
+                e
+            );
+            false
+        }
+    };
+    info!(
+        "io_uring: {} buffers of {} bytes, registered:{}",
+        n_buffers, tam_buffer, registrados
+    );
+
+    let (sender_pedidos, receiver_pedidos) = unbounded::<Pedido>();
+    let (sender_livres, receiver_livres) = unbounded::<usize>();
+    for index in 0..n_buffers {
+        sender_livres.send(index).unwrap();
+    }
+    let servico = ServicoUring {
+        pedidos: sender_pedidos,
+        livres: sender_livres,
+        memoria,
+    };
+    let servico_thread = ServicoUring {
+        pedidos: servico.pedidos.clone(),
+        livres: servico.livres.clone(),
+        memoria,
+    };
+
+    std::thread::Builder::new()
+        .name("hush-io_uring".to_string())
+        .spawn(move || {
+            loop_anel(
+                ring,
+                servico_thread,
+                receiver_pedidos,
+                receiver_livres,
+                registrados,
+            )
+        })?;
+    Ok(servico)
+}
+
+#[cfg(target_os = "linux")]
+fn loop_anel(
+    mut ring: io_uring::IoUring,
+    servico: ServicoUring,
+    receiver_pedidos: Receiver<Pedido>,
+    receiver_livres: Receiver<usize>,
+    registrados: bool,
+) {
+    use crossbeam_channel::select;
+    use io_uring::{opcode, types};
+    use std::collections::VecDeque;
+    use std::os::unix::io::AsRawFd;
+
+    // Pedidos na ordem de chegada: quem pediu primeiro recebe buffer primeiro, então o
+    // pedaço que uma thread está esperando nunca fica sem buffer por causa dos seguintes.
+    let mut fila: VecDeque<Pedido> = VecDeque::new();
+    let mut livres: Vec<usize> = Vec::new();
+    // Em voo: user_data = índice do buffer
+    let mut em_voo: Vec<Option<Pedido>> = (0..PROFUNDIDADE).map(|_| None).collect();
+    let mut n_em_voo = 0;
+
+    loop {
+        // Sem nada em voo, espera um pedido novo ou um buffer devolvido
+        if n_em_voo == 0 && (fila.is_empty() || livres.is_empty()) {
+            select! {
+                recv(receiver_pedidos) -> pedido => match pedido {
+                    Ok(pedido) => fila.push_back(pedido),
+                    Err(_) => return,
+                },
+                recv(receiver_livres) -> index => {
+                    if let Ok(index) = index {
+                        livres.push(index);
+                    }
+                },
+            }
+        }
+        fila.extend(receiver_pedidos.try_iter());
+        livres.extend(receiver_livres.try_iter());
+
+        while !fila.is_empty() && !livres.is_empty() {
+            let pedido = fila.pop_front().unwrap();
+            let index = livres.pop().unwrap();
+            let buf = unsafe { servico.memoria.base.add(index * servico.memoria.tam_buffer) };
+            let fd = types::Fd(pedido.file.as_raw_fd());
+            let entrada = if registrados {
+                opcode::ReadFixed::new(fd, buf, pedido.len as u32, index as u16)
+                    .offset(pedido.offset)
+                    .build()
+            } else {
+                opcode::Read::new(fd, buf, pedido.len as u32)
+                    .offset(pedido.offset)
+                    .build()
+            }
+            .user_data(index as u64);
+            // Safety: o buffer e o descritor (Arc<File> no pedido) vivem até a conclusão
+            if unsafe { ring.submission().push(&entrada) }.is_err() {
+                // Anel cheio: não acontece, há um buffer por entrada
+                fila.push_front(pedido);
+                livres.push(index);
+                break;
+            }
+            em_voo[index] = Some(pedido);
+            n_em_voo += 1;
+        }
+
+        if n_em_voo == 0 {
+            continue;
+        }
+        if let Err(e) = ring.submit_and_wait(1) {
+            match e.raw_os_error() {
+                _ if e.kind() == io::ErrorKind::Interrupted => continue,
+                // Falta de recurso no kernel ou fila de conclusões cheia: passa com o tempo (e
+                // com as conclusões lidas abaixo). Espera um pouco para não girar à toa.
+                Some(libc::EAGAIN) | Some(libc::EBUSY) => {
+                    warn!("io_uring submit error, retrying: {}", e);
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                }
+                // Outro erro não passa: os pedidos falham e o serviço para. Os pedidos
+                // seguintes recebem "io_uring service stopped".
+                _ => {
+                    warn!("io_uring submit error, stopping io_uring: {}", e);
+                    eprintln!("io_uring submit error, stopping io_uring: {}", e);
+                    for pedido in em_voo.iter_mut().filter_map(Option::take).chain(fila) {
+                        let _ = pedido
+                            .resposta
+                            .send(Err(io::Error::other(format!("io_uring submit: {}", e))));
+                    }
+                    return;
+                }
+            }
+        }
+        let concluidos: Vec<(u64, i32)> = ring
+            .completion()
+            .map(|cqe| (cqe.user_data(), cqe.result()))
+            .collect();
+        for (user_data, resultado) in concluidos {
+            let index = user_data as usize;
+            if let Some(pedido) = em_voo[index].take() {
+                n_em_voo -= 1;
+                if resultado < 0 {
+                    livres.push(index);
+                    let _ = pedido
+                        .resposta
+                        .send(Err(io::Error::from_raw_os_error(-resultado)));
+                } else {
+                    // Se quem pediu já desistiu, o BufferUring descartado devolve o buffer
+                    let _ = pedido
+                        .resposta
+                        .send(Ok(servico.buffer(index, resultado as usize)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn inicia_servico(_tam_buffer: usize) -> io::Result<ServicoUring> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "io_uring is only available on Linux",
+    ))
+}